@@ -0,0 +1,4 @@
+ALTER TABLE issue_delivery_queue ADD COLUMN n_retries SMALLINT NOT NULL DEFAULT 0;
+ALTER TABLE issue_delivery_queue ADD COLUMN execute_after timestamptz NOT NULL DEFAULT now();
+-- Set once a task has exhausted its retries; it is no longer picked up by the worker.
+ALTER TABLE issue_delivery_queue ADD COLUMN failed_at timestamptz NULL;
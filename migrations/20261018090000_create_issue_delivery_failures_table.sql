@@ -0,0 +1,21 @@
+CREATE TABLE issue_delivery_failures (
+    newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (
+        newsletter_issue_id
+    ),
+    subscriber_email TEXT NOT NULL,
+    n_attempts SMALLINT NOT NULL,
+    last_error TEXT NOT NULL,
+    failed_at timestamptz NOT NULL,
+    PRIMARY KEY (newsletter_issue_id, subscriber_email)
+);
+
+-- Permanently failed tasks used to be parked in the queue: move them over.
+INSERT INTO issue_delivery_failures (
+    newsletter_issue_id, subscriber_email, n_attempts, last_error, failed_at
+)
+SELECT newsletter_issue_id, subscriber_email, n_retries + 1, 'Unknown', failed_at
+FROM issue_delivery_queue
+WHERE failed_at IS NOT NULL;
+
+DELETE FROM issue_delivery_queue WHERE failed_at IS NOT NULL;
+ALTER TABLE issue_delivery_queue DROP COLUMN failed_at;
@@ -0,0 +1,26 @@
+-- Per-recipient delivery log. Unlike `issue_delivery_queue`, rows are kept once
+-- the delivery is over so that we can report on the progress of an issue.
+CREATE TABLE issue_deliveries (
+    newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (
+        newsletter_issue_id
+    ),
+    subscriber_email TEXT NOT NULL,
+    -- One of `queued`, `sent`, `failed` or `skipped`.
+    status TEXT NOT NULL,
+    provider_message_id TEXT NULL,
+    queued_at timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL,
+    PRIMARY KEY (newsletter_issue_id, subscriber_email)
+);
+
+INSERT INTO issue_deliveries (
+    newsletter_issue_id, subscriber_email, status, queued_at, updated_at
+)
+SELECT newsletter_issue_id, subscriber_email, 'queued', now(), now()
+FROM issue_delivery_queue;
+
+INSERT INTO issue_deliveries (
+    newsletter_issue_id, subscriber_email, status, queued_at, updated_at
+)
+SELECT newsletter_issue_id, subscriber_email, 'failed', failed_at, failed_at
+FROM issue_delivery_failures;
@@ -0,0 +1,5 @@
+-- Issues go out once `send_at` has passed, unless they have been cancelled first.
+ALTER TABLE newsletter_issues ADD COLUMN send_at timestamptz NULL;
+UPDATE newsletter_issues SET send_at = published_at::timestamptz;
+ALTER TABLE newsletter_issues ALTER COLUMN send_at SET NOT NULL;
+ALTER TABLE newsletter_issues ADD COLUMN cancelled_at timestamptz NULL;
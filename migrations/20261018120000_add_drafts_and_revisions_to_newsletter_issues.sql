@@ -0,0 +1,27 @@
+-- Issues start as drafts: `published_at` and `send_at` stay NULL until the issue is published.
+ALTER TABLE newsletter_issues
+    ALTER COLUMN published_at TYPE timestamptz USING published_at::timestamptz;
+ALTER TABLE newsletter_issues ALTER COLUMN published_at DROP NOT NULL;
+ALTER TABLE newsletter_issues ALTER COLUMN send_at DROP NOT NULL;
+
+-- Every save of a draft is kept; `newsletter_issues` holds the latest revision.
+CREATE TABLE newsletter_issue_revisions (
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    revision INT NOT NULL,
+    title TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY (newsletter_issue_id, revision)
+);
+INSERT INTO newsletter_issue_revisions (
+    newsletter_issue_id,
+    revision,
+    title,
+    text_content,
+    html_content,
+    created_at
+)
+SELECT newsletter_issue_id, 1, title, text_content, html_content, published_at
+FROM newsletter_issues;
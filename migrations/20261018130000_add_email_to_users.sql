@@ -0,0 +1,2 @@
+-- Where admins receive test sends of newsletter issues.
+ALTER TABLE users ADD COLUMN email TEXT NULL;
@@ -0,0 +1,3 @@
+-- Markdown source both bodies are rendered from, unless the author overrides them.
+ALTER TABLE newsletter_issues ADD COLUMN markdown_content TEXT NOT NULL DEFAULT '';
+ALTER TABLE newsletter_issue_revisions ADD COLUMN markdown_content TEXT NOT NULL DEFAULT '';
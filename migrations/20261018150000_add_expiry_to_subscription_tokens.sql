@@ -0,0 +1,6 @@
+-- Confirmation tokens are only valid for a limited time.
+-- Tokens issued before this migration get a fresh validity window.
+ALTER TABLE subscription_tokens
+    ADD COLUMN created_at timestamptz NOT NULL DEFAULT now(),
+    ADD COLUMN expires_at timestamptz NOT NULL DEFAULT now() + interval '2 days';
+ALTER TABLE subscription_tokens ALTER COLUMN expires_at DROP DEFAULT;
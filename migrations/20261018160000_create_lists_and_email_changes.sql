@@ -0,0 +1,18 @@
+-- Mailing lists subscribers can pick from in their preference center.
+CREATE TABLE lists (
+    list_id uuid NOT NULL,
+    PRIMARY KEY (list_id),
+    name TEXT NOT NULL UNIQUE,
+    is_default BOOLEAN NOT NULL DEFAULT false,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE UNIQUE INDEX lists_single_default ON lists (is_default) WHERE is_default;
+CREATE TABLE list_memberships (
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    list_id uuid NOT NULL REFERENCES lists (list_id) ON DELETE CASCADE,
+    status TEXT NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (subscriber_id, list_id)
+);
+-- A token with a `new_email` confirms a change of address rather than a signup.
+ALTER TABLE subscription_tokens ADD COLUMN new_email TEXT;
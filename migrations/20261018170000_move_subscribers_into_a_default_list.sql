@@ -0,0 +1,19 @@
+-- Everything so far went to a single list: it becomes the default one.
+INSERT INTO lists (list_id, name, is_default)
+VALUES (gen_random_uuid(), 'Newsletter', true)
+ON CONFLICT (name) DO UPDATE SET is_default = true;
+INSERT INTO list_memberships (subscriber_id, list_id, status, created_at)
+SELECT s.id, l.list_id, s.status, s.subscribed_at
+FROM subscriptions s, lists l
+WHERE l.is_default
+ON CONFLICT (subscriber_id, list_id) DO UPDATE SET status = EXCLUDED.status;
+
+-- Drafts pick their list when they are published.
+ALTER TABLE newsletter_issues ADD COLUMN list_id uuid REFERENCES lists (list_id);
+UPDATE newsletter_issues SET list_id = (SELECT list_id FROM lists WHERE is_default)
+WHERE published_at IS NOT NULL;
+
+-- Signup tokens confirm a subscription to one list.
+ALTER TABLE subscription_tokens ADD COLUMN list_id uuid REFERENCES lists (list_id) ON DELETE CASCADE;
+UPDATE subscription_tokens SET list_id = (SELECT list_id FROM lists WHERE is_default)
+WHERE new_email IS NULL;
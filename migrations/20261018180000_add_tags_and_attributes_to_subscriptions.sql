@@ -0,0 +1,8 @@
+-- Free-form labels and custom fields used to target a segment of a list.
+ALTER TABLE subscriptions
+    ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN attributes JSONB NOT NULL DEFAULT '{}';
+CREATE INDEX subscriptions_tags ON subscriptions USING GIN (tags);
+
+-- The segment filter an issue was published with, if any.
+ALTER TABLE newsletter_issues ADD COLUMN segment TEXT;
@@ -0,0 +1,26 @@
+-- CSV uploads of subscribers, processed row by row in the background.
+CREATE TABLE subscriber_imports (
+    import_id uuid PRIMARY KEY,
+    list_id uuid NOT NULL REFERENCES lists (list_id) ON DELETE CASCADE,
+    file_name TEXT NOT NULL,
+    -- Import the rows as confirmed subscribers rather than ask them to confirm.
+    confirmed BOOLEAN NOT NULL,
+    consent_source TEXT,
+    created_at timestamptz NOT NULL
+);
+
+CREATE TABLE subscriber_import_rows (
+    import_id uuid NOT NULL REFERENCES subscriber_imports (import_id) ON DELETE CASCADE,
+    line INT NOT NULL,
+    email TEXT NOT NULL,
+    name TEXT NOT NULL,
+    -- 'pending', 'imported', 'skipped', 'invalid' or 'failed'.
+    status TEXT NOT NULL,
+    message TEXT,
+    PRIMARY KEY (import_id, line)
+);
+CREATE INDEX subscriber_import_rows_pending ON subscriber_import_rows (import_id)
+WHERE status = 'pending';
+
+-- How consent was obtained for subscribers who did not sign up through our form.
+ALTER TABLE subscriptions ADD COLUMN consent_source TEXT;
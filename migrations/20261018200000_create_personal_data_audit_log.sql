@@ -0,0 +1,13 @@
+-- Access and erasure requests about a person's data. The address itself is
+-- only kept as a hash, so that erasing it does not leave it behind here.
+CREATE TABLE personal_data_audit_log (
+    entry_id uuid PRIMARY KEY,
+    -- 'export' or 'erasure'.
+    action TEXT NOT NULL,
+    email_hash TEXT NOT NULL,
+    -- Who made the request: an admin's username, or 'subscriber' for self-service.
+    requested_by TEXT NOT NULL,
+    details TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
+CREATE INDEX personal_data_audit_log_email_hash ON personal_data_audit_log (email_hash);
@@ -0,0 +1,10 @@
+-- Addresses we must never email again, whatever their subscription says.
+CREATE TABLE suppressions (
+    email TEXT NOT NULL,
+    -- 'hard_bounce', 'spam_complaint' or 'do_not_contact'.
+    reason TEXT NOT NULL,
+    -- Where the suppression came from, e.g. an admin's username or a webhook.
+    source TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
+CREATE UNIQUE INDEX suppressions_email ON suppressions (lower(email));
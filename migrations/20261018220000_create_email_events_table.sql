@@ -0,0 +1,15 @@
+-- What the email provider told us about the emails we sent.
+CREATE TABLE email_events (
+    event_id uuid PRIMARY KEY,
+    -- 'bounce', 'spam_complaint' or 'delivery'.
+    kind TEXT NOT NULL,
+    -- The provider's own classification, e.g. `HardBounce` or `SoftBounce`.
+    provider_type TEXT,
+    email TEXT NOT NULL,
+    provider_message_id TEXT,
+    occurred_at timestamptz,
+    payload JSONB NOT NULL,
+    received_at timestamptz NOT NULL
+);
+CREATE INDEX email_events_email ON email_events (lower(email));
+CREATE INDEX email_events_provider_message_id ON email_events (provider_message_id);
@@ -0,0 +1,14 @@
+-- Suppressions are matched on a hash of the address, so that the address
+-- itself can be forgotten when its owner's data is erased.
+ALTER TABLE suppressions ADD COLUMN email_hash TEXT;
+UPDATE suppressions SET email_hash = encode(sha256(convert_to(lower(email), 'UTF8')), 'hex');
+ALTER TABLE suppressions ALTER COLUMN email_hash SET NOT NULL;
+ALTER TABLE suppressions ALTER COLUMN email DROP NOT NULL;
+DROP INDEX suppressions_email;
+CREATE UNIQUE INDEX suppressions_email_hash ON suppressions (email_hash);
+-- Forget the addresses that have been erased already.
+UPDATE suppressions s SET email = NULL
+WHERE EXISTS (
+    SELECT 1 FROM personal_data_audit_log l
+    WHERE l.action = 'erasure' AND l.email_hash = s.email_hash
+);
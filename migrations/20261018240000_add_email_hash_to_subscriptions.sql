@@ -0,0 +1,4 @@
+-- The hash suppressions are matched on. It is only ever computed by the
+-- application, which fills it in for existing subscribers on startup.
+ALTER TABLE subscriptions ADD COLUMN email_hash TEXT;
+CREATE INDEX subscriptions_email_hash ON subscriptions (email_hash);
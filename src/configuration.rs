@@ -1,7 +1,13 @@
 use crate::domain::SubscriberEmail;
-use crate::email_client::EmailClient;
+use crate::email_client::{EmailClient, SmtpTlsMode};
+use anyhow::Context;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::PoolConfig;
+use lettre::{AsyncSmtpTransport, Tokio1Executor};
 use secrecy::{ExposeSecret, Secret};
-use serde_aux::field_attributes::deserialize_number_from_string;
+use serde_aux::field_attributes::{
+    deserialize_number_from_string, deserialize_option_number_from_string,
+};
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
 use std::convert::{TryFrom, TryInto};
 
@@ -10,6 +16,9 @@ pub struct Settings {
     pub database: DatabaseSettings,
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
+    pub issue_delivery: IssueDeliverySettings,
+    pub subscriptions: SubscriptionSettings,
+    pub webhooks: WebhookSettings,
     pub redis_uri: Secret<String>,
 }
 
@@ -77,29 +86,81 @@ pub struct EmailProviderSMTPSettings {
     username: Option<String>,
     password: Secret<String>,
     smtp_server: String,
+    /// Defaults to the usual port for the TLS mode.
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    port: Option<u16>,
+    #[serde(default)]
+    tls: SmtpTlsMode,
+    /// How many connections to the server are kept open at most.
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pool_size: Option<u32>,
+}
+
+impl EmailProviderSMTPSettings {
+    /// The mailer is built once and reused, so that connections can be pooled.
+    /// It must be built from within a Tokio runtime.
+    pub fn mailer(
+        &self,
+        sender: &SubscriberEmail,
+        timeout: std::time::Duration,
+    ) -> Result<AsyncSmtpTransport<Tokio1Executor>, lettre::transport::smtp::Error> {
+        let mut builder = match self.tls {
+            SmtpTlsMode::Implicit => {
+                AsyncSmtpTransport::<Tokio1Executor>::relay(&self.smtp_server)?
+            }
+            SmtpTlsMode::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.smtp_server)?
+            }
+            SmtpTlsMode::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.smtp_server)
+            }
+        };
+        if let Some(port) = self.port {
+            builder = builder.port(port);
+        }
+        let mut pool_config = PoolConfig::new();
+        if let Some(pool_size) = self.pool_size {
+            pool_config = pool_config.max_size(pool_size);
+        }
+        // For providers such as Gmail the username is the sender's address.
+        let username = match &self.username {
+            None => sender.to_string(),
+            Some(username) => username.clone(),
+        };
+        Ok(builder
+            .credentials(Credentials::new(
+                username,
+                self.password.expose_secret().to_owned(),
+            ))
+            .timeout(Some(timeout))
+            .pool_config(pool_config)
+            .build())
+    }
 }
 
 impl EmailClientSettings {
-    pub fn client(self) -> EmailClient {
-        let sender_email = self.sender().expect("Invalid sender email address.");
+    pub fn client(self) -> Result<EmailClient, anyhow::Error> {
+        let sender_email = self
+            .sender()
+            .map_err(|e| anyhow::anyhow!(e))
+            .context("Invalid sender email address.")?;
         let timeout = self.timeout();
 
-        match self.kind {
+        let client = match self.kind {
             KindEmailProviderSettings::URL(kind) => EmailClient::new_url(
                 kind.base_url,
                 sender_email,
                 kind.authorization_token,
                 timeout,
             ),
-            KindEmailProviderSettings::SMTP(kind) => EmailClient::new_smtp(
-                sender_email,
-                timeout,
-                kind.name,
-                kind.username,
-                kind.password,
-                kind.smtp_server,
-            ),
-        }
+            KindEmailProviderSettings::SMTP(kind) => {
+                let mailer = kind
+                    .mailer(&sender_email, timeout)
+                    .context("Invalid SMTP settings.")?;
+                EmailClient::new_smtp(sender_email, timeout, kind.name, mailer)
+            }
+        };
+        Ok(client)
     }
 
     pub fn sender(&self) -> Result<SubscriberEmail, String> {
@@ -111,6 +172,74 @@ impl EmailClientSettings {
     }
 }
 
+#[derive(serde::Deserialize, Clone)]
+pub struct IssueDeliverySettings {
+    /// How many times a failed delivery is retried before giving up on it.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_retries: i16,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub backoff_base_seconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub backoff_max_seconds: u64,
+}
+
+impl IssueDeliverySettings {
+    pub fn backoff_base(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.backoff_base_seconds)
+    }
+
+    pub fn backoff_max(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.backoff_max_seconds)
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct SubscriptionSettings {
+    /// How long a confirmation link stays valid.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub confirmation_token_ttl_hours: u32,
+    /// How long an unconfirmed subscriber is kept before being purged.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub pending_subscriber_ttl_hours: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub purge_interval_seconds: u64,
+    /// How long the preference center link of an email stays valid.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub preferences_link_ttl_hours: u32,
+    /// How long the link to download or erase personal data stays valid.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub personal_data_link_ttl_minutes: u32,
+}
+
+impl SubscriptionSettings {
+    pub fn confirmation_token_ttl(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.confirmation_token_ttl_hours.into())
+    }
+
+    pub fn pending_subscriber_ttl(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.pending_subscriber_ttl_hours.into())
+    }
+
+    pub fn purge_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.purge_interval_seconds)
+    }
+
+    pub fn preferences_link_ttl(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.preferences_link_ttl_hours.into())
+    }
+
+    pub fn personal_data_link_ttl(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.personal_data_link_ttl_minutes.into())
+    }
+}
+
+/// The HTTP Basic credentials the email provider uses to call our webhooks.
+#[derive(serde::Deserialize, Clone)]
+pub struct WebhookSettings {
+    pub username: String,
+    pub password: Secret<String>,
+}
+
 pub fn get_configuration() -> Result<Settings, config::ConfigError> {
     let base_path = std::env::current_dir().expect("Failed to determine the current directory");
     let configuration_directory = base_path.join("configuration");
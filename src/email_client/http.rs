@@ -1,160 +1,62 @@
-use crate::domain::SubscriberEmail;
-use anyhow::Context;
-use lettre::message::MultiPart;
-use lettre::{
-    message::Mailbox, transport::smtp::authentication::Credentials, Message, SmtpTransport,
-    Transport,
-};
+use super::{EmailTransport, OutgoingEmail};
+use async_trait::async_trait;
 use reqwest::Client;
 use secrecy::{ExposeSecret, Secret};
 
-pub struct EmailClient {
-    sender: SubscriberEmail,
-    timeout: std::time::Duration,
-    kind_email_provider: KindEmailProvider,
-}
-
-pub enum KindEmailProvider {
-    URL(EmailProviderURL),
-    SMTP(EmailProviderSMTP),
-}
-
-pub struct EmailProviderURL {
+/// Sends emails through a Postmark-style HTTP API.
+pub struct HttpEmailTransport {
     http_client: Client,
     base_url: String,
     authorization_token: Secret<String>,
 }
 
-pub struct EmailProviderSMTP {
-    /// The name to put on outgoing emails
-    name: Option<String>,
-    /// The username to use to log into the SMTP server, if not provided [`EmailClient::sender`] is
-    /// used (eg. For Gmail they are the same and this can be None)
-    username: Option<String>,
-    password: Secret<String>,
-    smtp_server: String,
-}
-
-impl EmailClient {
+impl HttpEmailTransport {
     pub fn new(
-        sender: SubscriberEmail,
-        timeout: std::time::Duration,
-        kind_email_provider: KindEmailProvider,
-    ) -> Self {
-        Self {
-            sender,
-            timeout,
-            kind_email_provider,
-        }
-    }
-
-    pub fn new_url(
         base_url: String,
-        sender: SubscriberEmail,
         authorization_token: Secret<String>,
         timeout: std::time::Duration,
     ) -> Self {
         let http_client = Client::builder().timeout(timeout).build().unwrap();
-        let kind_email_provider = EmailProviderURL {
+        Self {
             http_client,
             base_url,
             authorization_token,
-        };
-        Self {
-            sender,
-            timeout,
-            kind_email_provider: KindEmailProvider::URL(kind_email_provider),
         }
     }
+}
 
-    pub fn new_smtp(
-        sender: SubscriberEmail,
-        timeout: std::time::Duration,
-        name: Option<String>,
-        username: Option<String>,
-        password: Secret<String>,
-        smtp_server: String,
-    ) -> Self {
-        let kind_email_provider = EmailProviderSMTP {
-            name,
-            username,
-            password,
-            smtp_server,
+#[async_trait]
+impl EmailTransport for HttpEmailTransport {
+    async fn send(&self, email: &OutgoingEmail<'_>) -> Result<Option<String>, anyhow::Error> {
+        let url = format!("{}/email", self.base_url);
+        let request_body = SendEmailRequest {
+            from: email.sender.as_ref(),
+            to: email.recipient.as_ref(),
+            subject: email.subject,
+            html_body: email.html_content,
+            text_body: email.text_content,
+            headers: email
+                .headers
+                .iter()
+                .map(|&(name, value)| EmailHeader { name, value })
+                .collect(),
         };
-        Self {
-            sender,
-            timeout,
-            kind_email_provider: KindEmailProvider::SMTP(kind_email_provider),
-        }
-    }
-
-    pub async fn send_email(
-        &self,
-        recipient: &SubscriberEmail,
-        subject: &str,
-        html_content: &str,
-        text_content: &str,
-    ) -> Result<(), anyhow::Error> {
-        match &self.kind_email_provider {
-            KindEmailProvider::URL(kind_url) => {
-                let url = format!("{}/email", kind_url.base_url);
-                let request_body = SendEmailRequest {
-                    from: self.sender.as_ref(),
-                    to: recipient.as_ref(),
-                    subject,
-                    html_body: html_content,
-                    text_body: text_content,
-                };
-                kind_url
-                    .http_client
-                    .post(&url)
-                    .header(
-                        "X-Postmark-Server-Token",
-                        kind_url.authorization_token.expose_secret(),
-                    )
-                    .json(&request_body)
-                    .send()
-                    .await?
-                    .error_for_status()?;
-            }
-            KindEmailProvider::SMTP(kind_smtp) => {
-                let from = Mailbox {
-                    name: kind_smtp.name.to_owned(),
-                    email: self
-                        .sender
-                        .as_ref()
-                        .parse()
-                        .context("Failed to parse email address")?,
-                };
-
-                let email = Message::builder()
-                    .from(from)
-                    .to(recipient.as_ref().parse()?)
-                    .subject(subject)
-                    .multipart(MultiPart::alternative_plain_html(
-                        String::from(text_content),
-                        String::from(html_content),
-                    ))?;
-
-                let username = match &kind_smtp.username {
-                    None => self.sender.to_string(),
-                    Some(username) => username.clone(),
-                };
-
-                let mailer = SmtpTransport::relay(&kind_smtp.smtp_server)?
-                    .credentials(Credentials::new(
-                        username,
-                        kind_smtp.password.expose_secret().to_owned(),
-                    ))
-                    .timeout(Some(self.timeout))
-                    .build();
-
-                // Sends the email
-                mailer.send(&email)?;
-            }
-        }
-
-        Ok(())
+        let response = self
+            .http_client
+            .post(&url)
+            .header(
+                "X-Postmark-Server-Token",
+                self.authorization_token.expose_secret(),
+            )
+            .json(&request_body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response
+            .json::<SendEmailResponse>()
+            .await
+            .ok()
+            .and_then(|r| r.message_id))
     }
 }
 
@@ -166,6 +68,21 @@ struct SendEmailRequest<'a> {
     subject: &'a str,
     html_body: &'a str,
     text_body: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    headers: Vec<EmailHeader<'a>>,
+}
+
+#[derive(serde::Deserialize)]
+struct SendEmailResponse {
+    #[serde(rename = "MessageID")]
+    message_id: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct EmailHeader<'a> {
+    name: &'a str,
+    value: &'a str,
 }
 
 #[cfg(test)]
@@ -197,6 +114,23 @@ mod tests {
         }
     }
 
+    struct EmailHeaderMatcher(&'static str, &'static str);
+
+    impl wiremock::Match for EmailHeaderMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+            if let Ok(body) = result {
+                body["Headers"].as_array().is_some_and(|headers| {
+                    headers
+                        .iter()
+                        .any(|h| h["Name"] == self.0 && h["Value"] == self.1)
+                })
+            } else {
+                false
+            }
+        }
+    }
+
     /// Generate a random email subject
     fn subject() -> String {
         Sentence(1..2).fake()
@@ -246,6 +180,67 @@ mod tests {
         // Assert
     }
 
+    #[tokio::test]
+    async fn send_email_with_headers_forwards_them_to_the_provider() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(SendEmailBodyMatcher)
+            .and(EmailHeaderMatcher(
+                "List-Unsubscribe-Post",
+                "List-Unsubscribe=One-Click",
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let _ = email_client
+            .send_email_with_headers(
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+                &[("List-Unsubscribe-Post", "List-Unsubscribe=One-Click")],
+            )
+            .await;
+
+        // Assert
+    }
+
+    #[tokio::test]
+    async fn send_email_with_headers_returns_the_provider_message_id() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        let response = ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "To": "receiver@example.com",
+            "SubmittedAt": "2014-02-17T07:25:01.4178645-05:00",
+            "MessageID": "0a129aee-e1cd-480d-b08d-4f48548ff48d",
+            "ErrorCode": 0,
+            "Message": "OK"
+        }));
+        Mock::given(any())
+            .respond_with(response)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email_with_headers(&email(), &subject(), &content(), &content(), &[])
+            .await;
+
+        // Assert
+        assert_eq!(
+            assert_ok!(outcome).as_deref(),
+            Some("0a129aee-e1cd-480d-b08d-4f48548ff48d")
+        );
+    }
+
     #[tokio::test]
     async fn send_email_succeeds_if_the_server_returns_200() {
         // Arrange
@@ -0,0 +1,173 @@
+mod http;
+mod smtp;
+
+pub use http::HttpEmailTransport;
+pub use smtp::{SmtpEmailTransport, SmtpTlsMode};
+
+use crate::domain::SubscriberEmail;
+use async_trait::async_trait;
+use lettre::{AsyncSmtpTransport, Tokio1Executor};
+use secrecy::Secret;
+use std::sync::Arc;
+
+/// An email, ready to be handed over to an [`EmailTransport`].
+pub struct OutgoingEmail<'a> {
+    pub sender: &'a SubscriberEmail,
+    pub recipient: &'a SubscriberEmail,
+    pub subject: &'a str,
+    pub html_content: &'a str,
+    pub text_content: &'a str,
+    /// Extra `(name, value)` headers to attach to the message.
+    pub headers: &'a [(&'a str, &'a str)],
+}
+
+/// A way of getting emails out of the door: an email provider's API, an SMTP
+/// relay, or a test double.
+#[async_trait]
+pub trait EmailTransport: Send + Sync {
+    /// Returns the id the provider assigned to the message, if it told us.
+    async fn send(&self, email: &OutgoingEmail<'_>) -> Result<Option<String>, anyhow::Error>;
+}
+
+#[derive(Clone)]
+pub struct EmailClient {
+    sender: SubscriberEmail,
+    transport: Arc<dyn EmailTransport>,
+}
+
+impl EmailClient {
+    pub fn new(sender: SubscriberEmail, transport: Arc<dyn EmailTransport>) -> Self {
+        Self { sender, transport }
+    }
+
+    pub fn new_url(
+        base_url: String,
+        sender: SubscriberEmail,
+        authorization_token: Secret<String>,
+        timeout: std::time::Duration,
+    ) -> Self {
+        let transport = HttpEmailTransport::new(base_url, authorization_token, timeout);
+        Self::new(sender, Arc::new(transport))
+    }
+
+    pub fn new_smtp(
+        sender: SubscriberEmail,
+        timeout: std::time::Duration,
+        name: Option<String>,
+        mailer: AsyncSmtpTransport<Tokio1Executor>,
+    ) -> Self {
+        let transport = SmtpEmailTransport::new(name, mailer, timeout);
+        Self::new(sender, Arc::new(transport))
+    }
+
+    pub async fn send_email(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.send_email_with_headers(recipient, subject, html_content, text_content, &[])
+            .await?;
+        Ok(())
+    }
+
+    /// Same as [`EmailClient::send_email`], with extra `(name, value)` headers
+    /// attached to the outgoing message.
+    ///
+    /// Returns the id the provider assigned to the message, if it told us.
+    pub async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<Option<String>, anyhow::Error> {
+        self.transport
+            .send(&OutgoingEmail {
+                sender: &self.sender,
+                recipient,
+                subject,
+                html_content,
+                text_content,
+                headers,
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EmailClient, EmailTransport, OutgoingEmail};
+    use crate::domain::SubscriberEmail;
+    use async_trait::async_trait;
+    use claims::assert_ok;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, PartialEq)]
+    struct SentEmail {
+        sender: String,
+        recipient: String,
+        headers: Vec<(String, String)>,
+    }
+
+    /// Keeps the emails it is given instead of sending them.
+    #[derive(Default)]
+    struct RecordingTransport {
+        sent: Mutex<Vec<SentEmail>>,
+    }
+
+    #[async_trait]
+    impl EmailTransport for RecordingTransport {
+        async fn send(&self, email: &OutgoingEmail<'_>) -> Result<Option<String>, anyhow::Error> {
+            let headers = email
+                .headers
+                .iter()
+                .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+                .collect();
+            self.sent.lock().unwrap().push(SentEmail {
+                sender: email.sender.to_string(),
+                recipient: email.recipient.to_string(),
+                headers,
+            });
+            Ok(Some("recorded".into()))
+        }
+    }
+
+    fn address(s: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(s.into()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn emails_are_handed_over_to_the_transport_from_the_configured_sender() {
+        // Arrange
+        let transport = Arc::new(RecordingTransport::default());
+        let email_client = EmailClient::new(address("sender@example.com"), transport.clone());
+
+        // Act
+        let outcome = email_client
+            .send_email_with_headers(
+                &address("ursula@example.com"),
+                "Subject",
+                "<p>Hi!</p>",
+                "Hi!",
+                &[("List-Unsubscribe-Post", "List-Unsubscribe=One-Click")],
+            )
+            .await;
+
+        // Assert
+        assert_eq!(assert_ok!(outcome).as_deref(), Some("recorded"));
+        assert_eq!(
+            *transport.sent.lock().unwrap(),
+            vec![SentEmail {
+                sender: "sender@example.com".into(),
+                recipient: "ursula@example.com".into(),
+                headers: vec![(
+                    "List-Unsubscribe-Post".into(),
+                    "List-Unsubscribe=One-Click".into()
+                )],
+            }]
+        );
+    }
+}
@@ -0,0 +1,266 @@
+use super::{EmailTransport, OutgoingEmail};
+use anyhow::Context;
+use async_trait::async_trait;
+use lettre::message::header::{HeaderName, HeaderValue};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::response::Response;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+/// How the connection to the SMTP server is secured.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTlsMode {
+    /// TLS from the first byte, usually on port 465.
+    #[default]
+    Implicit,
+    /// A plain connection upgraded with STARTTLS, usually on port 587.
+    StartTls,
+    /// No encryption at all. Only meant for relays on the local network.
+    None,
+}
+
+/// Sends emails through an SMTP relay.
+///
+/// Connections are pooled by the underlying mailer, so that we don't go
+/// through the TLS handshake for every email.
+pub struct SmtpEmailTransport {
+    /// The name to put on outgoing emails
+    name: Option<String>,
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+    /// The mailer's own timeout only covers opening a connection, this one
+    /// covers the whole exchange.
+    timeout: std::time::Duration,
+}
+
+impl SmtpEmailTransport {
+    pub fn new(
+        name: Option<String>,
+        mailer: AsyncSmtpTransport<Tokio1Executor>,
+        timeout: std::time::Duration,
+    ) -> Self {
+        Self {
+            name,
+            mailer,
+            timeout,
+        }
+    }
+}
+
+#[async_trait]
+impl EmailTransport for SmtpEmailTransport {
+    async fn send(&self, email: &OutgoingEmail<'_>) -> Result<Option<String>, anyhow::Error> {
+        let from = Mailbox {
+            name: self.name.to_owned(),
+            email: email
+                .sender
+                .as_ref()
+                .parse()
+                .context("Failed to parse email address")?,
+        };
+
+        let mut builder = Message::builder()
+            .from(from)
+            .to(email.recipient.as_ref().parse()?)
+            .subject(email.subject);
+        for (name, value) in email.headers {
+            let name = HeaderName::new_from_ascii(name.to_string())
+                .context("Invalid email header name")?;
+            builder = builder.raw_header(HeaderValue::new(name, value.to_string()));
+        }
+        let message = builder.multipart(MultiPart::alternative_plain_html(
+            String::from(email.text_content),
+            String::from(email.html_content),
+        ))?;
+
+        let response = tokio::time::timeout(self.timeout, self.mailer.send(message))
+            .await
+            .context("Timed out while talking to the SMTP server")??;
+        Ok(smtp_queue_id(&response))
+    }
+}
+
+/// Extract the queue id from an SMTP reply such as `250 2.0.0 Ok: queued as 4ABC123`.
+fn smtp_queue_id(response: &Response) -> Option<String> {
+    response.message().find_map(|line| {
+        line.split_once("queued as ")
+            .and_then(|(_, id)| id.split_whitespace().next())
+            .map(String::from)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::EmailClient;
+    use claims::{assert_err, assert_ok};
+    use fake::faker::internet::en::SafeEmail;
+    use fake::Fake;
+    use lettre::transport::smtp::authentication::Credentials;
+    use lettre::transport::smtp::PoolConfig;
+    use lettre::{AsyncSmtpTransport, Tokio1Executor};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// A bare-bones SMTP server that accepts every message it is given.
+    #[derive(Clone, Default)]
+    struct FakeSmtpServer {
+        connections: Arc<AtomicUsize>,
+        messages: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl FakeSmtpServer {
+        /// Start listening on a random port, greeting clients only if `greet` is set.
+        async fn start(&self, greet: bool) -> u16 {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let server = self.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    server.connections.fetch_add(1, Ordering::SeqCst);
+                    if greet {
+                        tokio::spawn(server.clone().serve(stream));
+                    } else {
+                        // Hold on to the connection without ever answering.
+                        tokio::spawn(async move {
+                            let _stream = stream;
+                            std::future::pending::<()>().await
+                        });
+                    }
+                }
+            });
+            port
+        }
+
+        async fn serve(self, stream: TcpStream) -> std::io::Result<()> {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            writer.write_all(b"220 fake.smtp ESMTP\r\n").await?;
+            while let Some(line) = lines.next_line().await? {
+                let reply = match line.split_whitespace().next().unwrap_or_default() {
+                    "EHLO" => "250-fake.smtp\r\n250 AUTH PLAIN LOGIN\r\n".to_string(),
+                    "AUTH" => "235 2.7.0 Authentication successful\r\n".to_string(),
+                    "DATA" => {
+                        writer
+                            .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                            .await?;
+                        let mut message = String::new();
+                        while let Some(line) = lines.next_line().await? {
+                            if line == "." {
+                                break;
+                            }
+                            message.push_str(&line);
+                            message.push('\n');
+                        }
+                        let mut messages = self.messages.lock().unwrap();
+                        messages.push(message);
+                        format!("250 2.0.0 Ok: queued as QUEUE{}\r\n", messages.len())
+                    }
+                    "QUIT" => {
+                        writer.write_all(b"221 2.0.0 Bye\r\n").await?;
+                        return Ok(());
+                    }
+                    _ => "250 2.0.0 Ok\r\n".to_string(),
+                };
+                writer.write_all(reply.as_bytes()).await?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Connections go back to the pool in a background task. With the clock
+    /// paused, the runtime only moves it forward once no task has anything
+    /// left to do, so the sleep returns once the connection is in the pool,
+    /// however slow the machine is.
+    async fn wait_for_the_connection_to_be_pooled() {
+        tokio::time::pause();
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        tokio::time::resume();
+    }
+
+    /// Generate a random subscriber email
+    fn email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    /// Get a test instance of `EmailClient`, talking to a local server in plain text.
+    fn email_client(port: u16) -> EmailClient {
+        let mailer = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous("127.0.0.1")
+            .port(port)
+            .credentials(Credentials::new("username".into(), "password".into()))
+            .timeout(Some(std::time::Duration::from_millis(200)))
+            .pool_config(PoolConfig::new().max_size(1))
+            .build();
+        EmailClient::new_smtp(
+            email(),
+            std::time::Duration::from_millis(200),
+            Some("Newsletter".into()),
+            mailer,
+        )
+    }
+
+    #[tokio::test]
+    async fn send_email_hands_the_message_over_and_returns_the_queue_id() {
+        // Arrange
+        let server = FakeSmtpServer::default();
+        let email_client = email_client(server.start(true).await);
+
+        // Act
+        let outcome = email_client
+            .send_email_with_headers(
+                &email(),
+                "Newsletter title",
+                "<p>Newsletter body as HTML</p>",
+                "Newsletter body as plain text",
+                &[("List-Unsubscribe-Post", "List-Unsubscribe=One-Click")],
+            )
+            .await;
+
+        // Assert
+        assert_eq!(assert_ok!(outcome).as_deref(), Some("QUEUE1"));
+        let messages = server.messages.lock().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("Subject: Newsletter title"));
+        assert!(messages[0].contains("List-Unsubscribe-Post: List-Unsubscribe=One-Click"));
+        assert!(messages[0].contains("Newsletter body as plain text"));
+    }
+
+    #[tokio::test]
+    async fn connections_are_reused_across_emails() {
+        // Arrange
+        let server = FakeSmtpServer::default();
+        let email_client = email_client(server.start(true).await);
+
+        // Act
+        for _ in 0..3 {
+            let outcome = email_client
+                .send_email(&email(), "Subject", "<p>Content</p>", "Content")
+                .await;
+            assert_ok!(outcome);
+            wait_for_the_connection_to_be_pooled().await;
+        }
+
+        // Assert
+        assert_eq!(server.messages.lock().unwrap().len(), 3);
+        assert_eq!(server.connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn send_email_times_out_if_the_server_takes_too_long() {
+        // Arrange
+        let server = FakeSmtpServer::default();
+        let email_client = email_client(server.start(false).await);
+
+        // Act
+        let outcome = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            email_client.send_email(&email(), "Subject", "<p>Content</p>", "Content"),
+        )
+        .await
+        .expect("The SMTP timeout was not honoured.");
+
+        // Assert
+        assert_err!(outcome);
+    }
+}
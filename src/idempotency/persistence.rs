@@ -119,7 +119,13 @@ pub async fn try_processing(
         user_id,
         idempotency_key.as_ref()
     );
-    let n_inserted_rows = transaction.execute(query).await?.rows_affected();
+    let n_inserted_rows = match transaction.execute(query).await {
+        Ok(outcome) => outcome.rows_affected(),
+        // Under repeatable read, a concurrent request for the same key that
+        // committed while we were waiting surfaces as a serialization failure.
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("40001") => 0,
+        Err(e) => return Err(e.into()),
+    };
     if n_inserted_rows > 0 {
         Ok(NextAction::StartProcessing(transaction))
     } else {
@@ -1,6 +1,12 @@
-use crate::configuration::Settings;
+use crate::configuration::{IssueDeliverySettings, Settings, SubscriptionSettings};
 use crate::email_client::EmailClient;
+use crate::merge_tags::{self, MergeFields};
+use crate::routes::{archive_link, is_archived, preferences_link, unsubscribe_link};
+use crate::suppression::is_suppressed;
 use crate::{domain::SubscriberEmail, startup::get_connection_pool};
+use anyhow::Context;
+use rand::Rng;
+use secrecy::Secret;
 use sqlx::{Executor, PgPool, Postgres, Transaction};
 use std::time::Duration;
 use tracing::{field::display, Span};
@@ -15,41 +21,139 @@ pub enum ExecutionOutcome {
     skip_all,
     fields(
         newsletter_issue_id=tracing::field::Empty,
-        subscriber_email=tracing::field::Empty
+        subscriber_email=tracing::field::Empty,
+        n_retries=tracing::field::Empty
     ),
     err
 )]
 pub async fn try_execute_task(
     pool: &PgPool,
     email_client: &EmailClient,
+    base_url: &str,
+    hmac_secret: &Secret<String>,
+    settings: &IssueDeliverySettings,
+    subscription_settings: &SubscriptionSettings,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
     let task = dequeue_task(pool).await?;
     if task.is_none() {
         return Ok(ExecutionOutcome::EmptyQueue);
     }
-    let (transaction, issue_id, email) = task.unwrap();
+    let (mut transaction, issue_id, email, n_retries) = task.unwrap();
     Span::current()
         .record("newsletter_issue_id", display(issue_id))
-        .record("subscriber_email", display(&email));
+        .record("subscriber_email", display(&email))
+        .record("n_retries", display(n_retries));
+
+    let subscriber = match get_confirmed_subscriber(pool, issue_id, &email).await? {
+        Some(subscriber) => subscriber,
+        None => {
+            tracing::info!("Skipping a subscriber who is no longer confirmed.");
+            update_delivery_status(&mut transaction, issue_id, &email, "skipped", None).await?;
+            delete_task(transaction, issue_id, &email).await?;
+            return Ok(ExecutionOutcome::TaskCompleted);
+        }
+    };
+    // The address may have been suppressed since the issue was published.
+    if is_suppressed(pool, &email).await? {
+        tracing::info!("Skipping a suppressed subscriber.");
+        update_delivery_status(&mut transaction, issue_id, &email, "skipped", None).await?;
+        delete_task(transaction, issue_id, &email).await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
 
     match SubscriberEmail::parse(email.clone()) {
         Ok(email) => {
-            let issue = get_issue(pool, issue_id).await?;
-            if let Err(e) = email_client
-                .send_email(
+            let mut issue = get_issue(pool, issue_id)
+                .await?
+                .context("The newsletter issue does not exist.")?;
+            let unsubscribe_link = unsubscribe_link(
+                base_url,
+                subscriber.id,
+                Some(subscriber.list_id),
+                hmac_secret,
+            );
+            issue.personalize(&MergeFields {
+                name: &subscriber.name,
+                email: email.as_ref(),
+                unsubscribe_url: &unsubscribe_link,
+            });
+            let archive_link = is_archived(pool, issue_id)
+                .await?
+                .then(|| archive_link(base_url, issue_id));
+            issue.add_footer(&FooterLinks {
+                archive: archive_link.as_deref(),
+                preferences: &preferences_link(
+                    base_url,
+                    subscriber.id,
+                    subscription_settings.preferences_link_ttl(),
+                    hmac_secret,
+                ),
+                unsubscribe: &unsubscribe_link,
+            });
+            // RFC 8058 one-click unsubscribe, required by bulk-sender rules.
+            let list_unsubscribe = format!("<{}>", unsubscribe_link);
+            let headers = [
+                ("List-Unsubscribe", list_unsubscribe.as_str()),
+                ("List-Unsubscribe-Post", "List-Unsubscribe=One-Click"),
+            ];
+            match email_client
+                .send_email_with_headers(
                     &email,
                     &issue.title,
                     &issue.html_content,
                     &issue.text_content,
+                    &headers,
                 )
                 .await
             {
-                tracing::error!(
-                    error.cause_chain = ?e,
-                    error.message = %e,
-                    "Failed to deliver issue to a confirmed subscriber. \
-                        Skipping.",
-                );
+                Ok(message_id) => {
+                    update_delivery_status(
+                        &mut transaction,
+                        issue_id,
+                        email.as_ref(),
+                        "sent",
+                        message_id.as_deref(),
+                    )
+                    .await?;
+                }
+                Err(e) => {
+                    if n_retries >= settings.max_retries {
+                        tracing::error!(
+                            error.cause_chain = ?e,
+                            error.message = %e,
+                            "Failed to deliver issue to a confirmed subscriber. \
+                            Giving up.",
+                        );
+                        update_delivery_status(
+                            &mut transaction,
+                            issue_id,
+                            email.as_ref(),
+                            "failed",
+                            None,
+                        )
+                        .await?;
+                        move_task_to_failures(
+                            transaction,
+                            issue_id,
+                            email.as_ref(),
+                            n_retries + 1,
+                            &e,
+                        )
+                        .await?;
+                    } else {
+                        let delay =
+                            backoff(n_retries, settings.backoff_base(), settings.backoff_max());
+                        tracing::warn!(
+                            error.cause_chain = ?e,
+                            error.message = %e,
+                            "Failed to deliver issue to a confirmed subscriber. \
+                            Retrying in {:?}.",
+                            delay
+                        );
+                        reschedule_task(transaction, issue_id, email.as_ref(), delay).await?;
+                    }
+                    return Ok(ExecutionOutcome::TaskCompleted);
+                }
             }
         }
         Err(e) => {
@@ -59,6 +163,7 @@ pub async fn try_execute_task(
                 "Skipping a confirmed subscriber. \
                 Their stored contact details are invalid",
             );
+            update_delivery_status(&mut transaction, issue_id, &email, "skipped", None).await?;
         }
     }
     delete_task(transaction, issue_id, &email).await?;
@@ -70,13 +175,15 @@ type PgTransaction = Transaction<'static, Postgres>;
 #[tracing::instrument(skip_all)]
 async fn dequeue_task(
     pool: &PgPool,
-) -> Result<Option<(PgTransaction, Uuid, String)>, anyhow::Error> {
+) -> Result<Option<(PgTransaction, Uuid, String, i16)>, anyhow::Error> {
     let mut transaction = pool.begin().await?;
     let r = sqlx::query!(
         r#"
-        SELECT newsletter_issue_id, subscriber_email
-        FROM issue_delivery_queue
-        FOR UPDATE
+        SELECT q.newsletter_issue_id, q.subscriber_email, q.n_retries
+        FROM issue_delivery_queue q
+        JOIN newsletter_issues i USING (newsletter_issue_id)
+        WHERE q.execute_after <= now() AND i.send_at <= now()
+        FOR UPDATE OF q
         SKIP LOCKED
         LIMIT 1
         "#,
@@ -88,6 +195,7 @@ async fn dequeue_task(
             transaction,
             r.newsletter_issue_id,
             r.subscriber_email,
+            r.n_retries,
         )))
     } else {
         Ok(None)
@@ -115,14 +223,202 @@ async fn delete_task(
     Ok(())
 }
 
-struct NewsletterIssue {
-    title: String,
-    text_content: String,
-    html_content: String,
+/// Record the outcome of a delivery attempt in the per-recipient delivery log.
+#[tracing::instrument(skip(transaction))]
+async fn update_delivery_status(
+    transaction: &mut PgTransaction,
+    issue_id: Uuid,
+    email: &str,
+    status: &str,
+    provider_message_id: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        UPDATE issue_deliveries
+        SET
+            status = $3,
+            provider_message_id = COALESCE($4, provider_message_id),
+            updated_at = now()
+        WHERE
+            newsletter_issue_id = $1 AND
+            subscriber_email = $2
+        "#,
+        issue_id,
+        email,
+        status,
+        provider_message_id
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn reschedule_task(
+    mut transaction: PgTransaction,
+    issue_id: Uuid,
+    email: &str,
+    delay: Duration,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET
+            n_retries = n_retries + 1,
+            execute_after = now() + make_interval(secs => $3)
+        WHERE
+            newsletter_issue_id = $1 AND
+            subscriber_email = $2
+        "#,
+        issue_id,
+        email,
+        delay.as_secs_f64()
+    );
+    transaction.execute(query).await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Park a task that ran out of retries in the dead-letter table, where an
+/// admin can inspect it and re-enqueue it.
+#[tracing::instrument(skip_all)]
+async fn move_task_to_failures(
+    mut transaction: PgTransaction,
+    issue_id: Uuid,
+    email: &str,
+    n_attempts: i16,
+    error: &anyhow::Error,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_failures (
+            newsletter_issue_id,
+            subscriber_email,
+            n_attempts,
+            last_error,
+            failed_at
+        )
+        VALUES ($1, $2, $3, $4, now())
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET
+            n_attempts = EXCLUDED.n_attempts,
+            last_error = EXCLUDED.last_error,
+            failed_at = EXCLUDED.failed_at
+        "#,
+        issue_id,
+        email,
+        n_attempts,
+        format!("{:?}", error)
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE
+            newsletter_issue_id = $1 AND
+            subscriber_email = $2
+        "#,
+        issue_id,
+        email
+    );
+    transaction.execute(query).await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Exponential backoff with jitter: the `n`-th retry waits somewhere between
+/// half and the whole of `base * 2^n`, capped at `max`.
+fn backoff(n_retries: i16, base: Duration, max: Duration) -> Duration {
+    let exponent = n_retries.clamp(0, 31) as u32;
+    let delay = base.saturating_mul(2u32.saturating_pow(exponent)).min(max);
+    let jitter = rand::thread_rng().gen_range(0.0..=0.5);
+    delay.mul_f64(1.0 - jitter)
+}
+
+pub(crate) struct NewsletterIssue {
+    pub(crate) title: String,
+    pub(crate) text_content: String,
+    pub(crate) html_content: String,
+}
+
+impl NewsletterIssue {
+    /// Substitute the merge tags with the values of a recipient.
+    pub(crate) fn personalize(&mut self, fields: &MergeFields) {
+        self.title = merge_tags::render_text(&self.title, fields);
+        self.text_content = merge_tags::render_text(&self.text_content, fields);
+        self.html_content = merge_tags::render_html(&self.html_content, fields);
+    }
+
+    /// Append the footer links to both bodies.
+    pub(crate) fn add_footer(&mut self, links: &FooterLinks) {
+        let mut text_links = Vec::new();
+        let mut html_links = Vec::new();
+        let mut push = |label: &str, link: &str| {
+            text_links.push(format!("{}: {}", label, link));
+            html_links.push(format!(
+                r#"<a href="{}">{}</a>"#,
+                htmlescape::encode_minimal(link),
+                label
+            ));
+        };
+        if let Some(archive) = links.archive {
+            push("View in browser", archive);
+        }
+        push("Manage your preferences", links.preferences);
+        push("Unsubscribe", links.unsubscribe);
+        self.text_content
+            .push_str(&format!("\n\n{}", text_links.join("\n")));
+        self.html_content
+            .push_str(&format!("<p>{}</p>", html_links.join(" | ")));
+    }
+}
+
+/// The links at the bottom of every issue; all but the archive one are per-recipient.
+pub(crate) struct FooterLinks<'a> {
+    /// `None` if the issue is not in the public archive.
+    pub(crate) archive: Option<&'a str>,
+    pub(crate) preferences: &'a str,
+    pub(crate) unsubscribe: &'a str,
+}
+
+struct ConfirmedSubscriber {
+    id: Uuid,
+    name: String,
+    list_id: Uuid,
+}
+
+/// The subscriber behind `email`, if they are still a confirmed member
+/// of the list the issue was published to.
+#[tracing::instrument(skip_all)]
+async fn get_confirmed_subscriber(
+    pool: &PgPool,
+    issue_id: Uuid,
+    email: &str,
+) -> Result<Option<ConfirmedSubscriber>, anyhow::Error> {
+    let subscriber = sqlx::query_as!(
+        ConfirmedSubscriber,
+        r#"
+        SELECT s.id, s.name, m.list_id
+        FROM subscriptions s
+        JOIN list_memberships m ON m.subscriber_id = s.id
+        JOIN newsletter_issues i ON i.list_id = m.list_id
+        WHERE
+            s.email = $1 AND
+            i.newsletter_issue_id = $2 AND
+            m.status = 'confirmed'
+        "#,
+        email,
+        issue_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(subscriber)
 }
 
 #[tracing::instrument(skip_all)]
-async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, anyhow::Error> {
+pub(crate) async fn get_issue(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Option<NewsletterIssue>, anyhow::Error> {
     let issue = sqlx::query_as!(
         NewsletterIssue,
         r#"
@@ -133,14 +429,30 @@ async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, any
         "#,
         issue_id
     )
-    .fetch_one(pool)
+    .fetch_optional(pool)
     .await?;
     Ok(issue)
 }
 
-async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyhow::Error> {
+async fn worker_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    base_url: String,
+    hmac_secret: Secret<String>,
+    settings: IssueDeliverySettings,
+    subscription_settings: SubscriptionSettings,
+) -> Result<(), anyhow::Error> {
     loop {
-        match try_execute_task(&pool, &email_client).await {
+        match try_execute_task(
+            &pool,
+            &email_client,
+            &base_url,
+            &hmac_secret,
+            &settings,
+            &subscription_settings,
+        )
+        .await
+        {
             Ok(ExecutionOutcome::EmptyQueue) => {
                 tokio::time::sleep(Duration::from_secs(10)).await;
             }
@@ -154,6 +466,39 @@ async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyh
 
 pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
     let connection_pool = get_connection_pool(&configuration.database);
-    let email_client = configuration.email_client.client();
-    worker_loop(connection_pool, email_client).await
+    let email_client = configuration.email_client.client()?;
+    worker_loop(
+        connection_pool,
+        email_client,
+        configuration.application.base_url,
+        configuration.application.hmac_secret,
+        configuration.issue_delivery,
+        configuration.subscriptions,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::backoff;
+    use std::time::Duration;
+
+    #[test]
+    fn backoff_grows_exponentially_with_jitter() {
+        let base = Duration::from_secs(10);
+        let max = Duration::from_secs(3600);
+        for n_retries in 0..5 {
+            let upper = base * 2u32.pow(n_retries as u32);
+            let delay = backoff(n_retries, base, max);
+            assert!(delay <= upper, "{:?} > {:?}", delay, upper);
+            assert!(delay >= upper / 2, "{:?} < {:?}", delay, upper / 2);
+        }
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let max = Duration::from_secs(60);
+        let delay = backoff(i16::MAX, Duration::from_secs(10), max);
+        assert!(delay <= max);
+    }
 }
@@ -4,8 +4,15 @@ pub mod domain;
 pub mod email_client;
 pub mod idempotency;
 pub mod issue_delivery_worker;
+pub mod markdown;
+pub mod merge_tags;
+pub mod personal_data;
 pub mod routes;
+pub mod segment;
 pub mod session_state;
 pub mod startup;
+pub mod subscriber_import_worker;
+pub mod subscriber_purge;
+pub mod suppression;
 pub mod telemetry;
 pub mod utils;
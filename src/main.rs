@@ -4,6 +4,8 @@ use tokio::task::JoinError;
 use zero2prod::configuration::get_configuration;
 use zero2prod::issue_delivery_worker::run_worker_until_stopped;
 use zero2prod::startup::Application;
+use zero2prod::subscriber_import_worker::run_import_worker_until_stopped;
+use zero2prod::subscriber_purge::run_purge_until_stopped;
 use zero2prod::telemetry::{get_subscriber, init_subscriber};
 
 #[tokio::main]
@@ -15,11 +17,15 @@ async fn main() -> anyhow::Result<()> {
 
     let application = Application::build(configuration.clone()).await?;
     let application_task = tokio::spawn(application.run_until_stopped());
-    let worker_task = tokio::spawn(run_worker_until_stopped(configuration));
+    let worker_task = tokio::spawn(run_worker_until_stopped(configuration.clone()));
+    let import_task = tokio::spawn(run_import_worker_until_stopped(configuration.clone()));
+    let purge_task = tokio::spawn(run_purge_until_stopped(configuration));
 
     tokio::select! {
         o = application_task => report_exit("API",o),
-        o = worker_task => report_exit("Background worker", o)
+        o = worker_task => report_exit("Background worker", o),
+        o = import_task => report_exit("Subscriber import worker", o),
+        o = purge_task => report_exit("Subscriber purge", o)
     };
 
     Ok(())
@@ -0,0 +1,116 @@
+use pulldown_cmark::{html, Event, Parser, Tag, TagEnd};
+
+/// Render Markdown to the HTML body of an email.
+///
+/// Raw HTML in the source is kept as is: only admins author issues.
+pub fn render_html(markdown: &str) -> String {
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, Parser::new(markdown));
+    // Link targets get percent-encoded, merge tags such as `{{unsubscribe_url}}` must survive.
+    html_output.replace("%7B%7B", "{{").replace("%7D%7D", "}}")
+}
+
+/// Render Markdown to the plain-text body of an email.
+///
+/// Formatting is dropped, links keep their target in parentheses and lists
+/// keep their bullets or numbers.
+pub fn render_text(markdown: &str) -> String {
+    let mut text = String::new();
+    let mut links: Vec<String> = Vec::new();
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::SoftBreak | Event::HardBreak => text.push('\n'),
+            Event::Rule => end_block(&mut text, "---\n\n"),
+            Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. }) => {
+                links.push(dest_url.to_string())
+            }
+            Event::End(TagEnd::Link | TagEnd::Image) => {
+                if let Some(url) = links.pop() {
+                    if !text.ends_with(&url) {
+                        text.push_str(&format!(" ({})", url));
+                    }
+                }
+            }
+            Event::Start(Tag::List(first_number)) => {
+                if !lists.is_empty() {
+                    end_block(&mut text, "\n");
+                }
+                lists.push(first_number)
+            }
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+                end_block(&mut text, if lists.is_empty() { "\n\n" } else { "\n" });
+            }
+            Event::Start(Tag::Item) => {
+                text.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                match lists.last_mut() {
+                    Some(Some(n)) => {
+                        text.push_str(&format!("{}. ", n));
+                        *n += 1;
+                    }
+                    _ => text.push_str("- "),
+                }
+            }
+            Event::End(TagEnd::Item) => end_block(&mut text, "\n"),
+            Event::End(
+                TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::CodeBlock | TagEnd::BlockQuote(_),
+            ) => end_block(&mut text, if lists.is_empty() { "\n\n" } else { "\n" }),
+            _ => {}
+        }
+    }
+    text.trim_end().to_string()
+}
+
+fn end_block(text: &mut String, separator: &str) {
+    while text.ends_with('\n') {
+        text.pop();
+    }
+    text.push_str(separator);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_html, render_text};
+
+    #[test]
+    fn html_is_rendered_from_markdown() {
+        assert_eq!(
+            render_html("# Hello\n\nSome *emphasis*."),
+            "<h1>Hello</h1>\n<p>Some <em>emphasis</em>.</p>\n"
+        );
+    }
+
+    #[test]
+    fn merge_tags_survive_in_link_targets() {
+        assert_eq!(
+            render_html("[Leave]({{unsubscribe_url}})"),
+            "<p><a href=\"{{unsubscribe_url}}\">Leave</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn text_drops_formatting_and_separates_blocks() {
+        assert_eq!(
+            render_text("# Hello\n\nSome *emphasis* and `code`.\n\nBye"),
+            "Hello\n\nSome emphasis and code.\n\nBye"
+        );
+    }
+
+    #[test]
+    fn text_keeps_link_targets() {
+        assert_eq!(
+            render_text("Read [the post](https://example.com/post) or <https://example.com>."),
+            "Read the post (https://example.com/post) or https://example.com."
+        );
+    }
+
+    #[test]
+    fn text_keeps_list_markers() {
+        assert_eq!(
+            render_text("Intro\n\n- one\n- two\n  1. nested\n  2. again\n\nOutro"),
+            "Intro\n\n- one\n- two\n  1. nested\n  2. again\n\nOutro"
+        );
+    }
+}
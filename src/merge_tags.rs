@@ -0,0 +1,138 @@
+use htmlescape::encode_minimal;
+
+/// Tags authors can use in issue content, e.g. `Hi {{name}}!`.
+pub const MERGE_TAGS: [&str; 3] = ["name", "email", "unsubscribe_url"];
+
+/// The per-recipient values substituted for [`MERGE_TAGS`].
+pub struct MergeFields<'a> {
+    pub name: &'a str,
+    pub email: &'a str,
+    pub unsubscribe_url: &'a str,
+}
+
+impl MergeFields<'_> {
+    fn get(&self, tag: &str) -> Option<&str> {
+        match tag {
+            "name" => Some(self.name),
+            "email" => Some(self.email),
+            "unsubscribe_url" => Some(self.unsubscribe_url),
+            _ => None,
+        }
+    }
+}
+
+/// Check that every `{{tag}}` in `template` is one of [`MERGE_TAGS`].
+pub fn validate(template: &str) -> Result<(), String> {
+    for tag in tags(template) {
+        if !MERGE_TAGS.contains(&tag) {
+            return Err(format!(
+                "Unknown merge tag {{{{{}}}}}, the available ones are {}.",
+                tag,
+                MERGE_TAGS
+                    .iter()
+                    .map(|t| format!("{{{{{}}}}}", t))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Substitute the merge tags in a plain-text template.
+/// Unknown tags are left untouched, [`validate`] is meant to catch them beforehand.
+pub fn render_text(template: &str, fields: &MergeFields) -> String {
+    render(template, |tag| fields.get(tag).map(String::from))
+}
+
+/// Same as [`render_text`], with the values escaped for an HTML template.
+pub fn render_html(template: &str, fields: &MergeFields) -> String {
+    render(template, |tag| fields.get(tag).map(encode_minimal))
+}
+
+/// The trimmed names of the `{{ ... }}` placeholders in `template`, in order.
+fn tags(template: &str) -> impl Iterator<Item = &str> {
+    let mut rest = template;
+    std::iter::from_fn(move || {
+        let start = rest.find("{{")?;
+        let end = rest[start..].find("}}")? + start;
+        let tag = rest[start + 2..end].trim();
+        rest = &rest[end + 2..];
+        Some(tag)
+    })
+}
+
+fn render(template: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}").map(|end| end + start) else {
+            break;
+        };
+        output.push_str(&rest[..start]);
+        match value(rest[start + 2..end].trim()) {
+            Some(value) => output.push_str(&value),
+            None => output.push_str(&rest[start..end + 2]),
+        }
+        rest = &rest[end + 2..];
+    }
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_html, render_text, validate, MergeFields};
+    use claims::{assert_err, assert_ok};
+
+    fn fields() -> MergeFields<'static> {
+        MergeFields {
+            name: "Tom & Jerry",
+            email: "tom@example.com",
+            unsubscribe_url: "https://example.com/unsubscribe?a=1&b=2",
+        }
+    }
+
+    #[test]
+    fn known_tags_are_valid() {
+        assert_ok!(validate(
+            "Hi {{name}} ({{ email }}), bye: {{unsubscribe_url}}"
+        ));
+    }
+
+    #[test]
+    fn unknown_tags_are_rejected() {
+        let e = validate("Hi {{first_name}}").unwrap_err();
+        assert!(e.starts_with("Unknown merge tag {{first_name}}"));
+    }
+
+    #[test]
+    fn content_without_tags_is_valid() {
+        assert_ok!(validate("No tags here, not even a { or a }}"));
+    }
+
+    #[test]
+    fn text_values_are_substituted_as_is() {
+        assert_eq!(
+            render_text("Hi {{name}}, {{ email }}!", &fields()),
+            "Hi Tom & Jerry, tom@example.com!"
+        );
+    }
+
+    #[test]
+    fn html_values_are_escaped() {
+        assert_eq!(
+            render_html(
+                r#"<p>Hi {{name}}</p><a href="{{unsubscribe_url}}">x</a>"#,
+                &fields()
+            ),
+            r#"<p>Hi Tom &amp; Jerry</p><a href="https://example.com/unsubscribe?a=1&amp;b=2">x</a>"#
+        );
+    }
+
+    #[test]
+    fn unterminated_tags_are_left_alone() {
+        assert_eq!(render_text("Hi {{name", &fields()), "Hi {{name");
+        assert_err!(validate("{{nope}} and {{name"));
+    }
+}
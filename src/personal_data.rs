@@ -0,0 +1,409 @@
+//! Data-subject requests: everything we hold on an email address, to hand it
+//! over or to get rid of it.
+//!
+//! Addresses are matched case-insensitively, so that nothing is left behind
+//! because of the way it was typed.
+use crate::suppression::{suppress_anonymously, SuppressionReason};
+use anyhow::Context;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+/// Who asked, as recorded in the audit log.
+pub enum Requester<'a> {
+    Admin(&'a str),
+    Subscriber,
+}
+
+impl Requester<'_> {
+    fn as_str(&self) -> &str {
+        match self {
+            Requester::Admin(username) => username,
+            Requester::Subscriber => "subscriber",
+        }
+    }
+}
+
+/// How many rows an erasure went through.
+pub struct ErasureOutcome {
+    pub n_deleted: u64,
+    pub n_anonymized: u64,
+}
+
+/// Everything we hold on `email`, as a JSON document, or `None` if we hold nothing.
+#[tracing::instrument(skip(pool, email, requester))]
+pub async fn export_personal_data(
+    pool: &PgPool,
+    email: &str,
+    requester: Requester<'_>,
+) -> Result<Option<Value>, anyhow::Error> {
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT
+            id, email, name, status, subscribed_at, tags, consent_source,
+            attributes::text AS "attributes!"
+        FROM subscriptions
+        WHERE lower(email) = lower($1)
+        "#,
+        email
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the subscriber.")?;
+    let subscriber_id = subscriber.as_ref().map(|s| s.id);
+
+    let memberships = sqlx::query!(
+        r#"
+        SELECT l.name, m.status, m.created_at
+        FROM list_memberships m
+        JOIN lists l USING (list_id)
+        WHERE m.subscriber_id = $1
+        ORDER BY m.created_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the list memberships.")?;
+    let tokens = sqlx::query!(
+        r#"
+        SELECT t.subscription_token, l.name AS "list_name?", t.new_email, t.created_at, t.expires_at
+        FROM subscription_tokens t
+        LEFT JOIN lists l USING (list_id)
+        WHERE t.subscriber_id = $1 OR lower(t.new_email) = lower($2)
+        ORDER BY t.created_at
+        "#,
+        subscriber_id,
+        email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the subscription tokens.")?;
+    let deliveries = sqlx::query!(
+        r#"
+        SELECT d.newsletter_issue_id, i.title, d.status, d.provider_message_id, d.queued_at, d.updated_at
+        FROM issue_deliveries d
+        JOIN newsletter_issues i USING (newsletter_issue_id)
+        WHERE lower(d.subscriber_email) = lower($1)
+        ORDER BY d.queued_at
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the delivery history.")?;
+    let queued_deliveries = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, n_retries, execute_after
+        FROM issue_delivery_queue
+        WHERE lower(subscriber_email) = lower($1)
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the queued deliveries.")?;
+    let failed_deliveries = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, n_attempts, last_error, failed_at
+        FROM issue_delivery_failures
+        WHERE lower(subscriber_email) = lower($1)
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the failed deliveries.")?;
+    let imports = sqlx::query!(
+        r#"
+        SELECT r.import_id, i.file_name, i.created_at, r.line, r.email, r.name, r.status, r.message
+        FROM subscriber_import_rows r
+        JOIN subscriber_imports i USING (import_id)
+        WHERE lower(r.email) = lower($1)
+        ORDER BY i.created_at, r.line
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the imported rows.")?;
+    let email_events = sqlx::query!(
+        r#"
+        SELECT kind, provider_type, provider_message_id, occurred_at, received_at
+        FROM email_events
+        WHERE lower(email) = lower($1)
+        ORDER BY received_at
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the email events.")?;
+    let suppression = sqlx::query!(
+        r#"
+        SELECT reason, source, created_at
+        FROM suppressions
+        WHERE email_hash = $1
+        "#,
+        email_hash(email)
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the suppression.")?;
+
+    if subscriber.is_none()
+        && suppression.is_none()
+        && tokens.is_empty()
+        && deliveries.is_empty()
+        && queued_deliveries.is_empty()
+        && failed_deliveries.is_empty()
+        && imports.is_empty()
+        && email_events.is_empty()
+    {
+        return Ok(None);
+    }
+    let subscriber = match subscriber {
+        Some(s) => json!({
+            "id": s.id,
+            "email": s.email,
+            "name": s.name,
+            "status": s.status,
+            "subscribed_at": s.subscribed_at.to_rfc3339(),
+            "tags": s.tags,
+            "attributes": serde_json::from_str::<Value>(&s.attributes)?,
+            "consent_source": s.consent_source,
+        }),
+        None => Value::Null,
+    };
+    let suppression = match suppression {
+        Some(s) => json!({
+            "reason": s.reason,
+            "source": s.source,
+            "created_at": s.created_at.to_rfc3339(),
+        }),
+        None => Value::Null,
+    };
+    let document = json!({
+        "email": email,
+        "exported_at": chrono::Utc::now().to_rfc3339(),
+        "subscriber": subscriber,
+        "list_memberships": memberships.into_iter().map(|m| json!({
+            "list": m.name,
+            "status": m.status,
+            "created_at": m.created_at.to_rfc3339(),
+        })).collect::<Vec<_>>(),
+        "subscription_tokens": tokens.into_iter().map(|t| json!({
+            "subscription_token": t.subscription_token,
+            "list": t.list_name,
+            "new_email": t.new_email,
+            "created_at": t.created_at.to_rfc3339(),
+            "expires_at": t.expires_at.to_rfc3339(),
+        })).collect::<Vec<_>>(),
+        "deliveries": deliveries.into_iter().map(|d| json!({
+            "newsletter_issue_id": d.newsletter_issue_id,
+            "title": d.title,
+            "status": d.status,
+            "provider_message_id": d.provider_message_id,
+            "queued_at": d.queued_at.to_rfc3339(),
+            "updated_at": d.updated_at.to_rfc3339(),
+        })).collect::<Vec<_>>(),
+        "queued_deliveries": queued_deliveries.into_iter().map(|d| json!({
+            "newsletter_issue_id": d.newsletter_issue_id,
+            "n_retries": d.n_retries,
+            "execute_after": d.execute_after.to_rfc3339(),
+        })).collect::<Vec<_>>(),
+        "failed_deliveries": failed_deliveries.into_iter().map(|d| json!({
+            "newsletter_issue_id": d.newsletter_issue_id,
+            "n_attempts": d.n_attempts,
+            "last_error": d.last_error,
+            "failed_at": d.failed_at.to_rfc3339(),
+        })).collect::<Vec<_>>(),
+        "imports": imports.into_iter().map(|r| json!({
+            "import_id": r.import_id,
+            "file_name": r.file_name,
+            "imported_at": r.created_at.to_rfc3339(),
+            "line": r.line,
+            "email": r.email,
+            "name": r.name,
+            "status": r.status,
+            "message": r.message,
+        })).collect::<Vec<_>>(),
+        "email_events": email_events.into_iter().map(|e| json!({
+            "kind": e.kind,
+            "provider_type": e.provider_type,
+            "provider_message_id": e.provider_message_id,
+            "occurred_at": e.occurred_at.map(|t| t.to_rfc3339()),
+            "received_at": e.received_at.to_rfc3339(),
+        })).collect::<Vec<_>>(),
+        "suppression": suppression,
+    });
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    record(
+        &mut transaction,
+        "export",
+        email,
+        &requester,
+        "Exported all personal data.",
+    )
+    .await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to record a data export.")?;
+    Ok(Some(document))
+}
+
+/// Delete everything we hold on `email`.
+///
+/// The delivery log is anonymized rather than deleted, to keep the delivery
+/// counts of past issues right. A suppression is kept, under the hash of the
+/// address only, so that it is never emailed again.
+#[tracing::instrument(skip(pool, email, requester))]
+pub async fn erase_personal_data(
+    pool: &PgPool,
+    email: &str,
+    requester: Requester<'_>,
+) -> Result<ErasureOutcome, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let mut n_deleted = 0;
+    n_deleted += transaction
+        .execute(sqlx::query!(
+            r#"
+            DELETE FROM subscription_tokens
+            WHERE
+                lower(new_email) = lower($1) OR
+                subscriber_id IN (SELECT id FROM subscriptions WHERE lower(email) = lower($1))
+            "#,
+            email
+        ))
+        .await
+        .context("Failed to delete the subscription tokens.")?
+        .rows_affected();
+    n_deleted += transaction
+        .execute(sqlx::query!(
+            r#"
+            DELETE FROM list_memberships
+            WHERE subscriber_id IN (SELECT id FROM subscriptions WHERE lower(email) = lower($1))
+            "#,
+            email
+        ))
+        .await
+        .context("Failed to delete the list memberships.")?
+        .rows_affected();
+    n_deleted += transaction
+        .execute(sqlx::query!(
+            "DELETE FROM subscriptions WHERE lower(email) = lower($1)",
+            email
+        ))
+        .await
+        .context("Failed to delete the subscriber.")?
+        .rows_affected();
+    n_deleted += transaction
+        .execute(sqlx::query!(
+            "DELETE FROM issue_delivery_queue WHERE lower(subscriber_email) = lower($1)",
+            email
+        ))
+        .await
+        .context("Failed to delete the queued deliveries.")?
+        .rows_affected();
+    n_deleted += transaction
+        .execute(sqlx::query!(
+            "DELETE FROM issue_delivery_failures WHERE lower(subscriber_email) = lower($1)",
+            email
+        ))
+        .await
+        .context("Failed to delete the failed deliveries.")?
+        .rows_affected();
+    n_deleted += transaction
+        .execute(sqlx::query!(
+            "DELETE FROM subscriber_import_rows WHERE lower(email) = lower($1)",
+            email
+        ))
+        .await
+        .context("Failed to delete the imported rows.")?
+        .rows_affected();
+    n_deleted += transaction
+        .execute(sqlx::query!(
+            "DELETE FROM email_events WHERE lower(email) = lower($1)",
+            email
+        ))
+        .await
+        .context("Failed to delete the email events.")?
+        .rows_affected();
+    let n_anonymized = transaction
+        .execute(sqlx::query!(
+            r#"
+            UPDATE issue_deliveries
+            SET subscriber_email = 'erased:' || gen_random_uuid(), provider_message_id = NULL
+            WHERE lower(subscriber_email) = lower($1)
+            "#,
+            email
+        ))
+        .await
+        .context("Failed to anonymize the delivery history.")?
+        .rows_affected();
+    suppress_anonymously(
+        &mut *transaction,
+        email,
+        SuppressionReason::DoNotContact,
+        requester.as_str(),
+    )
+    .await
+    .context("Failed to suppress the erased address.")?;
+
+    record(
+        &mut transaction,
+        "erasure",
+        email,
+        &requester,
+        &format!("Deleted {n_deleted} rows and anonymized {n_anonymized} rows."),
+    )
+    .await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to erase personal data.")?;
+    Ok(ErasureOutcome {
+        n_deleted,
+        n_anonymized,
+    })
+}
+
+/// The hash an address is recorded under in the audit log and the
+/// suppression list.
+pub fn email_hash(email: &str) -> String {
+    hex::encode(Sha256::digest(email.trim().to_lowercase().as_bytes()))
+}
+
+async fn record(
+    transaction: &mut Transaction<'_, Postgres>,
+    action: &str,
+    email: &str,
+    requester: &Requester<'_>,
+    details: &str,
+) -> Result<(), anyhow::Error> {
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            INSERT INTO personal_data_audit_log
+                (entry_id, action, email_hash, requested_by, details, created_at)
+            VALUES ($1, $2, $3, $4, $5, now())
+            "#,
+            Uuid::new_v4(),
+            action,
+            email_hash(email),
+            requester.as_str(),
+            details
+        ))
+        .await
+        .context("Failed to record a data-subject request in the audit log.")?;
+    Ok(())
+}
@@ -39,6 +39,13 @@ pub async fn admin_dashboard(
                 <p>Available actions:</p>
                 <ol>
                     <li><a href="/admin/password">Change Password</a></li>
+                    <li><a href="/admin/email">Change Email Address</a></li>
+                    <li><a href="/admin/newsletters">Newsletter issues</a></li>
+                    <li><a href="/admin/lists">Mailing lists</a></li>
+                    <li><a href="/admin/subscribers">Subscribers</a></li>
+                    <li><a href="/admin/suppressions">Suppression list</a></li>
+                    <li><a href="/admin/personal_data">Personal data requests</a></li>
+                    <li><a href="/admin/deliveries/failed">Failed deliveries</a></li>
                     <li>
                         <form name="logoutForm" action="/admin/logout" method="post">
                             <input type="submit" value="Logout"/>
@@ -0,0 +1,112 @@
+use crate::utils::e500;
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+struct FailedDelivery {
+    newsletter_issue_id: Uuid,
+    title: String,
+    subscriber_email: String,
+    n_attempts: i16,
+    last_error: String,
+    failed_at: DateTime<Utc>,
+}
+
+pub async fn failed_deliveries(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let failures = get_failed_deliveries(&pool).await.map_err(e500)?;
+    let mut rows_html = String::new();
+    for f in &failures {
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td>{title}</td>
+                <td>{email}</td>
+                <td>{n_attempts}</td>
+                <td>{failed_at}</td>
+                <td><pre>{last_error}</pre></td>
+                <td>
+                    <form action="/admin/deliveries/failed/retry" method="post">
+                        <input hidden type="text" name="newsletter_issue_id" value="{issue_id}">
+                        <input hidden type="text" name="subscriber_email" value="{email}">
+                        <button type="submit">Retry</button>
+                    </form>
+                </td>
+            </tr>"#,
+            title = encode_minimal(&f.title),
+            email = encode_minimal(&f.subscriber_email),
+            n_attempts = f.n_attempts,
+            failed_at = f.failed_at.to_rfc3339(),
+            last_error = encode_minimal(&f.last_error),
+            issue_id = f.newsletter_issue_id,
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                <title>Failed Deliveries</title>
+            </head>
+            <body>
+                {msg_html}
+                <h1>Failed deliveries ({n_failures})</h1>
+                <table>
+                    <tr>
+                        <th>Issue</th>
+                        <th>Subscriber</th>
+                        <th>Attempts</th>
+                        <th>Failed at</th>
+                        <th>Last error</th>
+                        <th></th>
+                    </tr>
+                    {rows_html}
+                </table>
+                <form action="/admin/deliveries/failed/retry_all" method="post">
+                    <button type="submit">Retry all</button>
+                </form>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#,
+            n_failures = failures.len(),
+        )))
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_failed_deliveries(pool: &PgPool) -> Result<Vec<FailedDelivery>, anyhow::Error> {
+    let failures = sqlx::query_as!(
+        FailedDelivery,
+        r#"
+        SELECT
+            f.newsletter_issue_id,
+            i.title,
+            f.subscriber_email,
+            f.n_attempts,
+            f.last_error,
+            f.failed_at
+        FROM issue_delivery_failures f
+        JOIN newsletter_issues i USING (newsletter_issue_id)
+        ORDER BY f.failed_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve failed deliveries.")?;
+    Ok(failures)
+}
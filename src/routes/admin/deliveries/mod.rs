@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::failed_deliveries;
+pub use post::{retry_all_failed_deliveries, retry_failed_delivery};
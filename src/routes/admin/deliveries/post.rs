@@ -0,0 +1,109 @@
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+}
+
+#[tracing::instrument(
+    name = "Retry a failed delivery",
+    skip_all,
+    fields(
+        newsletter_issue_id = %form.newsletter_issue_id,
+        subscriber_email = %form.subscriber_email
+    )
+)]
+pub async fn retry_failed_delivery(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let n_requeued = requeue_failed_deliveries(
+        &mut transaction,
+        Some((form.newsletter_issue_id, &form.subscriber_email)),
+    )
+    .await
+    .context("Failed to re-enqueue a failed delivery")
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to re-enqueue a failed delivery.")
+        .map_err(e500)?;
+    success_message(n_requeued).send();
+    Ok(see_other("/admin/deliveries/failed"))
+}
+
+#[tracing::instrument(name = "Retry all failed deliveries", skip_all)]
+pub async fn retry_all_failed_deliveries(
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let n_requeued = requeue_failed_deliveries(&mut transaction, None)
+        .await
+        .context("Failed to re-enqueue failed deliveries")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to re-enqueue failed deliveries.")
+        .map_err(e500)?;
+    success_message(n_requeued).send();
+    Ok(see_other("/admin/deliveries/failed"))
+}
+
+fn success_message(n_requeued: u64) -> FlashMessage {
+    FlashMessage::info(format!(
+        "{} failed deliveries have been re-enqueued.",
+        n_requeued
+    ))
+}
+
+/// Move failed deliveries back into `issue_delivery_queue`, with a fresh retry budget.
+/// Only the given delivery is moved if `only` is set, all of them otherwise.
+#[tracing::instrument(skip_all)]
+async fn requeue_failed_deliveries(
+    transaction: &mut Transaction<'_, Postgres>,
+    only: Option<(Uuid, &str)>,
+) -> Result<u64, sqlx::Error> {
+    let (issue_id, email) = only.unzip();
+    let query = sqlx::query!(
+        r#"
+        WITH requeued AS (
+            DELETE FROM issue_delivery_failures
+            WHERE
+                ($1::uuid IS NULL OR newsletter_issue_id = $1) AND
+                ($2::text IS NULL OR subscriber_email = $2)
+            RETURNING newsletter_issue_id, subscriber_email
+        ), delivery_log AS (
+            UPDATE issue_deliveries d
+            SET status = 'queued', updated_at = now()
+            FROM requeued r
+            WHERE
+                d.newsletter_issue_id = r.newsletter_issue_id AND
+                d.subscriber_email = r.subscriber_email
+        )
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+        SELECT newsletter_issue_id, subscriber_email
+        FROM requeued
+        ON CONFLICT DO NOTHING
+        "#,
+        issue_id,
+        email,
+    );
+    Ok(transaction.execute(query).await?.rows_affected())
+}
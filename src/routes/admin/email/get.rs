@@ -0,0 +1,72 @@
+use crate::authentication::UserId;
+use crate::utils::e500;
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+pub async fn change_email_form(
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = get_user_email(*user_id.into_inner(), &pool)
+        .await
+        .map_err(e500)?
+        .unwrap_or_default();
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="Content-Type" content="text/html; charset=UTF-8">
+            <title>Change Email Address</title>
+        </head>
+        <body>
+            {msg_html}
+            <p>Test sends of newsletter issues go to this address.</p>
+            <form action="/admin/email" method="post">
+                <label>Email address
+                    <input
+                        type="email"
+                        name="email"
+                        placeholder="Enter your email address"
+                        value="{email}"
+                    >
+                </label>
+                <br>
+                <button type="submit">Change Email Address</button>
+            </form>
+            <p><a href="/admin/dashboard">&lt;- Back</a></p>
+        </body>
+        </html>
+        "#,
+            email = encode_minimal(&email),
+        )))
+}
+
+#[tracing::instrument(name = "Get user email", skip(pool))]
+pub async fn get_user_email(user_id: Uuid, pool: &PgPool) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT email
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to perform a query to retrieve a user email.")?;
+    Ok(row.email)
+}
@@ -0,0 +1,5 @@
+mod get;
+pub use get::change_email_form;
+pub(crate) use get::get_user_email;
+mod post;
+pub use post::change_email;
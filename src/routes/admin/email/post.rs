@@ -0,0 +1,51 @@
+use crate::authentication::UserId;
+use crate::domain::SubscriberEmail;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    email: String,
+}
+
+pub async fn change_email(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let Ok(email) = SubscriberEmail::parse(form.0.email) else {
+        FlashMessage::error("Please enter a valid email address.").send();
+        return Ok(see_other("/admin/email"));
+    };
+    store_user_email(*user_id, &email, &pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("Your email address has been changed.").send();
+    Ok(see_other("/admin/email"))
+}
+
+#[tracing::instrument(name = "Store user email", skip(pool))]
+async fn store_user_email(
+    user_id: Uuid,
+    email: &SubscriberEmail,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET email = $1
+        WHERE user_id = $2
+        "#,
+        email.as_ref(),
+        user_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to change user's email in the database.")?;
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use crate::utils::e500;
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+pub(crate) struct MailingList {
+    pub(crate) list_id: Uuid,
+    pub(crate) name: String,
+    pub(crate) is_default: bool,
+}
+
+struct ListSummary {
+    name: String,
+    is_default: bool,
+    n_confirmed: i64,
+    n_pending: i64,
+}
+
+pub async fn mailing_lists(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut rows_html = String::new();
+    for l in get_list_summaries(&pool).await.map_err(e500)? {
+        writeln!(
+            rows_html,
+            "<tr><td>{}{}</td><td>{}</td><td>{}</td></tr>",
+            encode_minimal(&l.name),
+            if l.is_default { " (default)" } else { "" },
+            l.n_confirmed,
+            l.n_pending,
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                <title>Mailing Lists</title>
+            </head>
+            <body>
+                {msg_html}
+                <h1>Mailing lists</h1>
+                <table>
+                    <tr><th>Name</th><th>Confirmed</th><th>Pending</th></tr>
+                    {rows_html}
+                </table>
+                <h2>New list</h2>
+                <form action="/admin/lists" method="post">
+                    <label>Name
+                        <input type="text" name="name">
+                    </label>
+                    <button type="submit">Create</button>
+                </form>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
+
+/// The `<option>`s of a list selector, with `selected` or else the default
+/// list preselected.
+pub(crate) fn list_options(lists: &[MailingList], selected: Option<Uuid>) -> String {
+    let mut options_html = String::new();
+    for l in lists {
+        writeln!(
+            options_html,
+            r#"<option value="{}"{}>{}</option>"#,
+            l.list_id,
+            if selected.map_or(l.is_default, |id| id == l.list_id) {
+                " selected"
+            } else {
+                ""
+            },
+            encode_minimal(&l.name),
+        )
+        .unwrap();
+    }
+    options_html
+}
+
+#[tracing::instrument(skip(pool))]
+pub(crate) async fn get_lists(pool: &PgPool) -> Result<Vec<MailingList>, anyhow::Error> {
+    let lists = sqlx::query_as!(
+        MailingList,
+        "SELECT list_id, name, is_default FROM lists ORDER BY name"
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the mailing lists.")?;
+    Ok(lists)
+}
+
+/// Check that `list_id` names an existing list, falling back to the default
+/// list when it is not set.
+#[tracing::instrument(skip(pool))]
+pub(crate) async fn get_list_id(
+    pool: &PgPool,
+    list_id: Option<Uuid>,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT list_id FROM lists
+        WHERE list_id = $1 OR ($1 IS NULL AND is_default)
+        "#,
+        list_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up a mailing list.")?;
+    Ok(row.map(|r| r.list_id))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_list_summaries(pool: &PgPool) -> Result<Vec<ListSummary>, anyhow::Error> {
+    let lists = sqlx::query_as!(
+        ListSummary,
+        r#"
+        SELECT
+            l.name,
+            l.is_default,
+            count(m.subscriber_id) FILTER (WHERE m.status = 'confirmed') AS "n_confirmed!",
+            count(m.subscriber_id) FILTER (WHERE m.status = 'pending_confirmation') AS "n_pending!"
+        FROM lists l
+        LEFT JOIN list_memberships m USING (list_id)
+        GROUP BY l.list_id
+        ORDER BY l.name
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the mailing lists.")?;
+    Ok(lists)
+}
@@ -0,0 +1,5 @@
+mod get;
+pub use get::mailing_lists;
+pub(crate) use get::{get_list_id, get_lists, list_options};
+mod post;
+pub use post::create_mailing_list;
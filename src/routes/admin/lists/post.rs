@@ -0,0 +1,48 @@
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    name: String,
+}
+
+#[tracing::instrument(name = "Create a mailing list", skip(form, pool))]
+pub async fn create_mailing_list(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let name = form.0.name.trim().to_owned();
+    if name.is_empty() {
+        FlashMessage::error("The list needs a name.").send();
+        return Ok(see_other("/admin/lists"));
+    }
+    if insert_list(&pool, &name).await.map_err(e500)? {
+        FlashMessage::info("The list has been created.").send();
+    } else {
+        FlashMessage::error("There is already a list with that name.").send();
+    }
+    Ok(see_other("/admin/lists"))
+}
+
+/// Returns `false` if the name is already taken.
+#[tracing::instrument(skip(pool))]
+async fn insert_list(pool: &PgPool, name: &str) -> Result<bool, anyhow::Error> {
+    let n_inserted = sqlx::query!(
+        r#"
+        INSERT INTO lists (list_id, name)
+        VALUES ($1, $2)
+        ON CONFLICT (name) DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        name
+    )
+    .execute(pool)
+    .await
+    .context("Failed to insert a mailing list.")?
+    .rows_affected();
+    Ok(n_inserted == 1)
+}
@@ -1,9 +1,21 @@
 mod dashboard;
+mod deliveries;
+mod email;
+mod lists;
 mod logout;
 mod newsletter;
 mod password;
+mod personal_data;
+mod subscribers;
+mod suppressions;
 
 pub use dashboard::admin_dashboard;
+pub use deliveries::*;
+pub use email::*;
+pub use lists::*;
 pub use logout::log_out;
 pub use newsletter::*;
 pub use password::*;
+pub use personal_data::*;
+pub use subscribers::*;
+pub use suppressions::*;
@@ -0,0 +1,199 @@
+use super::post::{insert_revision, render_bodies};
+use crate::markdown;
+use crate::utils::{e500, see_other};
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::fmt::Write;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    title: String,
+    #[serde(default)]
+    markdown_content: String,
+    #[serde(default)]
+    text_content: String,
+    #[serde(default)]
+    html_content: String,
+}
+
+#[tracing::instrument(
+    name = "Show the edit form of a newsletter draft",
+    skip(pool, flash_messages)
+)]
+pub async fn edit_newsletter_draft_form(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let Some(draft) = get_draft(&pool, newsletter_issue_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    // Only bodies the author wrote by hand are shown, rendered ones follow the Markdown.
+    let text_content = if draft.text_content == markdown::render_text(&draft.markdown_content) {
+        ""
+    } else {
+        &draft.text_content
+    };
+    let html_content = if draft.html_content == markdown::render_html(&draft.markdown_content) {
+        ""
+    } else {
+        &draft.html_content
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                <title>Edit Newsletter Draft</title>
+            </head>
+            <body>
+                {msg_html}
+                <form action="/admin/newsletters/{newsletter_issue_id}/edit" method="post">
+                    <label>Title:<br>
+                        <input
+                            type="text"
+                            placeholder="Enter the issue title"
+                            name="title"
+                            value="{title}"
+                        >
+                    </label>
+                    <br>
+                    <label>Markdown content:<br>
+                        <textarea
+                            placeholder="Enter the content in Markdown"
+                            name="markdown_content"
+                            rows="20"
+                            cols="50"
+                        >{markdown_content}</textarea>
+                    </label>
+                    <br>
+                    <label>Plaint text content (leave empty to render it from Markdown):<br>
+                        <textarea
+                            placeholder="Enter the content in plaint text"
+                            name="text_content"
+                            rows="20"
+                            cols="50"
+                        >{text_content}</textarea>
+                    </label>
+                    <br>
+                    <label>HTML content (leave empty to render it from Markdown):<br>
+                        <textarea
+                            placeholder="Enter the content in HTML format"
+                            name="html_content"
+                            rows="20"
+                            cols="50"
+                        >{html_content}</textarea>
+                    </label>
+                    <br>
+                    <button type="submit">Save draft</button>
+                </form>
+                <p><a href="/admin/newsletters/{newsletter_issue_id}">&lt;- Back</a></p>
+            </body>
+            </html>"#,
+            title = encode_minimal(&draft.title),
+            markdown_content = encode_minimal(&draft.markdown_content),
+            text_content = encode_minimal(text_content),
+            html_content = encode_minimal(html_content),
+        )))
+}
+
+#[tracing::instrument(name = "Save a new revision of a newsletter draft", skip(form, pool))]
+pub async fn edit_newsletter_draft(
+    newsletter_issue_id: web::Path<Uuid>,
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let updated = update_draft(&mut transaction, newsletter_issue_id, form.0)
+        .await
+        .context("Failed to update a newsletter draft")
+        .map_err(e500)?;
+    if !updated {
+        FlashMessage::error("Published issues can no longer be edited.").send();
+        return Ok(see_other(&format!(
+            "/admin/newsletters/{newsletter_issue_id}"
+        )));
+    }
+    insert_revision(&mut transaction, newsletter_issue_id)
+        .await
+        .context("Failed to store a revision of a newsletter draft")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to update a newsletter draft.")
+        .map_err(e500)?;
+    FlashMessage::info("The draft has been saved.").send();
+    Ok(see_other(&format!(
+        "/admin/newsletters/{newsletter_issue_id}"
+    )))
+}
+
+struct Draft {
+    title: String,
+    markdown_content: String,
+    text_content: String,
+    html_content: String,
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_draft(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<Draft>, anyhow::Error> {
+    let draft = sqlx::query_as!(
+        Draft,
+        r#"
+        SELECT title, markdown_content, text_content, html_content
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1 AND published_at IS NULL
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the newsletter draft.")?;
+    Ok(draft)
+}
+
+/// Overwrite the content of a draft.
+/// Returns `false` if the issue does not exist or has already been published.
+#[tracing::instrument(skip(transaction, form))]
+async fn update_draft(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    form: FormData,
+) -> Result<bool, sqlx::Error> {
+    let (text_content, html_content) =
+        render_bodies(&form.markdown_content, form.text_content, form.html_content);
+    let query = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET title = $2, markdown_content = $3, text_content = $4, html_content = $5
+        WHERE newsletter_issue_id = $1 AND published_at IS NULL
+        "#,
+        newsletter_issue_id,
+        form.title,
+        form.markdown_content,
+        text_content,
+        html_content
+    );
+    Ok(transaction.execute(query).await?.rows_affected() == 1)
+}
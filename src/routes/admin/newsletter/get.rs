@@ -1,15 +1,46 @@
-use actix_web::{http::header::ContentType, HttpResponse};
+use crate::utils::e500;
+use actix_web::{http::header::ContentType, web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
 use std::fmt::Write;
+use uuid::Uuid;
 
-pub async fn publish_newsletter_form(
+struct IssueListEntry {
+    newsletter_issue_id: Uuid,
+    title: String,
+    send_at: Option<DateTime<Utc>>,
+    cancelled_at: Option<DateTime<Utc>>,
+}
+
+pub async fn newsletter_issues(
     flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let mut msg_html = String::new();
     for m in flash_messages.iter() {
         writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
     }
-    let idempotency_key = uuid::Uuid::new_v4();
+    let mut issues_html = String::new();
+    for issue in get_issues(&pool).await.map_err(e500)? {
+        let status = match issue.send_at {
+            None => "draft".to_string(),
+            Some(_) if issue.cancelled_at.is_some() => "cancelled".to_string(),
+            Some(send_at) if send_at > Utc::now() => {
+                format!("scheduled for {}", send_at.to_rfc3339())
+            }
+            Some(send_at) => format!("sent at {}", send_at.to_rfc3339()),
+        };
+        writeln!(
+            issues_html,
+            r#"<li><a href="/admin/newsletters/{}">{}</a> ({status})</li>"#,
+            issue.newsletter_issue_id,
+            encode_minimal(&issue.title),
+        )
+        .unwrap();
+    }
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
         .body(format!(
@@ -17,11 +48,12 @@ pub async fn publish_newsletter_form(
             <html lang="en">
             <head>
                 <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
-                <title>Publish Newsletter Issue</title>
+                <title>Newsletter Issues</title>
             </head>
             <body>
                 {msg_html}
-                <form action="/admin/newsletter" method="post">
+                <h2>New draft</h2>
+                <form action="/admin/newsletters" method="post">
                     <label>Title:<br>
                         <input
                             type="text"
@@ -30,7 +62,16 @@ pub async fn publish_newsletter_form(
                         >
                     </label>
                     <br>
-                    <label>Plaint text content:<br>
+                    <label>Markdown content:<br>
+                        <textarea
+                            placeholder="Enter the content in Markdown"
+                            name="markdown_content"
+                            rows="20"
+                            cols="50"
+                        ></textarea>
+                    </label>
+                    <br>
+                    <label>Plaint text content (leave empty to render it from Markdown):<br>
                         <textarea
                             placeholder="Enter the content in plaint text"
                             name="text_content"
@@ -39,7 +80,7 @@ pub async fn publish_newsletter_form(
                         ></textarea>
                     </label>
                     <br>
-                    <label>HTML content:<br>
+                    <label>HTML content (leave empty to render it from Markdown):<br>
                         <textarea
                             placeholder="Enter the content in HTML format"
                             name="html_content"
@@ -48,11 +89,30 @@ pub async fn publish_newsletter_form(
                         ></textarea>
                     </label>
                     <br>
-                    <input hidden type="text" name="idempotency_key" value="{idempotency_key}">
-                    <button type="submit">Publish</button>
+                    <button type="submit">Save draft</button>
                 </form>
+                <h2>Issues</h2>
+                <ul>
+                    {issues_html}
+                </ul>
                 <p><a href="/admin/dashboard">&lt;- Back</a></p>
             </body>
             </html>"#
         )))
 }
+
+#[tracing::instrument(skip_all)]
+async fn get_issues(pool: &PgPool) -> Result<Vec<IssueListEntry>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        IssueListEntry,
+        r#"
+        SELECT newsletter_issue_id, title, send_at, cancelled_at
+        FROM newsletter_issues
+        ORDER BY send_at DESC NULLS FIRST
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the newsletter issues.")?;
+    Ok(issues)
+}
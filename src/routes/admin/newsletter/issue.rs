@@ -0,0 +1,290 @@
+use super::publish::{count_audience, parse_segment};
+use crate::routes::{get_list_id, get_lists, list_options};
+use crate::utils::e500;
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// Set by the "Preview audience" button of the publish form.
+#[derive(serde::Deserialize)]
+pub struct AudienceParameters {
+    list_id: Option<Uuid>,
+    #[serde(default)]
+    segment: String,
+}
+
+struct IssueSummary {
+    title: String,
+    list_name: Option<String>,
+    segment: Option<String>,
+    published_at: Option<DateTime<Utc>>,
+    send_at: Option<DateTime<Utc>>,
+    cancelled_at: Option<DateTime<Utc>>,
+}
+
+struct Revision {
+    revision: i32,
+    created_at: DateTime<Utc>,
+}
+
+struct DeliveryCounts {
+    queued: i64,
+    sent: i64,
+    failed: i64,
+    skipped: i64,
+}
+
+impl DeliveryCounts {
+    fn total(&self) -> i64 {
+        self.queued + self.sent + self.failed + self.skipped
+    }
+
+    /// Share of the deliveries that are over, whatever their outcome.
+    fn percent_done(&self) -> i64 {
+        match self.total() {
+            0 => 100,
+            total => (total - self.queued) * 100 / total,
+        }
+    }
+}
+
+#[tracing::instrument(name = "Show a newsletter issue", skip(audience, pool, flash_messages))]
+pub async fn newsletter_issue(
+    newsletter_issue_id: web::Path<Uuid>,
+    audience: web::Query<AudienceParameters>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let Some(issue) = get_issue_summary(&pool, newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let counts = get_delivery_counts(&pool, newsletter_issue_id)
+        .await
+        .map_err(e500)?;
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut revisions_html = String::new();
+    for r in get_revisions(&pool, newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    {
+        writeln!(
+            revisions_html,
+            r#"<li><a href="/admin/newsletters/{newsletter_issue_id}/preview?revision={revision}">Revision {revision}</a> ({created_at})</li>"#,
+            revision = r.revision,
+            created_at = r.created_at.to_rfc3339(),
+        )
+        .unwrap();
+    }
+
+    let mut refresh_html = "";
+    let status_html = match (issue.published_at, issue.send_at) {
+        (Some(published_at), Some(send_at)) => {
+            let schedule_html = if let Some(cancelled_at) = issue.cancelled_at {
+                format!("<p>Cancelled at {}</p>", cancelled_at.to_rfc3339())
+            } else if send_at > Utc::now() {
+                format!(
+                    r#"<p>Scheduled for {send_at}</p>
+                <form action="/admin/newsletters/{newsletter_issue_id}/reschedule" method="post">
+                    <label>Send at (UTC):
+                        <input type="datetime-local" name="send_at" value="{send_at_value}">
+                    </label>
+                    <button type="submit">Reschedule</button>
+                </form>
+                <form action="/admin/newsletters/{newsletter_issue_id}/cancel" method="post">
+                    <button type="submit">Cancel</button>
+                </form>"#,
+                    send_at = send_at.to_rfc3339(),
+                    send_at_value = send_at.format("%Y-%m-%dT%H:%M"),
+                )
+            } else {
+                if counts.queued > 0 {
+                    refresh_html = r#"<meta http-equiv="refresh" content="5">"#;
+                }
+                format!("<p>Sent at {}</p>", send_at.to_rfc3339())
+            };
+            format!(
+                r#"<p>Published at {published_at} to {list_name}{segment}</p>
+                {schedule_html}
+                <p>Delivered to {percent_done}% of {total} recipients.</p>
+                <table>
+                    <tr><th>Queued</th><td>{queued}</td></tr>
+                    <tr><th>Sent</th><td>{sent}</td></tr>
+                    <tr><th>Failed</th><td>{failed}</td></tr>
+                    <tr><th>Skipped</th><td>{skipped}</td></tr>
+                </table>"#,
+                published_at = published_at.to_rfc3339(),
+                list_name = encode_minimal(issue.list_name.as_deref().unwrap_or_default()),
+                segment = issue
+                    .segment
+                    .map(|s| format!(" (segment: <code>{}</code>)", encode_minimal(&s)))
+                    .unwrap_or_default(),
+                percent_done = counts.percent_done(),
+                total = counts.total(),
+                queued = counts.queued,
+                sent = counts.sent,
+                failed = counts.failed,
+                skipped = counts.skipped,
+            )
+        }
+        _ => {
+            let idempotency_key = Uuid::new_v4();
+            let list_options =
+                list_options(&get_lists(&pool).await.map_err(e500)?, audience.list_id);
+            let audience_html = audience_preview(&pool, &audience).await?;
+            format!(
+                r#"<p>Draft</p>
+                <p><a href="/admin/newsletters/{newsletter_issue_id}/edit">Edit</a></p>
+                <form action="/admin/newsletters/{newsletter_issue_id}/publish" method="post">
+                    <label>Mailing list:
+                        <select name="list_id">
+                            {list_options}
+                        </select>
+                    </label>
+                    <label>Segment (e.g. <code>tag:beta and attr.plan = "pro"</code>, leave empty to send to the whole list):
+                        <input type="text" name="segment" value="{segment}">
+                    </label>
+                    <button type="submit" formmethod="get" formaction="/admin/newsletters/{newsletter_issue_id}">Preview audience</button>
+                    {audience_html}
+                    <label>Send at (UTC, leave empty to send right away):
+                        <input type="datetime-local" name="send_at">
+                    </label>
+                    <input hidden type="text" name="idempotency_key" value="{idempotency_key}">
+                    <button type="submit">Publish</button>
+                </form>
+                <form action="/admin/newsletters/{newsletter_issue_id}/send_test" method="post">
+                    <button type="submit">Send a test email to myself</button>
+                </form>"#,
+                segment = encode_minimal(&audience.segment),
+            )
+        }
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                {refresh_html}
+                <title>Newsletter Issue</title>
+            </head>
+            <body>
+                {msg_html}
+                <h1>{title}</h1>
+                {status_html}
+                <p><a href="/admin/newsletters/{newsletter_issue_id}/preview">Preview</a></p>
+                <h2>Revisions</h2>
+                <ul>
+                    {revisions_html}
+                </ul>
+                <p><a href="/admin/newsletters">&lt;- Back</a></p>
+            </body>
+            </html>"#,
+            title = encode_minimal(&issue.title),
+        )))
+}
+
+/// How many subscribers the list and segment picked in the publish form
+/// would reach, once the admin asked for a preview.
+async fn audience_preview(
+    pool: &PgPool,
+    audience: &AudienceParameters,
+) -> Result<String, actix_web::Error> {
+    let Some(list_id) = audience.list_id else {
+        return Ok(String::new());
+    };
+    let Some(list_id) = get_list_id(pool, Some(list_id)).await.map_err(e500)? else {
+        return Ok("<p><i>Unknown mailing list.</i></p>".into());
+    };
+    let segment = match parse_segment(&audience.segment) {
+        Ok(segment) => segment,
+        Err(e) => return Ok(format!("<p><i>{}</i></p>", encode_minimal(&e))),
+    };
+    let n_subscribers = count_audience(pool, list_id, segment.as_ref().map(|(_, s)| s))
+        .await
+        .map_err(e500)?;
+    Ok(format!(
+        "<p>This issue would be sent to {n_subscribers} confirmed subscriber{}.</p>",
+        if n_subscribers == 1 { "" } else { "s" }
+    ))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_issue_summary(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<IssueSummary>, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        IssueSummary,
+        r#"
+        SELECT i.title, l.name AS "list_name?", i.segment, i.published_at, i.send_at, i.cancelled_at
+        FROM newsletter_issues i
+        LEFT JOIN lists l USING (list_id)
+        WHERE i.newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the newsletter issue.")?;
+    Ok(issue)
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_delivery_counts(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<DeliveryCounts, anyhow::Error> {
+    let counts = sqlx::query_as!(
+        DeliveryCounts,
+        r#"
+        SELECT
+            count(*) FILTER (WHERE status = 'queued') AS "queued!",
+            count(*) FILTER (WHERE status = 'sent') AS "sent!",
+            count(*) FILTER (WHERE status = 'failed') AS "failed!",
+            count(*) FILTER (WHERE status = 'skipped') AS "skipped!"
+        FROM issue_deliveries
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count the deliveries of a newsletter issue.")?;
+    Ok(counts)
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_revisions(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Vec<Revision>, anyhow::Error> {
+    let revisions = sqlx::query_as!(
+        Revision,
+        r#"
+        SELECT revision, created_at
+        FROM newsletter_issue_revisions
+        WHERE newsletter_issue_id = $1
+        ORDER BY revision DESC
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the revisions of the newsletter issue.")?;
+    Ok(revisions)
+}
@@ -1,5 +1,17 @@
+mod edit;
 mod get;
+mod issue;
 mod post;
+mod preview;
+mod publish;
+mod schedule;
+mod send_test;
 
-pub use get::publish_newsletter_form;
-pub use post::publish_newsletter;
+pub use edit::{edit_newsletter_draft, edit_newsletter_draft_form};
+pub use get::newsletter_issues;
+pub use issue::newsletter_issue;
+pub use post::create_newsletter_draft;
+pub use preview::preview_newsletter_issue;
+pub use publish::publish_newsletter;
+pub use schedule::{cancel_newsletter_issue, reschedule_newsletter_issue};
+pub use send_test::send_test_newsletter;
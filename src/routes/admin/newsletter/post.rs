@@ -1,13 +1,6 @@
-use crate::idempotency::save_response;
-use crate::{
-    authentication::UserId,
-    idempotency::{try_processing, IdempotencyKey, NextAction},
-    utils::{e400, e500, see_other},
-};
-use actix_web::{
-    web::{self, Form, ReqData},
-    HttpResponse,
-};
+use crate::markdown;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
 use sqlx::{Executor, PgPool, Postgres, Transaction};
@@ -16,67 +9,78 @@ use uuid::Uuid;
 #[derive(serde::Deserialize)]
 pub struct FormData {
     title: String,
+    #[serde(default)]
+    markdown_content: String,
+    #[serde(default)]
     text_content: String,
+    #[serde(default)]
     html_content: String,
-    idempotency_key: String,
 }
 
-#[tracing::instrument(
-    name = "Publish a newsletter issue",
-    skip_all,
-    fields(user_id=%&*user_id)
-)]
-pub async fn publish_newsletter(
-    form: Form<FormData>,
+#[tracing::instrument(name = "Create a newsletter draft", skip_all)]
+pub async fn create_newsletter_draft(
+    form: web::Form<FormData>,
     pool: web::Data<PgPool>,
-    user_id: ReqData<UserId>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let user_id = user_id.into_inner();
     let FormData {
         title,
+        markdown_content,
         text_content,
         html_content,
-        idempotency_key,
     } = form.0;
-    let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
-    let mut transaction = match try_processing(&pool, &idempotency_key, *user_id)
+    let (text_content, html_content) = render_bodies(&markdown_content, text_content, html_content);
+    let mut transaction = pool
+        .begin()
         .await
-        .map_err(e500)?
-    {
-        NextAction::StartProcessing(t) => t,
-        NextAction::ReturnSavedResponse(saved_response) => {
-            success_message().send();
-            return Ok(saved_response);
-        }
-    };
-
-    let issue_id = insert_newsletter_issue(&mut transaction, &title, &text_content, &html_content)
-        .await
-        .context("Failed to store newsletter issue details")
+        .context("Failed to acquire a Postgres connection from the pool")
         .map_err(e500)?;
-    enqueue_delivery_tasks(&mut transaction, issue_id)
+    let issue_id = insert_newsletter_draft(
+        &mut transaction,
+        &title,
+        &markdown_content,
+        &text_content,
+        &html_content,
+    )
+    .await
+    .context("Failed to store newsletter draft details")
+    .map_err(e500)?;
+    insert_revision(&mut transaction, issue_id)
         .await
-        .context("Failed to enqueue delivery tasks")
+        .context("Failed to store the first revision of a newsletter draft")
         .map_err(e500)?;
-    let response = see_other("/admin/newsletters");
-    let response = save_response(transaction, &idempotency_key, *user_id, response)
+    transaction
+        .commit()
         .await
+        .context("Failed to commit SQL transaction to store a newsletter draft.")
         .map_err(e500)?;
-    success_message().send();
-    Ok(response)
+    FlashMessage::info("The draft has been saved.").send();
+    Ok(see_other(&format!("/admin/newsletters/{issue_id}")))
 }
 
-fn success_message() -> FlashMessage {
-    FlashMessage::info(
-        "The newsletter issue has been accepted - \
-        emails will go out shortly.",
-    )
+/// Render the bodies the author left empty from the Markdown source.
+pub(super) fn render_bodies(
+    markdown_content: &str,
+    text_content: String,
+    html_content: String,
+) -> (String, String) {
+    let text_content = if text_content.trim().is_empty() {
+        markdown::render_text(markdown_content)
+    } else {
+        text_content
+    };
+    let html_content = if html_content.trim().is_empty() {
+        markdown::render_html(markdown_content)
+    } else {
+        html_content
+    };
+    (text_content, html_content)
 }
 
 #[tracing::instrument(skip_all)]
-async fn insert_newsletter_issue(
+async fn insert_newsletter_draft(
     transaction: &mut Transaction<'_, Postgres>,
     title: &str,
+    markdown_content: &str,
     text_content: &str,
     html_content: &str,
 ) -> Result<Uuid, sqlx::Error> {
@@ -86,14 +90,15 @@ async fn insert_newsletter_issue(
         INSERT INTO newsletter_issues (
             newsletter_issue_id,
             title,
+            markdown_content,
             text_content,
-            html_content,
-            published_at
+            html_content
         )
-        VALUES ($1, $2, $3, $4, now())
+        VALUES ($1, $2, $3, $4, $5)
         "#,
         newsletter_issue_id,
         title,
+        markdown_content,
         text_content,
         html_content
     );
@@ -101,21 +106,41 @@ async fn insert_newsletter_issue(
     Ok(newsletter_issue_id)
 }
 
-#[tracing::instrument(skip_all)]
-async fn enqueue_delivery_tasks(
+/// Snapshot the current content of an issue as its next revision.
+#[tracing::instrument(skip(transaction))]
+pub(super) async fn insert_revision(
     transaction: &mut Transaction<'_, Postgres>,
     newsletter_issue_id: Uuid,
 ) -> Result<(), sqlx::Error> {
     let query = sqlx::query!(
         r#"
-    INSERT INTO issue_delivery_queue (
-        newsletter_issue_id,
-        subscriber_email
-    )
-    SELECT $1, email
-    FROM subscriptions
-    WHERE status = 'confirmed'
-    "#,
+        INSERT INTO newsletter_issue_revisions (
+            newsletter_issue_id,
+            revision,
+            title,
+            markdown_content,
+            text_content,
+            html_content,
+            created_at
+        )
+        SELECT
+            i.newsletter_issue_id,
+            COALESCE(
+                (
+                    SELECT max(revision)
+                    FROM newsletter_issue_revisions
+                    WHERE newsletter_issue_id = $1
+                ),
+                0
+            ) + 1,
+            i.title,
+            i.markdown_content,
+            i.text_content,
+            i.html_content,
+            now()
+        FROM newsletter_issues i
+        WHERE i.newsletter_issue_id = $1
+        "#,
         newsletter_issue_id
     );
     transaction.execute(query).await?;
@@ -0,0 +1,101 @@
+use crate::utils::e500;
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct PreviewParameters {
+    /// Show an older revision instead of the current content.
+    revision: Option<i32>,
+}
+
+struct IssueContent {
+    title: String,
+    text_content: String,
+    html_content: String,
+}
+
+#[tracing::instrument(name = "Preview a newsletter issue", skip(pool))]
+pub async fn preview_newsletter_issue(
+    newsletter_issue_id: web::Path<Uuid>,
+    parameters: web::Query<PreviewParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let content = match parameters.revision {
+        None => get_current_content(&pool, newsletter_issue_id).await,
+        Some(revision) => get_revision_content(&pool, newsletter_issue_id, revision).await,
+    }
+    .map_err(e500)?;
+    let Some(content) = content else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    // The HTML content is what subscribers get, so it is rendered as is.
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                <title>Newsletter Preview</title>
+            </head>
+            <body>
+                <h1>{title}</h1>
+                <h2>HTML</h2>
+                <div>{html_content}</div>
+                <h2>Plain text</h2>
+                <pre>{text_content}</pre>
+                <p><a href="/admin/newsletters/{newsletter_issue_id}">&lt;- Back</a></p>
+            </body>
+            </html>"#,
+            title = encode_minimal(&content.title),
+            html_content = content.html_content,
+            text_content = encode_minimal(&content.text_content),
+        )))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_current_content(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<IssueContent>, anyhow::Error> {
+    let content = sqlx::query_as!(
+        IssueContent,
+        r#"
+        SELECT title, text_content, html_content
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the newsletter issue.")?;
+    Ok(content)
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_revision_content(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    revision: i32,
+) -> Result<Option<IssueContent>, anyhow::Error> {
+    let content = sqlx::query_as!(
+        IssueContent,
+        r#"
+        SELECT title, text_content, html_content
+        FROM newsletter_issue_revisions
+        WHERE newsletter_issue_id = $1 AND revision = $2
+        "#,
+        newsletter_issue_id,
+        revision
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the newsletter issue revision.")?;
+    Ok(content)
+}
@@ -0,0 +1,272 @@
+use crate::idempotency::save_response;
+use crate::merge_tags;
+use crate::routes::get_list_id;
+use crate::segment::Segment;
+use crate::{
+    authentication::UserId,
+    idempotency::{try_processing, IdempotencyKey, NextAction},
+    utils::{e400, e500, see_other},
+};
+use actix_web::{
+    web::{self, Form, ReqData},
+    HttpResponse,
+};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use htmlescape::encode_minimal;
+use sqlx::{Executor, PgPool, Postgres, QueryBuilder, Transaction};
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    idempotency_key: String,
+    /// When the issue should go out, leave empty to send it right away.
+    #[serde(default)]
+    send_at: String,
+    /// The mailing list to send the issue to, the default one if not set.
+    #[serde(default)]
+    list_id: Option<Uuid>,
+    /// A filter on the list's subscribers, leave empty to send to all of them.
+    #[serde(default)]
+    segment: String,
+}
+
+#[tracing::instrument(
+    name = "Publish a newsletter issue",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn publish_newsletter(
+    newsletter_issue_id: web::Path<Uuid>,
+    form: Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let user_id = user_id.into_inner();
+    let FormData {
+        idempotency_key,
+        send_at,
+        list_id,
+        segment,
+    } = form.0;
+    let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
+    let send_at = parse_send_at(&send_at).map_err(e400)?;
+    let list_id = get_list_id(&pool, list_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e400("Unknown mailing list."))?;
+    let issue_page = format!("/admin/newsletters/{newsletter_issue_id}");
+    let Some(contents) = get_issue_contents(&pool, newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let segment = match parse_segment(&segment) {
+        Ok(segment) => segment,
+        Err(e) => {
+            FlashMessage::error(encode_minimal(&e)).send();
+            return Ok(see_other(&issue_page));
+        }
+    };
+    // A typo in a merge tag must not surface once emails are going out.
+    if let Err(e) = contents.iter().try_for_each(|c| merge_tags::validate(c)) {
+        FlashMessage::error(encode_minimal(&e)).send();
+        return Ok(see_other(&issue_page));
+    }
+    let mut transaction = match try_processing(&pool, &idempotency_key, *user_id)
+        .await
+        .map_err(e500)?
+    {
+        NextAction::StartProcessing(t) => t,
+        NextAction::ReturnSavedResponse(saved_response) => {
+            success_message(send_at).send();
+            return Ok(saved_response);
+        }
+    };
+
+    let published = mark_issue_as_published(
+        &mut transaction,
+        newsletter_issue_id,
+        send_at,
+        list_id,
+        segment.as_ref().map(|(text, _)| text.as_str()),
+    )
+    .await
+    .context("Failed to publish the newsletter issue")
+    .map_err(e500)?;
+    if published {
+        let segment = segment.as_ref().map(|(_, segment)| segment);
+        enqueue_delivery_tasks(&mut transaction, newsletter_issue_id, list_id, segment)
+            .await
+            .context("Failed to enqueue delivery tasks")
+            .map_err(e500)?;
+    }
+    let response = see_other(&issue_page);
+    let response = save_response(transaction, &idempotency_key, *user_id, response)
+        .await
+        .map_err(e500)?;
+    if published {
+        success_message(send_at).send();
+    } else {
+        FlashMessage::error("The newsletter issue has already been published.").send();
+    }
+    Ok(response)
+}
+
+fn success_message(send_at: Option<DateTime<Utc>>) -> FlashMessage {
+    match send_at {
+        Some(send_at) if send_at > Utc::now() => FlashMessage::info(format!(
+            "The newsletter issue has been scheduled - \
+            emails will go out at {}.",
+            send_at.to_rfc3339()
+        )),
+        _ => FlashMessage::info(
+            "The newsletter issue has been accepted - \
+            emails will go out shortly.",
+        ),
+    }
+}
+
+/// Parse the `send_at` field of the admin forms.
+///
+/// Accepts RFC 3339 timestamps as well as the zone-less value of a
+/// `datetime-local` input, which is interpreted as UTC.
+/// An empty value means "now".
+pub(super) fn parse_send_at(send_at: &str) -> Result<Option<DateTime<Utc>>, anyhow::Error> {
+    let send_at = send_at.trim();
+    if send_at.is_empty() {
+        return Ok(None);
+    }
+    if let Ok(send_at) = DateTime::parse_from_rfc3339(send_at) {
+        return Ok(Some(send_at.with_timezone(&Utc)));
+    }
+    let send_at = NaiveDateTime::parse_from_str(send_at, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(send_at, "%Y-%m-%dT%H:%M"))
+        .with_context(|| format!("`{}` is not a valid date and time.", send_at))?;
+    Ok(Some(send_at.and_utc()))
+}
+
+/// Parse the `segment` field of the admin forms, an empty one selects everybody.
+/// The trimmed text is kept alongside, to be shown back to the admin.
+pub(super) fn parse_segment(segment: &str) -> Result<Option<(String, Segment)>, String> {
+    let segment = segment.trim();
+    if segment.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some((segment.to_owned(), Segment::parse(segment)?)))
+}
+
+/// The title and bodies of an issue, if it exists.
+#[tracing::instrument(skip(pool))]
+async fn get_issue_contents(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<[String; 3]>, anyhow::Error> {
+    let issue = sqlx::query!(
+        r#"
+        SELECT title, text_content, html_content
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the newsletter issue.")?;
+    Ok(issue.map(|i| [i.title, i.text_content, i.html_content]))
+}
+
+/// Turn a draft into an issue published to `list_id`, or to a segment of it.
+/// Returns `false` if the issue has already been published.
+#[tracing::instrument(skip(transaction))]
+async fn mark_issue_as_published(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    send_at: Option<DateTime<Utc>>,
+    list_id: Uuid,
+    segment: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET published_at = now(), send_at = COALESCE($2, now()), list_id = $3, segment = $4
+        WHERE newsletter_issue_id = $1 AND published_at IS NULL
+        "#,
+        newsletter_issue_id,
+        send_at,
+        list_id,
+        segment
+    );
+    Ok(transaction.execute(query).await?.rows_affected() == 1)
+}
+
+/// Append the `FROM ... WHERE ...` clauses selecting the confirmed
+/// and unsuppressed members of `list_id` that match `segment`, as
+/// `subscriptions s`.
+fn push_audience<'a>(
+    query: &mut QueryBuilder<'a, Postgres>,
+    list_id: Uuid,
+    segment: Option<&Segment>,
+) {
+    query.push(
+        " FROM subscriptions s \
+        JOIN list_memberships m ON m.subscriber_id = s.id \
+        WHERE \
+            m.status = 'confirmed' AND \
+            NOT EXISTS (SELECT 1 FROM suppressions x WHERE x.email_hash = s.email_hash) AND \
+            m.list_id = ",
+    );
+    query.push_bind(list_id);
+    if let Some(segment) = segment {
+        query.push(" AND ");
+        segment.push_sql(query);
+    }
+}
+
+/// How many subscribers an issue sent to `list_id` and `segment` would reach.
+#[tracing::instrument(skip(pool, segment))]
+pub(super) async fn count_audience(
+    pool: &PgPool,
+    list_id: Uuid,
+    segment: Option<&Segment>,
+) -> Result<i64, anyhow::Error> {
+    let mut query = QueryBuilder::new("SELECT count(*)");
+    push_audience(&mut query, list_id, segment);
+    let n_subscribers = query
+        .build_query_scalar()
+        .fetch_one(pool)
+        .await
+        .context("Failed to count the audience of a segment.")?;
+    Ok(n_subscribers)
+}
+
+#[tracing::instrument(skip(transaction, segment))]
+async fn enqueue_delivery_tasks(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    list_id: Uuid,
+    segment: Option<&Segment>,
+) -> Result<(), sqlx::Error> {
+    let mut query = QueryBuilder::new(
+        "WITH enqueued AS ( \
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email) \
+        SELECT ",
+    );
+    query.push_bind(newsletter_issue_id);
+    query.push(", s.email");
+    push_audience(&mut query, list_id, segment);
+    query.push(
+        " RETURNING newsletter_issue_id, subscriber_email \
+        ) \
+        INSERT INTO issue_deliveries ( \
+            newsletter_issue_id, subscriber_email, status, queued_at, updated_at \
+        ) \
+        SELECT newsletter_issue_id, subscriber_email, 'queued', now(), now() \
+        FROM enqueued",
+    );
+    transaction.execute(query.build()).await?;
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use super::publish::parse_send_at;
+use crate::utils::{e400, e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct RescheduleFormData {
+    send_at: String,
+}
+
+#[tracing::instrument(
+    name = "Reschedule a newsletter issue",
+    skip(form, pool),
+    fields(send_at = %form.send_at)
+)]
+pub async fn reschedule_newsletter_issue(
+    newsletter_issue_id: web::Path<Uuid>,
+    form: web::Form<RescheduleFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let send_at = parse_send_at(&form.send_at)
+        .map_err(e400)?
+        .unwrap_or_else(Utc::now);
+    let rescheduled = reschedule_issue(&pool, newsletter_issue_id, send_at)
+        .await
+        .map_err(e500)?;
+    if rescheduled {
+        FlashMessage::info(format!(
+            "The newsletter issue has been rescheduled for {}.",
+            send_at.to_rfc3339()
+        ))
+        .send();
+    } else {
+        FlashMessage::error("The newsletter issue has already gone out or has been cancelled.")
+            .send();
+    }
+    Ok(see_other(&format!(
+        "/admin/newsletters/{newsletter_issue_id}"
+    )))
+}
+
+#[tracing::instrument(name = "Cancel a newsletter issue", skip(pool))]
+pub async fn cancel_newsletter_issue(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let cancelled = cancel_issue(&mut transaction, newsletter_issue_id)
+        .await
+        .context("Failed to cancel a newsletter issue")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to cancel a newsletter issue.")
+        .map_err(e500)?;
+    if cancelled {
+        FlashMessage::info("The newsletter issue has been cancelled.").send();
+    } else {
+        FlashMessage::error("The newsletter issue has already gone out or has been cancelled.")
+            .send();
+    }
+    Ok(see_other(&format!(
+        "/admin/newsletters/{newsletter_issue_id}"
+    )))
+}
+
+/// Move `send_at` of an issue that has not gone out yet.
+/// Returns `false` if the issue is already being delivered, or has been cancelled.
+#[tracing::instrument(skip(pool))]
+async fn reschedule_issue(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    send_at: DateTime<Utc>,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET send_at = $2
+        WHERE
+            newsletter_issue_id = $1 AND
+            send_at > now() AND
+            cancelled_at IS NULL
+        "#,
+        newsletter_issue_id,
+        send_at
+    )
+    .execute(pool)
+    .await
+    .context("Failed to reschedule the newsletter issue.")?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Mark an issue that has not gone out yet as cancelled and drop its pending deliveries.
+/// Returns `false` if the issue is already being delivered, or has been cancelled.
+#[tracing::instrument(skip(transaction))]
+async fn cancel_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET cancelled_at = now()
+        WHERE
+            newsletter_issue_id = $1 AND
+            send_at > now() AND
+            cancelled_at IS NULL
+        "#,
+        newsletter_issue_id
+    );
+    if transaction.execute(query).await?.rows_affected() == 0 {
+        return Ok(false);
+    }
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM issue_deliveries
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    );
+    transaction.execute(query).await?;
+    Ok(true)
+}
@@ -0,0 +1,82 @@
+use crate::authentication::UserId;
+use crate::configuration::SubscriptionSettings;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::issue_delivery_worker::{get_issue, FooterLinks};
+use crate::merge_tags::MergeFields;
+use crate::routes::admin::dashboard::get_username;
+use crate::routes::admin::email::get_user_email;
+use crate::routes::{archive_link, is_archived, preferences_link, unsubscribe_link};
+use crate::startup::{ApplicationBaseUrl, HmacSecret};
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[tracing::instrument(
+    name = "Send a test email of a newsletter issue",
+    skip(pool, email_client, base_url, hmac_secret, settings, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn send_test_newsletter(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+    settings: web::Data<SubscriptionSettings>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let issue_page = format!("/admin/newsletters/{newsletter_issue_id}");
+    let user_id = user_id.into_inner();
+    let email = get_user_email(*user_id, &pool).await.map_err(e500)?;
+    let Some(Ok(email)) = email.map(SubscriberEmail::parse) else {
+        FlashMessage::error("Set your email address before sending a test email.").send();
+        return Ok(see_other(&issue_page));
+    };
+
+    let Some(mut issue) = get_issue(&pool, newsletter_issue_id)
+        .await
+        .context("Failed to retrieve the newsletter issue")
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let username = get_username(*user_id, &pool).await.map_err(e500)?;
+    // Admins are not subscribers, the per-recipient links point nowhere but look the part.
+    let unsubscribe_link = unsubscribe_link(&base_url.0, Uuid::nil(), None, &hmac_secret.0);
+    issue.personalize(&MergeFields {
+        name: &username,
+        email: email.as_ref(),
+        unsubscribe_url: &unsubscribe_link,
+    });
+    let archive_link = is_archived(&pool, newsletter_issue_id)
+        .await
+        .map_err(e500)?
+        .then(|| archive_link(&base_url.0, newsletter_issue_id));
+    issue.add_footer(&FooterLinks {
+        archive: archive_link.as_deref(),
+        preferences: &preferences_link(
+            &base_url.0,
+            Uuid::nil(),
+            settings.preferences_link_ttl(),
+            &hmac_secret.0,
+        ),
+        unsubscribe: &unsubscribe_link,
+    });
+    email_client
+        .send_email(
+            &email,
+            &issue.title,
+            &issue.html_content,
+            &issue.text_content,
+        )
+        .await
+        .context("Failed to send a test email")
+        .map_err(e500)?;
+    FlashMessage::info(format!("A test email has been sent to {}.", email)).send();
+    Ok(see_other(&issue_page))
+}
@@ -0,0 +1,143 @@
+use crate::authentication::UserId;
+use crate::personal_data::{self, Requester};
+use crate::routes::admin::dashboard::get_username;
+use crate::utils::{e500, see_other};
+use actix_web::http::header::{ContentDisposition, ContentType, DispositionParam, DispositionType};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+#[derive(serde::Deserialize)]
+pub struct EmailParameters {
+    email: String,
+}
+
+struct AuditEntry {
+    action: String,
+    email_hash: String,
+    requested_by: String,
+    details: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[tracing::instrument(name = "Show the personal data requests", skip(pool, flash_messages))]
+pub async fn personal_data(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut rows_html = String::new();
+    for e in get_audit_log(&pool).await.map_err(e500)? {
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td><code>{}</code></td><td>{}</td><td>{}</td></tr>",
+            e.created_at.format("%Y-%m-%d %H:%M"),
+            e.action,
+            e.email_hash,
+            encode_minimal(&e.requested_by),
+            encode_minimal(&e.details),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                <title>Personal data</title>
+            </head>
+            <body>
+                {msg_html}
+                <h1>Personal data</h1>
+                <form action="/admin/personal_data/export" method="get">
+                    <label>Email address
+                        <input type="email" name="email">
+                    </label>
+                    <button type="submit">Export</button>
+                </form>
+                <form action="/admin/personal_data/erase" method="post">
+                    <label>Email address
+                        <input type="email" name="email">
+                    </label>
+                    <button type="submit">Erase</button>
+                </form>
+                <h2>Audit trail</h2>
+                <p>Addresses are recorded as the SHA-256 of their lowercase form.</p>
+                <table>
+                    <tr><th>Date</th><th>Action</th><th>Address</th><th>Requested by</th><th>Details</th></tr>
+                    {rows_html}
+                </table>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
+
+/// Download everything we hold on an address as JSON.
+#[tracing::instrument(
+    name = "Export personal data",
+    skip(parameters, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn export_personal_data(
+    parameters: web::Query<EmailParameters>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let username = get_username(*user_id.into_inner(), &pool)
+        .await
+        .map_err(e500)?;
+    let Some(document) = personal_data::export_personal_data(
+        &pool,
+        parameters.email.trim(),
+        Requester::Admin(&username),
+    )
+    .await
+    .map_err(e500)?
+    else {
+        FlashMessage::info("We hold no personal data on that address.").send();
+        return Ok(see_other("/admin/personal_data"));
+    };
+    personal_data_download(&document).map_err(e500)
+}
+
+/// The JSON document as an attachment.
+pub(crate) fn personal_data_download(
+    document: &serde_json::Value,
+) -> Result<HttpResponse, anyhow::Error> {
+    let body =
+        serde_json::to_string_pretty(document).context("Failed to serialize the personal data.")?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::json())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("personal-data.json".into())],
+        })
+        .body(body))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_audit_log(pool: &PgPool) -> Result<Vec<AuditEntry>, anyhow::Error> {
+    let entries = sqlx::query_as!(
+        AuditEntry,
+        r#"
+        SELECT action, email_hash, requested_by, details, created_at
+        FROM personal_data_audit_log
+        ORDER BY created_at DESC
+        LIMIT 100
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the personal data audit log.")?;
+    Ok(entries)
+}
@@ -0,0 +1,6 @@
+mod get;
+mod post;
+
+pub(crate) use get::personal_data_download;
+pub use get::{export_personal_data, personal_data};
+pub use post::erase_personal_data;
@@ -0,0 +1,41 @@
+use crate::authentication::UserId;
+use crate::personal_data::{self, Requester};
+use crate::routes::admin::dashboard::get_username;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    email: String,
+}
+
+#[tracing::instrument(
+    name = "Erase personal data",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn erase_personal_data(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = form.email.trim();
+    if email.is_empty() {
+        FlashMessage::error("Enter the email address to erase.").send();
+        return Ok(see_other("/admin/personal_data"));
+    }
+    let username = get_username(*user_id.into_inner(), &pool)
+        .await
+        .map_err(e500)?;
+    let outcome = personal_data::erase_personal_data(&pool, email, Requester::Admin(&username))
+        .await
+        .map_err(e500)?;
+    if outcome.n_deleted + outcome.n_anonymized == 0 {
+        FlashMessage::info("We hold no personal data on that address.").send();
+    } else {
+        FlashMessage::info("All personal data on that address has been erased.").send();
+    }
+    Ok(see_other("/admin/personal_data"))
+}
@@ -0,0 +1,219 @@
+use crate::utils::e400;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web::{self, Bytes};
+use actix_web::HttpResponse;
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::TryStreamExt;
+use sqlx::PgPool;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Rows are sent to the client in chunks of roughly this size.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(serde::Deserialize)]
+pub struct ExportParameters {
+    /// `csv` or `ndjson`, CSV if empty.
+    #[serde(default)]
+    format: String,
+    /// Only export subscribers with this status, their status in `list_id`
+    /// if it is set.
+    #[serde(default)]
+    status: String,
+    /// Only export subscribers who signed up on this day or later.
+    #[serde(default)]
+    subscribed_from: String,
+    /// Only export subscribers who signed up on this day or earlier.
+    #[serde(default)]
+    subscribed_until: String,
+    /// Only export the members of this list.
+    #[serde(default)]
+    list_id: String,
+}
+
+#[derive(Clone, Copy)]
+enum ExportFormat {
+    Csv,
+    Ndjson,
+}
+
+struct ExportFilters {
+    status: Option<String>,
+    subscribed_from: Option<DateTime<Utc>>,
+    subscribed_until: Option<DateTime<Utc>>,
+    list_id: Option<Uuid>,
+}
+
+#[derive(serde::Serialize)]
+struct ExportedSubscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: String,
+}
+
+impl ExportParameters {
+    fn parse(self) -> Result<(ExportFormat, ExportFilters), anyhow::Error> {
+        let format = match self.format.as_str() {
+            "" | "csv" => ExportFormat::Csv,
+            "ndjson" => ExportFormat::Ndjson,
+            format => anyhow::bail!("`{}` is not a supported export format.", format),
+        };
+        let date = |date: &str| -> Result<Option<NaiveDate>, anyhow::Error> {
+            if date.is_empty() {
+                return Ok(None);
+            }
+            let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .with_context(|| format!("`{}` is not a valid date.", date))?;
+            Ok(Some(date))
+        };
+        let subscribed_from =
+            date(&self.subscribed_from)?.map(|d| d.and_hms_opt(0, 0, 0).unwrap().and_utc());
+        // The range includes the whole of its last day.
+        let subscribed_until = date(&self.subscribed_until)?
+            .and_then(|d| d.succ_opt())
+            .map(|d| d.and_hms_opt(0, 0, 0).unwrap().and_utc());
+        let list_id = match self.list_id.as_str() {
+            "" => None,
+            list_id => Some(
+                list_id
+                    .parse()
+                    .with_context(|| format!("`{}` is not a valid list id.", list_id))?,
+            ),
+        };
+        let filters = ExportFilters {
+            status: Some(self.status).filter(|s| !s.is_empty()),
+            subscribed_from,
+            subscribed_until,
+            list_id,
+        };
+        Ok((format, filters))
+    }
+}
+
+/// Stream the matching subscribers straight from Postgres, without holding
+/// the whole export in memory.
+#[tracing::instrument(name = "Export subscribers", skip(parameters, pool))]
+pub async fn export_subscribers(
+    parameters: web::Query<ExportParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (format, filters) = parameters.into_inner().parse().map_err(e400)?;
+    let (sender, mut receiver) = mpsc::channel(4);
+    let pool = pool.get_ref().clone();
+    tokio::spawn(async move {
+        if let Err(e) = stream_subscribers(&pool, format, &filters, &sender).await {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to export subscribers."
+            );
+            // Cut the response short rather than let it look complete.
+            let _ = sender
+                .send(Err(std::io::Error::other("Failed to export subscribers.")))
+                .await;
+        }
+    });
+    let body = futures_util::stream::poll_fn(move |cx| receiver.poll_recv(cx));
+
+    let (content_type, extension) = match format {
+        ExportFormat::Csv => ("text/csv; charset=utf-8", "csv"),
+        ExportFormat::Ndjson => ("application/x-ndjson", "ndjson"),
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "subscribers.{}",
+                extension
+            ))],
+        })
+        .streaming(body))
+}
+
+/// Send the export to `sender` chunk by chunk, stopping early if the client
+/// went away.
+async fn stream_subscribers(
+    pool: &PgPool,
+    format: ExportFormat,
+    filters: &ExportFilters,
+    sender: &mpsc::Sender<Result<Bytes, std::io::Error>>,
+) -> Result<(), anyhow::Error> {
+    let mut rows = sqlx::query!(
+        r#"
+        SELECT s.id, s.email, s.name, COALESCE(m.status, s.status) AS "status!", s.subscribed_at
+        FROM subscriptions s
+        LEFT JOIN list_memberships m ON m.subscriber_id = s.id AND m.list_id = $4
+        WHERE
+            ($4::uuid IS NULL OR m.list_id IS NOT NULL) AND
+            ($1::text IS NULL OR COALESCE(m.status, s.status) = $1) AND
+            ($2::timestamptz IS NULL OR s.subscribed_at >= $2) AND
+            ($3::timestamptz IS NULL OR s.subscribed_at < $3)
+        ORDER BY s.subscribed_at, s.id
+        "#,
+        filters.status,
+        filters.subscribed_from,
+        filters.subscribed_until,
+        filters.list_id
+    )
+    .fetch(pool);
+
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+    if let ExportFormat::Csv = format {
+        chunk.extend_from_slice(b"id,email,name,status,subscribed_at\n");
+    }
+    while let Some(row) = rows
+        .try_next()
+        .await
+        .context("Failed to fetch the subscribers to export.")?
+    {
+        let subscriber = ExportedSubscriber {
+            id: row.id,
+            email: row.email,
+            name: row.name,
+            status: row.status,
+            subscribed_at: row.subscribed_at.to_rfc3339(),
+        };
+        match format {
+            ExportFormat::Csv => {
+                let subscriber = ExportedSubscriber {
+                    email: defuse_formula(subscriber.email),
+                    name: defuse_formula(subscriber.name),
+                    ..subscriber
+                };
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(false)
+                    .from_writer(&mut chunk);
+                writer.serialize(&subscriber)?;
+                writer.flush()?;
+            }
+            ExportFormat::Ndjson => {
+                serde_json::to_writer(&mut chunk, &subscriber)?;
+                chunk.push(b'\n');
+            }
+        }
+        if chunk.len() >= CHUNK_SIZE {
+            let full_chunk = std::mem::replace(&mut chunk, Vec::with_capacity(CHUNK_SIZE));
+            if sender.send(Ok(full_chunk.into())).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+    if !chunk.is_empty() {
+        let _ = sender.send(Ok(chunk.into())).await;
+    }
+    Ok(())
+}
+
+/// Spreadsheets run any cell starting with one of these characters as a
+/// formula: a leading `'` makes them show subscriber-provided values as text.
+fn defuse_formula(value: String) -> String {
+    if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value
+    }
+}
@@ -0,0 +1,218 @@
+use crate::routes::get_lists;
+use crate::utils::e500;
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct SubscribersParameters {
+    /// Only show the subscribers carrying this tag.
+    tag: Option<String>,
+}
+
+struct SubscriberSummary {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    tags: Vec<String>,
+}
+
+struct SubscriberDetails {
+    email: String,
+    name: String,
+    status: String,
+    tags: Vec<String>,
+    attributes: String,
+}
+
+/// How many subscribers the listing shows at most.
+const PAGE_SIZE: i64 = 100;
+
+#[tracing::instrument(name = "List subscribers", skip(parameters, pool))]
+pub async fn subscribers(
+    parameters: web::Query<SubscribersParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tag = parameters.0.tag.filter(|t| !t.trim().is_empty());
+    let mut rows_html = String::new();
+    for s in get_subscribers(&pool, tag.as_deref()).await.map_err(e500)? {
+        writeln!(
+            rows_html,
+            r#"<tr><td><a href="/admin/subscribers/{}">{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>"#,
+            s.id,
+            encode_minimal(&s.email),
+            encode_minimal(&s.name),
+            s.status,
+            encode_minimal(&s.tags.join(", ")),
+        )
+        .unwrap();
+    }
+
+    let mut list_options = String::new();
+    for l in get_lists(&pool).await.map_err(e500)? {
+        writeln!(
+            list_options,
+            r#"<option value="{}">{}</option>"#,
+            l.list_id,
+            encode_minimal(&l.name)
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                <title>Subscribers</title>
+            </head>
+            <body>
+                <h1>Subscribers</h1>
+                <p><a href="/admin/subscribers/import">Import subscribers from a CSV file</a></p>
+                <form action="/admin/subscribers" method="get">
+                    <label>Tag
+                        <input type="text" name="tag" value="{tag}">
+                    </label>
+                    <button type="submit">Filter</button>
+                </form>
+                <p>Showing the {PAGE_SIZE} most recent subscribers at most.</p>
+                <table>
+                    <tr><th>Email</th><th>Name</th><th>Status</th><th>Tags</th></tr>
+                    {rows_html}
+                </table>
+                <h2>Export</h2>
+                <form action="/admin/subscribers/export" method="get">
+                    <label>Format
+                        <select name="format">
+                            <option value="csv">CSV</option>
+                            <option value="ndjson">NDJSON</option>
+                        </select>
+                    </label>
+                    <label>Status
+                        <select name="status">
+                            <option value="">Any</option>
+                            <option value="confirmed">Confirmed</option>
+                            <option value="pending_confirmation">Pending confirmation</option>
+                            <option value="unsubscribed">Unsubscribed</option>
+                        </select>
+                    </label>
+                    <label>List
+                        <select name="list_id">
+                            <option value="">Any</option>
+                            {list_options}
+                        </select>
+                    </label>
+                    <label>Signed up from
+                        <input type="date" name="subscribed_from">
+                    </label>
+                    <label>until
+                        <input type="date" name="subscribed_until">
+                    </label>
+                    <button type="submit">Export</button>
+                </form>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#,
+            tag = encode_minimal(tag.as_deref().unwrap_or_default()),
+        )))
+}
+
+#[tracing::instrument(name = "Show a subscriber", skip(pool, flash_messages))]
+pub async fn subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let Some(subscriber) = get_subscriber(&pool, subscriber_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                <title>Subscriber</title>
+            </head>
+            <body>
+                {msg_html}
+                <h1>{email}</h1>
+                <p>{name}, {status}</p>
+                <form action="/admin/subscribers/{subscriber_id}" method="post">
+                    <label>Tags (comma-separated, e.g. <code>beta, region:eu</code>)
+                        <input type="text" name="tags" value="{tags}">
+                    </label>
+                    <br>
+                    <label>Attributes (a JSON object, e.g. <code>{{"plan": "pro", "seats": 10}}</code>)
+                        <textarea name="attributes" rows="10" cols="50">{attributes}</textarea>
+                    </label>
+                    <br>
+                    <button type="submit">Save</button>
+                </form>
+                <p><a href="/admin/subscribers">&lt;- Back</a></p>
+            </body>
+            </html>"#,
+            email = encode_minimal(&subscriber.email),
+            name = encode_minimal(&subscriber.name),
+            status = subscriber.status,
+            tags = encode_minimal(&subscriber.tags.join(", ")),
+            attributes = encode_minimal(&subscriber.attributes),
+        )))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_subscribers(
+    pool: &PgPool,
+    tag: Option<&str>,
+) -> Result<Vec<SubscriberSummary>, anyhow::Error> {
+    let subscribers = sqlx::query_as!(
+        SubscriberSummary,
+        r#"
+        SELECT id, email, name, status, tags
+        FROM subscriptions
+        WHERE $1::text IS NULL OR tags @> ARRAY[$1]
+        ORDER BY subscribed_at DESC
+        LIMIT $2
+        "#,
+        tag,
+        PAGE_SIZE
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the subscribers.")?;
+    Ok(subscribers)
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberDetails>, anyhow::Error> {
+    let subscriber = sqlx::query_as!(
+        SubscriberDetails,
+        r#"
+        SELECT email, name, status, tags, jsonb_pretty(attributes) AS "attributes!"
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the subscriber.")?;
+    Ok(subscriber)
+}
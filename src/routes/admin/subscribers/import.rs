@@ -0,0 +1,486 @@
+use crate::routes::{get_list_id, get_lists, list_options};
+use crate::utils::{e500, see_other};
+use actix_multipart::form::{bytes::Bytes, text::Text, MultipartForm};
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::encode_minimal;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// The largest CSV file the upload accepts.
+pub const MAX_IMPORT_SIZE: usize = 10 * 1024 * 1024;
+
+/// How many problematic rows the progress page lists at most.
+const MAX_REPORTED_ROWS: i64 = 500;
+
+#[derive(MultipartForm)]
+pub struct ImportForm {
+    file: Bytes,
+    list_id: Text<Uuid>,
+    /// `confirmed` to skip the confirmation email, which needs a `consent_source`.
+    mode: Text<String>,
+    consent_source: Option<Text<String>>,
+}
+
+/// A row of the uploaded file, before it has been validated.
+struct CsvRow {
+    line: i32,
+    email: String,
+    name: String,
+    /// Set if the row could not be read at all.
+    error: Option<String>,
+}
+
+struct ImportSummary {
+    import_id: Uuid,
+    file_name: String,
+    list_name: String,
+    created_at: DateTime<Utc>,
+    n_rows: i64,
+    n_pending: i64,
+}
+
+struct ImportProgress {
+    file_name: String,
+    list_name: String,
+    confirmed: bool,
+    consent_source: Option<String>,
+    n_rows: i64,
+    n_pending: i64,
+    n_imported: i64,
+    n_skipped: i64,
+    n_invalid: i64,
+    n_failed: i64,
+}
+
+impl ImportProgress {
+    fn percent_done(&self) -> i64 {
+        match self.n_rows {
+            0 => 100,
+            n_rows => (n_rows - self.n_pending) * 100 / n_rows,
+        }
+    }
+}
+
+struct ReportedRow {
+    line: i32,
+    email: String,
+    status: String,
+    message: Option<String>,
+}
+
+pub async fn import_subscribers_form(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let list_options = list_options(&get_lists(&pool).await.map_err(e500)?, None);
+    let mut imports_html = String::new();
+    for i in get_recent_imports(&pool).await.map_err(e500)? {
+        writeln!(
+            imports_html,
+            r#"<tr><td><a href="/admin/subscribers/import/{}">{}</a></td><td>{}</td><td>{}</td><td>{} / {}</td></tr>"#,
+            i.import_id,
+            encode_minimal(&i.file_name),
+            encode_minimal(&i.list_name),
+            i.created_at.to_rfc3339(),
+            i.n_rows - i.n_pending,
+            i.n_rows,
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                <title>Import Subscribers</title>
+            </head>
+            <body>
+                {msg_html}
+                <h1>Import subscribers</h1>
+                <p>Upload a CSV file with a header row naming an <code>email</code> and a <code>name</code> column.</p>
+                <form action="/admin/subscribers/import" method="post" enctype="multipart/form-data">
+                    <label>File
+                        <input type="file" name="file" accept=".csv,text/csv">
+                    </label>
+                    <br>
+                    <label>Mailing list
+                        <select name="list_id">
+                            {list_options}
+                        </select>
+                    </label>
+                    <br>
+                    <label>
+                        <input type="radio" name="mode" value="confirmation_email" checked>
+                        Send each subscriber a confirmation email
+                    </label>
+                    <br>
+                    <label>
+                        <input type="radio" name="mode" value="confirmed">
+                        Import them as confirmed, they already agreed to hear from us
+                    </label>
+                    <br>
+                    <label>How and when did they give their consent?
+                        <input type="text" name="consent_source">
+                    </label>
+                    <br>
+                    <button type="submit">Import</button>
+                </form>
+                <h2>Recent imports</h2>
+                <table>
+                    <tr><th>File</th><th>List</th><th>Started at</th><th>Processed rows</th></tr>
+                    {imports_html}
+                </table>
+                <p><a href="/admin/subscribers">&lt;- Back</a></p>
+            </body>
+            </html>"#,
+        )))
+}
+
+/// Store the rows of the uploaded file, the import worker takes it from there.
+#[tracing::instrument(name = "Start a subscriber import", skip(form, pool))]
+pub async fn import_subscribers(
+    MultipartForm(form): MultipartForm<ImportForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let confirmed = form.mode.as_str() == "confirmed";
+    let consent_source = form
+        .consent_source
+        .map(|c| c.into_inner().trim().to_owned())
+        .filter(|c| !c.is_empty());
+    if confirmed && consent_source.is_none() {
+        FlashMessage::error(
+            "Importing subscribers as confirmed needs a note on how they gave their consent.",
+        )
+        .send();
+        return Ok(see_other("/admin/subscribers/import"));
+    }
+    let Some(list_id) = get_list_id(&pool, Some(*form.list_id))
+        .await
+        .map_err(e500)?
+    else {
+        FlashMessage::error("Unknown mailing list.").send();
+        return Ok(see_other("/admin/subscribers/import"));
+    };
+    let rows = match read_csv(&form.file.data) {
+        Ok(rows) if rows.is_empty() => {
+            FlashMessage::error("The file has no rows to import.").send();
+            return Ok(see_other("/admin/subscribers/import"));
+        }
+        Ok(rows) => rows,
+        Err(e) => {
+            FlashMessage::error(encode_minimal(&e)).send();
+            return Ok(see_other("/admin/subscribers/import"));
+        }
+    };
+
+    let import_id = Uuid::new_v4();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    insert_import(
+        &mut transaction,
+        import_id,
+        list_id,
+        form.file.file_name.as_deref().unwrap_or("upload.csv"),
+        confirmed,
+        consent_source.as_deref(),
+    )
+    .await
+    .map_err(e500)?;
+    insert_rows(&mut transaction, import_id, rows)
+        .await
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a subscriber import.")
+        .map_err(e500)?;
+    FlashMessage::info("The import has started.").send();
+    Ok(see_other(&format!("/admin/subscribers/import/{import_id}")))
+}
+
+#[tracing::instrument(
+    name = "Show the progress of a subscriber import",
+    skip(pool, flash_messages)
+)]
+pub async fn subscriber_import(
+    import_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let import_id = import_id.into_inner();
+    let Some(progress) = get_import_progress(&pool, import_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let refresh_html = if progress.n_pending > 0 {
+        r#"<meta http-equiv="refresh" content="5">"#
+    } else {
+        ""
+    };
+    let mut rows_html = String::new();
+    for r in get_reported_rows(&pool, import_id).await.map_err(e500)? {
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            r.line,
+            encode_minimal(&r.email),
+            r.status,
+            encode_minimal(r.message.as_deref().unwrap_or_default()),
+        )
+        .unwrap();
+    }
+    let mode = match &progress.consent_source {
+        Some(consent_source) if progress.confirmed => format!(
+            "Imported as confirmed, consent: {}",
+            encode_minimal(consent_source)
+        ),
+        _ => "Each subscriber is sent a confirmation email".into(),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                {refresh_html}
+                <title>Subscriber Import</title>
+            </head>
+            <body>
+                {msg_html}
+                <h1>Import of {file_name} into {list_name}</h1>
+                <p>{mode}.</p>
+                <p>Processed {percent_done}% of {n_rows} rows.</p>
+                <table>
+                    <tr><th>Pending</th><td>{n_pending}</td></tr>
+                    <tr><th>Imported</th><td>{n_imported}</td></tr>
+                    <tr><th>Skipped</th><td>{n_skipped}</td></tr>
+                    <tr><th>Invalid</th><td>{n_invalid}</td></tr>
+                    <tr><th>Failed</th><td>{n_failed}</td></tr>
+                </table>
+                <h2>Rows that were not imported</h2>
+                <table>
+                    <tr><th>Line</th><th>Email</th><th>Status</th><th>Reason</th></tr>
+                    {rows_html}
+                </table>
+                <p><a href="/admin/subscribers/import">&lt;- Back</a></p>
+            </body>
+            </html>"#,
+            file_name = encode_minimal(&progress.file_name),
+            list_name = encode_minimal(&progress.list_name),
+            percent_done = progress.percent_done(),
+            n_rows = progress.n_rows,
+            n_pending = progress.n_pending,
+            n_imported = progress.n_imported,
+            n_skipped = progress.n_skipped,
+            n_invalid = progress.n_invalid,
+            n_failed = progress.n_failed,
+        )))
+}
+
+/// Read the `email` and `name` columns of a CSV file with a header row.
+/// Rows that can't be read are kept, to be reported along with invalid ones.
+fn read_csv(data: &[u8]) -> Result<Vec<CsvRow>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(data);
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("The header row can't be read: {}", e))?;
+    let column = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let (Some(email_column), Some(name_column)) = (column("email"), column("name")) else {
+        return Err("The file needs an `email` and a `name` column.".into());
+    };
+
+    let mut rows = Vec::new();
+    // The line is part of a row's key: a record without a position is given
+    // the one after the previous record, starting after the header.
+    let mut last_line = 1;
+    for record in reader.records() {
+        let row = match record {
+            Ok(record) => CsvRow {
+                line: record.position().map_or(last_line + 1, |p| p.line() as i32),
+                email: record.get(email_column).unwrap_or_default().to_owned(),
+                name: record.get(name_column).unwrap_or_default().to_owned(),
+                error: None,
+            },
+            Err(e) => CsvRow {
+                line: e.position().map_or(last_line + 1, |p| p.line() as i32),
+                email: String::new(),
+                name: String::new(),
+                error: Some(format!("The row can't be read: {}", e)),
+            },
+        };
+        last_line = row.line;
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[tracing::instrument(skip(transaction))]
+async fn insert_import(
+    transaction: &mut Transaction<'_, Postgres>,
+    import_id: Uuid,
+    list_id: Uuid,
+    file_name: &str,
+    confirmed: bool,
+    consent_source: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            INSERT INTO subscriber_imports
+                (import_id, list_id, file_name, confirmed, consent_source, created_at)
+            VALUES ($1, $2, $3, $4, $5, now())
+            "#,
+            import_id,
+            list_id,
+            file_name,
+            confirmed,
+            consent_source
+        ))
+        .await
+        .context("Failed to store a subscriber import.")?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(transaction, rows), fields(n_rows = rows.len()))]
+async fn insert_rows(
+    transaction: &mut Transaction<'_, Postgres>,
+    import_id: Uuid,
+    rows: Vec<CsvRow>,
+) -> Result<(), anyhow::Error> {
+    let mut lines = Vec::with_capacity(rows.len());
+    let mut emails = Vec::with_capacity(rows.len());
+    let mut names = Vec::with_capacity(rows.len());
+    let mut statuses = Vec::with_capacity(rows.len());
+    let mut messages = Vec::with_capacity(rows.len());
+    for row in rows {
+        lines.push(row.line);
+        emails.push(row.email);
+        names.push(row.name);
+        statuses.push(if row.error.is_some() {
+            "invalid"
+        } else {
+            "pending"
+        });
+        messages.push(row.error);
+    }
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            INSERT INTO subscriber_import_rows (import_id, line, email, name, status, message)
+            SELECT $1, * FROM UNNEST($2::int4[], $3::text[], $4::text[], $5::text[], $6::text[])
+            "#,
+            import_id,
+            &lines,
+            &emails,
+            &names,
+            &statuses as &[&str],
+            &messages as &[Option<String>]
+        ))
+        .await
+        .context("Failed to store the rows of a subscriber import.")?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_recent_imports(pool: &PgPool) -> Result<Vec<ImportSummary>, anyhow::Error> {
+    let imports = sqlx::query_as!(
+        ImportSummary,
+        r#"
+        SELECT
+            i.import_id,
+            i.file_name,
+            l.name AS list_name,
+            i.created_at,
+            count(r.line) AS "n_rows!",
+            count(r.line) FILTER (WHERE r.status = 'pending') AS "n_pending!"
+        FROM subscriber_imports i
+        JOIN lists l USING (list_id)
+        LEFT JOIN subscriber_import_rows r USING (import_id)
+        GROUP BY i.import_id, l.name
+        ORDER BY i.created_at DESC
+        LIMIT 20
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the recent subscriber imports.")?;
+    Ok(imports)
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_import_progress(
+    pool: &PgPool,
+    import_id: Uuid,
+) -> Result<Option<ImportProgress>, anyhow::Error> {
+    let progress = sqlx::query_as!(
+        ImportProgress,
+        r#"
+        SELECT
+            i.file_name,
+            l.name AS list_name,
+            i.confirmed,
+            i.consent_source,
+            count(r.line) AS "n_rows!",
+            count(r.line) FILTER (WHERE r.status = 'pending') AS "n_pending!",
+            count(r.line) FILTER (WHERE r.status = 'imported') AS "n_imported!",
+            count(r.line) FILTER (WHERE r.status = 'skipped') AS "n_skipped!",
+            count(r.line) FILTER (WHERE r.status = 'invalid') AS "n_invalid!",
+            count(r.line) FILTER (WHERE r.status = 'failed') AS "n_failed!"
+        FROM subscriber_imports i
+        JOIN lists l USING (list_id)
+        LEFT JOIN subscriber_import_rows r USING (import_id)
+        WHERE i.import_id = $1
+        GROUP BY i.import_id, l.name
+        "#,
+        import_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the progress of a subscriber import.")?;
+    Ok(progress)
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_reported_rows(
+    pool: &PgPool,
+    import_id: Uuid,
+) -> Result<Vec<ReportedRow>, anyhow::Error> {
+    let rows = sqlx::query_as!(
+        ReportedRow,
+        r#"
+        SELECT line, email, status, message
+        FROM subscriber_import_rows
+        WHERE import_id = $1 AND status NOT IN ('pending', 'imported')
+        ORDER BY line
+        LIMIT $2
+        "#,
+        import_id,
+        MAX_REPORTED_ROWS
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the rows of a subscriber import.")?;
+    Ok(rows)
+}
@@ -0,0 +1,9 @@
+mod export;
+mod get;
+mod import;
+mod post;
+
+pub use export::export_subscribers;
+pub use get::{subscriber, subscribers};
+pub use import::{import_subscribers, import_subscribers_form, subscriber_import, MAX_IMPORT_SIZE};
+pub use post::update_subscriber;
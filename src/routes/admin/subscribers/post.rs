@@ -0,0 +1,104 @@
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    tags: String,
+    attributes: String,
+}
+
+#[tracing::instrument(name = "Update a subscriber's tags and attributes", skip(form, pool))]
+pub async fn update_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let subscriber_page = format!("/admin/subscribers/{subscriber_id}");
+    let (tags, attributes) = match (parse_tags(&form.tags), parse_attributes(&form.attributes)) {
+        (Ok(tags), Ok(attributes)) => (tags, attributes),
+        (Err(e), _) | (_, Err(e)) => {
+            FlashMessage::error(encode_minimal(&e)).send();
+            return Ok(see_other(&subscriber_page));
+        }
+    };
+    if !update_tags_and_attributes(&pool, subscriber_id, &tags, &attributes)
+        .await
+        .map_err(e500)?
+    {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    FlashMessage::info("The subscriber has been updated.").send();
+    Ok(see_other(&subscriber_page))
+}
+
+/// Characters that would stop a tag or key from being used in a segment.
+const RESERVED: &str = "()=!<>\"";
+
+fn check_name(kind: &str, name: &str) -> Result<(), String> {
+    if name
+        .chars()
+        .any(|c| c.is_whitespace() || RESERVED.contains(c))
+    {
+        return Err(format!(
+            "The {} `{}` can't contain spaces nor any of {}.",
+            kind, name, RESERVED
+        ));
+    }
+    Ok(())
+}
+
+/// Split a comma-separated list of tags, dropping empty entries and duplicates.
+fn parse_tags(tags: &str) -> Result<Vec<String>, String> {
+    let mut parsed: Vec<String> = Vec::new();
+    for tag in tags.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+        check_name("tag", tag)?;
+        if !parsed.iter().any(|t| t == tag) {
+            parsed.push(tag.to_owned());
+        }
+    }
+    Ok(parsed)
+}
+
+/// Check that the attributes are a JSON object, an empty field clears them.
+fn parse_attributes(attributes: &str) -> Result<String, String> {
+    if attributes.trim().is_empty() {
+        return Ok("{}".into());
+    }
+    let Ok(serde_json::Value::Object(attributes)) = serde_json::from_str(attributes) else {
+        return Err("The attributes must be a JSON object.".into());
+    };
+    for key in attributes.keys() {
+        check_name("attribute", key)?;
+    }
+    Ok(serde_json::Value::Object(attributes).to_string())
+}
+
+/// Returns `false` if the subscriber does not exist.
+#[tracing::instrument(skip(pool, attributes))]
+async fn update_tags_and_attributes(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    tags: &[String],
+    attributes: &str,
+) -> Result<bool, anyhow::Error> {
+    let n_updated = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET tags = $2, attributes = $3::text::jsonb
+        WHERE id = $1
+        "#,
+        subscriber_id,
+        tags,
+        attributes
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update the subscriber's tags and attributes.")?
+    .rows_affected();
+    Ok(n_updated == 1)
+}
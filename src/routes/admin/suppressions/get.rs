@@ -0,0 +1,116 @@
+use crate::suppression::SuppressionReason;
+use crate::utils::e500;
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+struct Suppression {
+    /// `None` once the owner of the address has had their data erased.
+    email: Option<String>,
+    reason: String,
+    source: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[tracing::instrument(name = "Show the suppression list", skip(pool, flash_messages))]
+pub async fn suppressions(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut rows_html = String::new();
+    for s in get_suppressions(&pool).await.map_err(e500)? {
+        let reason = SuppressionReason::parse(&s.reason).map_or(s.reason.as_str(), |r| r.label());
+        // An erased address can't be shown, nor lifted.
+        let (email, remove_form) = match &s.email {
+            Some(email) => {
+                let email = encode_minimal(email);
+                let remove_form = format!(
+                    r#"<form action="/admin/suppressions/remove" method="post">
+                    <input type="hidden" name="email" value="{email}">
+                    <button type="submit">Remove</button>
+                </form>"#
+                );
+                (email, remove_form)
+            }
+            None => ("<i>Erased address</i>".to_owned(), String::new()),
+        };
+        writeln!(
+            rows_html,
+            r#"<tr><td>{email}</td><td>{reason}</td><td>{source}</td><td>{created_at}</td><td>
+                {remove_form}
+            </td></tr>"#,
+            reason = encode_minimal(reason),
+            source = encode_minimal(&s.source),
+            created_at = s.created_at.format("%Y-%m-%d %H:%M"),
+        )
+        .unwrap();
+    }
+    let mut reasons_html = String::new();
+    for r in [
+        SuppressionReason::DoNotContact,
+        SuppressionReason::HardBounce,
+        SuppressionReason::SpamComplaint,
+    ] {
+        writeln!(
+            reasons_html,
+            r#"<option value="{}">{}</option>"#,
+            r.as_str(),
+            r.label()
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                <title>Suppression list</title>
+            </head>
+            <body>
+                {msg_html}
+                <h1>Suppression list</h1>
+                <p>We never email these addresses, whatever their subscriptions say.</p>
+                <form action="/admin/suppressions" method="post">
+                    <label>Email address
+                        <input type="email" name="email">
+                    </label>
+                    <label>Reason
+                        <select name="reason">{reasons_html}</select>
+                    </label>
+                    <button type="submit">Suppress</button>
+                </form>
+                <table>
+                    <tr><th>Email</th><th>Reason</th><th>Source</th><th>Since</th><th></th></tr>
+                    {rows_html}
+                </table>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_suppressions(pool: &PgPool) -> Result<Vec<Suppression>, anyhow::Error> {
+    let suppressions = sqlx::query_as!(
+        Suppression,
+        r#"
+        SELECT email, reason, source, created_at
+        FROM suppressions
+        ORDER BY created_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the suppression list.")?;
+    Ok(suppressions)
+}
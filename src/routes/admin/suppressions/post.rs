@@ -0,0 +1,79 @@
+use crate::authentication::UserId;
+use crate::domain::SubscriberEmail;
+use crate::personal_data::email_hash;
+use crate::routes::admin::dashboard::get_username;
+use crate::suppression::{suppress, SuppressionReason};
+use crate::utils::{e400, e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct AddFormData {
+    email: String,
+    reason: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RemoveFormData {
+    email: String,
+}
+
+#[tracing::instrument(
+    name = "Add a suppression",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn add_suppression(
+    form: web::Form<AddFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let reason = SuppressionReason::parse(&form.reason).map_err(e400)?;
+    let email = match SubscriberEmail::parse(form.0.email) {
+        Ok(email) => email,
+        Err(e) => {
+            FlashMessage::error(encode_minimal(&e)).send();
+            return Ok(see_other("/admin/suppressions"));
+        }
+    };
+    let username = get_username(*user_id.into_inner(), &pool)
+        .await
+        .map_err(e500)?;
+    let added = suppress(pool.get_ref(), email.as_ref(), reason, &username)
+        .await
+        .context("Failed to add a suppression.")
+        .map_err(e500)?;
+    let email = encode_minimal(email.as_ref());
+    if added {
+        FlashMessage::info(format!("{email} has been added to the suppression list.")).send();
+    } else {
+        FlashMessage::info(format!("{email} is already on the suppression list.")).send();
+    }
+    Ok(see_other("/admin/suppressions"))
+}
+
+/// Lifting a suppression does not subscribe anybody back: it only lets them
+/// sign up again.
+#[tracing::instrument(name = "Remove a suppression", skip(form, pool))]
+pub async fn remove_suppression(
+    form: web::Form<RemoveFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    sqlx::query!(
+        "DELETE FROM suppressions WHERE email_hash = $1",
+        email_hash(&form.email)
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to remove a suppression.")
+    .map_err(e500)?;
+    FlashMessage::info(format!(
+        "{} has been removed from the suppression list.",
+        encode_minimal(&form.email)
+    ))
+    .send();
+    Ok(see_other("/admin/suppressions"))
+}
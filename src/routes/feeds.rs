@@ -0,0 +1,215 @@
+use super::issues::{archive_link, get_archived_issues, ArchivedIssue};
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::e500;
+use actix_web::http::header::{
+    ETag, EntityTag, Header, HttpDate, IfModifiedSince, IfNoneMatch, LastModified,
+};
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use std::time::SystemTime;
+
+const FEED_TITLE: &str = "Newsletter";
+
+/// Cheap summary of the archive, enough to tell whether a feed has changed
+/// since a poller last fetched it.
+struct ArchiveVersion {
+    n_issues: i64,
+    last_sent_at: Option<DateTime<Utc>>,
+}
+
+impl ArchiveVersion {
+    /// Published issues can no longer be edited, so the archive only changes
+    /// when an issue goes out: its size and latest date identify it.
+    fn etag(&self) -> EntityTag {
+        EntityTag::new_strong(format!(
+            "{}-{}",
+            self.n_issues,
+            self.last_sent_at.map_or(0, |t| t.timestamp_micros())
+        ))
+    }
+
+    fn last_modified(&self) -> Option<HttpDate> {
+        self.last_sent_at
+            .map(|t| HttpDate::from(SystemTime::from(t)))
+    }
+
+    /// Whether the client's cached copy, as described by its conditional
+    /// headers, is still current.
+    fn is_fresh(&self, request: &HttpRequest) -> bool {
+        // `If-None-Match` takes precedence over `If-Modified-Since` when both are sent.
+        if request.headers().contains_key(IfNoneMatch::name()) {
+            return match IfNoneMatch::parse(request) {
+                Ok(IfNoneMatch::Any) => true,
+                Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&self.etag())),
+                Err(_) => false,
+            };
+        }
+        match (IfModifiedSince::parse(request), self.last_sent_at) {
+            // HTTP dates have a one second resolution.
+            (Ok(IfModifiedSince(since)), Some(last_sent_at)) => {
+                last_sent_at.timestamp()
+                    <= DateTime::<Utc>::from(SystemTime::from(since)).timestamp()
+            }
+            _ => false,
+        }
+    }
+}
+
+#[tracing::instrument(name = "Serve the RSS feed", skip_all)]
+pub async fn rss_feed(
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    serve_feed(
+        &request,
+        &pool,
+        "application/rss+xml; charset=utf-8",
+        |issues| render_rss(&base_url.0, issues),
+    )
+    .await
+}
+
+#[tracing::instrument(name = "Serve the Atom feed", skip_all)]
+pub async fn atom_feed(
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    serve_feed(
+        &request,
+        &pool,
+        "application/atom+xml; charset=utf-8",
+        |issues| render_atom(&base_url.0, issues),
+    )
+    .await
+}
+
+/// Answer with a `304 Not Modified` if the client is up to date, without
+/// loading the issues themselves, or with the rendered feed otherwise.
+async fn serve_feed(
+    request: &HttpRequest,
+    pool: &PgPool,
+    content_type: &str,
+    render: impl FnOnce(&[ArchivedIssue]) -> String,
+) -> Result<HttpResponse, actix_web::Error> {
+    let version = get_archive_version(pool).await.map_err(e500)?;
+    let is_fresh = version.is_fresh(request);
+    let mut response = if is_fresh {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    response.insert_header(ETag(version.etag()));
+    if let Some(last_modified) = version.last_modified() {
+        response.insert_header(LastModified(last_modified));
+    }
+    if is_fresh {
+        return Ok(response.finish());
+    }
+
+    let issues = get_archived_issues(pool).await.map_err(e500)?;
+    Ok(response.content_type(content_type).body(render(&issues)))
+}
+
+fn render_rss(base_url: &str, issues: &[ArchivedIssue]) -> String {
+    let mut items_xml = String::new();
+    for issue in issues {
+        let link = encode_minimal(&archive_link(base_url, issue.newsletter_issue_id));
+        writeln!(
+            items_xml,
+            r#"<item>
+                <title>{title}</title>
+                <link>{link}</link>
+                <guid isPermaLink="true">{link}</guid>
+                <pubDate>{pub_date}</pubDate>
+                <description>{description}</description>
+            </item>"#,
+            title = encode_minimal(&issue.title()),
+            pub_date = issue.sent_at.to_rfc2822(),
+            description = encode_minimal(&issue.html_content()),
+        )
+        .unwrap();
+    }
+
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+        <rss version="2.0">
+            <channel>
+                <title>{FEED_TITLE}</title>
+                <link>{link}</link>
+                <description>Past issues of the newsletter</description>
+                {items_xml}
+            </channel>
+        </rss>"#,
+        link = encode_minimal(&format!("{}/issues", base_url)),
+    )
+}
+
+fn render_atom(base_url: &str, issues: &[ArchivedIssue]) -> String {
+    let mut entries_xml = String::new();
+    for issue in issues {
+        writeln!(
+            entries_xml,
+            r#"<entry>
+                <id>urn:uuid:{id}</id>
+                <title type="text">{title}</title>
+                <link rel="alternate" type="text/html" href="{link}"/>
+                <published>{sent_at}</published>
+                <updated>{sent_at}</updated>
+                <content type="html">{content}</content>
+            </entry>"#,
+            id = issue.newsletter_issue_id,
+            title = encode_minimal(&issue.title()),
+            link = encode_minimal(&archive_link(base_url, issue.newsletter_issue_id)),
+            sent_at = issue.sent_at.to_rfc3339(),
+            content = encode_minimal(&issue.html_content()),
+        )
+        .unwrap();
+    }
+
+    // Issues are sorted from the most recent one.
+    let updated = issues
+        .first()
+        .map_or(DateTime::UNIX_EPOCH, |issue| issue.sent_at);
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+        <feed xmlns="http://www.w3.org/2005/Atom">
+            <id>{archive}</id>
+            <title type="text">{FEED_TITLE}</title>
+            <updated>{updated}</updated>
+            <link rel="self" type="application/atom+xml" href="{base_url}/feed.atom"/>
+            <link rel="alternate" type="text/html" href="{archive}"/>
+            {entries_xml}
+        </feed>"#,
+        archive = encode_minimal(&format!("{}/issues", base_url)),
+        base_url = encode_minimal(base_url),
+        updated = updated.to_rfc3339(),
+    )
+}
+
+/// Must count the same issues as `get_archived_issues`.
+#[tracing::instrument(skip(pool))]
+async fn get_archive_version(pool: &PgPool) -> Result<ArchiveVersion, anyhow::Error> {
+    let version = sqlx::query_as!(
+        ArchiveVersion,
+        r#"
+        SELECT count(*) AS "n_issues!", max(send_at) AS last_sent_at
+        FROM newsletter_issues
+        WHERE
+            published_at IS NOT NULL AND
+            cancelled_at IS NULL AND
+            send_at <= now() AND
+            segment IS NULL AND
+            list_id = (SELECT list_id FROM lists WHERE is_default)
+        "#
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to retrieve the version of the archive.")?;
+    Ok(version)
+}
@@ -1,7 +1,33 @@
-use actix_web::{http::header::ContentType, HttpResponse};
+use crate::routes::{get_lists, list_options};
+use crate::utils::e500;
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use sqlx::PgPool;
 
-pub async fn home() -> HttpResponse {
-    HttpResponse::Ok()
+pub async fn home(pool: web::Data<PgPool>) -> Result<HttpResponse, actix_web::Error> {
+    let list_options = list_options(&get_lists(&pool).await.map_err(e500)?, None);
+    Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
-        .body(include_str!("home.html"))
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+  <head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Home</title>
+  </head>
+  <body>
+    <p>Welcome to our newsletter!</p>
+    <form action="/subscriptions" method="post">
+      <label>Name <input type="text" name="name"></label>
+      <label>Email address <input type="email" name="email"></label>
+      <label>Mailing list
+        <select name="list_id">
+          {list_options}
+        </select>
+      </label>
+      <button type="submit">Subscribe</button>
+    </form>
+    <p><a href="/issues">Past issues</a></p>
+  </body>
+</html>"#
+        )))
 }
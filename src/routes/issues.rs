@@ -0,0 +1,172 @@
+use crate::merge_tags::{self, MergeFields};
+use crate::utils::e500;
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// What merge tags turn into on the public archive, where there is no recipient.
+const ANONYMOUS_READER: MergeFields<'static> = MergeFields {
+    name: "reader",
+    email: "",
+    unsubscribe_url: "/",
+};
+
+pub(super) struct ArchivedIssue {
+    pub(super) newsletter_issue_id: Uuid,
+    title: String,
+    html_content: String,
+    pub(super) sent_at: DateTime<Utc>,
+}
+
+impl ArchivedIssue {
+    pub(super) fn title(&self) -> String {
+        merge_tags::render_text(&self.title, &ANONYMOUS_READER)
+    }
+
+    pub(super) fn html_content(&self) -> String {
+        merge_tags::render_html(&self.html_content, &ANONYMOUS_READER)
+    }
+}
+
+/// Build the public link to the web version of an issue.
+pub fn archive_link(base_url: &str, newsletter_issue_id: Uuid) -> String {
+    format!("{}/issues/{}", base_url, newsletter_issue_id)
+}
+
+/// Whether an issue can be read on the public archive, so that emails only
+/// link to web versions that exist.
+pub(crate) async fn is_archived(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    Ok(get_archived_issue(pool, newsletter_issue_id)
+        .await?
+        .is_some())
+}
+
+#[tracing::instrument(name = "List archived issues", skip(pool))]
+pub async fn issues_archive(pool: web::Data<PgPool>) -> Result<HttpResponse, actix_web::Error> {
+    let issues = get_archived_issues(&pool).await.map_err(e500)?;
+    let mut issues_html = String::new();
+    for issue in &issues {
+        writeln!(
+            issues_html,
+            r#"<li><a href="/issues/{}">{}</a> ({})</li>"#,
+            issue.newsletter_issue_id,
+            encode_minimal(&issue.title()),
+            issue.sent_at.format("%Y-%m-%d"),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                <title>Newsletter Archive</title>
+                <link rel="alternate" type="application/rss+xml" href="/feed.rss">
+                <link rel="alternate" type="application/atom+xml" href="/feed.atom">
+            </head>
+            <body>
+                <h1>Newsletter archive</h1>
+                <ul>
+                    {issues_html}
+                </ul>
+            </body>
+            </html>"#
+        )))
+}
+
+#[tracing::instrument(name = "Show an archived issue", skip(pool))]
+pub async fn archived_issue(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(issue) = get_archived_issue(&pool, newsletter_issue_id.into_inner())
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                <title>{title}</title>
+            </head>
+            <body>
+                <h1>{title}</h1>
+                <p>{sent_at}</p>
+                {html_content}
+                <p><a href="/issues">&lt;- All issues</a></p>
+            </body>
+            </html>"#,
+            title = encode_minimal(&issue.title()),
+            sent_at = issue.sent_at.format("%Y-%m-%d"),
+            html_content = issue.html_content(),
+        )))
+}
+
+/// Issues that have gone out to everyone on the default list: drafts, scheduled
+/// and cancelled issues are not public, nor are issues sent to another list or
+/// to a segment, whose audience was restricted on purpose.
+#[tracing::instrument(skip(pool))]
+pub(super) async fn get_archived_issues(
+    pool: &PgPool,
+) -> Result<Vec<ArchivedIssue>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        ArchivedIssue,
+        r#"
+        SELECT newsletter_issue_id, title, html_content, send_at AS "sent_at!"
+        FROM newsletter_issues
+        WHERE
+            published_at IS NOT NULL AND
+            cancelled_at IS NULL AND
+            send_at <= now() AND
+            segment IS NULL AND
+            list_id = (SELECT list_id FROM lists WHERE is_default)
+        ORDER BY send_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the archived issues.")?;
+    Ok(issues)
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_archived_issue(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<ArchivedIssue>, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        ArchivedIssue,
+        r#"
+        SELECT newsletter_issue_id, title, html_content, send_at AS "sent_at!"
+        FROM newsletter_issues
+        WHERE
+            newsletter_issue_id = $1 AND
+            published_at IS NOT NULL AND
+            cancelled_at IS NULL AND
+            send_at <= now() AND
+            segment IS NULL AND
+            list_id = (SELECT list_id FROM lists WHERE is_default)
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the archived issue.")?;
+    Ok(issue)
+}
@@ -1,10 +1,14 @@
+use crate::configuration::SubscriptionSettings;
 use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
 use crate::email_client::EmailClient;
+use crate::personal_data::email_hash;
+use crate::routes::get_list_id;
 use crate::startup::ApplicationBaseUrl;
+use crate::suppression::is_suppressed;
 use actix_web::http::StatusCode;
 use actix_web::{web, HttpResponse, ResponseError};
 use anyhow::Context;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use sqlx::types::Uuid;
@@ -14,6 +18,9 @@ use sqlx::{Executor, PgPool, Postgres, Transaction};
 pub struct FormData {
     email: String,
     name: String,
+    /// The mailing list to join, the default one if not set.
+    #[serde(default)]
+    list_id: Option<Uuid>,
 }
 
 impl TryFrom<FormData> for NewSubscriber {
@@ -27,7 +34,7 @@ impl TryFrom<FormData> for NewSubscriber {
 }
 
 #[tracing::instrument(name = "Adding a new subscriber",
-    skip(form, pool, email_client, base_url),
+    skip(form, pool, email_client, base_url, settings),
     fields(
         subscriber_email = %form.email,
         subscriber_name = %form.name
@@ -37,35 +44,100 @@ pub async fn subscribe(
     pool: web::Data<PgPool>,
     email_client: web::Data<EmailClient>,
     base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
 ) -> Result<HttpResponse, SubscribeError> {
-    let new_subscriber = form.0.try_into().map_err(SubscribeError::ValidationError)?;
+    let token_ttl = settings.confirmation_token_ttl();
+    let list_id = get_list_id(&pool, form.list_id)
+        .await?
+        .ok_or_else(|| SubscribeError::ValidationError("Unknown mailing list.".into()))?;
+    let new_subscriber: NewSubscriber =
+        form.0.try_into().map_err(SubscribeError::ValidationError)?;
+    // The response is the same whether the address is new, already known or
+    // suppressed, so that it can't be used to find out who is subscribed.
+    if is_suppressed(pool.get_ref(), new_subscriber.email.as_ref())
+        .await
+        .context("Failed to check the suppression list.")?
+    {
+        tracing::info!("Ignoring a sign-up from a suppressed address.");
+        return Ok(HttpResponse::Ok().finish());
+    }
     let mut transaction = pool
         .begin()
         .await
         .context("Failed to acquire a Postgres connection from the pool")?;
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
-        .await
-        .context("Failed to insert new subscriber in the database.")?;
-    let subscription_token = generate_subscription_token();
-    store_token(&mut transaction, subscriber_id, &subscription_token)
-        .await
-        .context("Failed to store the confirmation token for a new subscriber.")?;
+    let subscription_token = sign_up(&mut transaction, &new_subscriber, list_id, token_ttl).await?;
 
     transaction
         .commit()
         .await
         .context("Failed to commit SQL transaction to store a new subscriber.")?;
-    send_confirmation_email(
-        &email_client,
-        new_subscriber,
-        &base_url.0,
-        &subscription_token,
-    )
-    .await
-    .context("Failed to send a confirmation email?")?;
+    if let Some(subscription_token) = subscription_token {
+        send_confirmation_email(
+            &email_client,
+            &new_subscriber,
+            &base_url.0,
+            &subscription_token,
+        )
+        .await
+        .context("Failed to send a confirmation email?")?;
+    }
     Ok(HttpResponse::Ok().finish())
 }
 
+/// Add `new_subscriber` to `list_id`, pending confirmation, whether the address
+/// is new or already known.
+/// Returns the token to send them, or `None` if they are a confirmed member already.
+#[tracing::instrument(name = "Sign a subscriber up", skip(transaction, new_subscriber))]
+pub(crate) async fn sign_up(
+    transaction: &mut Transaction<'_, Postgres>,
+    new_subscriber: &NewSubscriber,
+    list_id: Uuid,
+    token_ttl: chrono::Duration,
+) -> Result<Option<String>, anyhow::Error> {
+    let subscriber_id = match insert_subscriber(transaction, new_subscriber)
+        .await
+        .context("Failed to insert new subscriber in the database.")?
+    {
+        Some(subscriber_id) => subscriber_id,
+        None => get_subscriber_id_by_email(transaction, &new_subscriber.email)
+            .await
+            .context("Failed to retrieve an existing subscriber.")?
+            .context("A conflicting subscriber could not be found.")?,
+    };
+    request_membership(transaction, subscriber_id, list_id, token_ttl).await
+}
+
+/// Ask `subscriber_id` to confirm they want to be on `list_id`.
+/// Returns the token to send them, or `None` if they are a confirmed member already.
+#[tracing::instrument(name = "Request a list membership", skip(transaction))]
+pub(crate) async fn request_membership(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+    token_ttl: chrono::Duration,
+) -> Result<Option<String>, anyhow::Error> {
+    let subscription_token = match join_list(transaction, subscriber_id, list_id)
+        .await
+        .context("Failed to add the subscriber to the mailing list.")?
+        .as_str()
+    {
+        // Confirmed members have nothing to do.
+        "confirmed" => None,
+        _ => match get_signup_token(transaction, subscriber_id, list_id)
+            .await
+            .context("Failed to retrieve a confirmation token.")?
+        {
+            Some(subscription_token) => Some(subscription_token),
+            None => Some(
+                issue_token(transaction, subscriber_id, list_id, token_ttl)
+                    .await
+                    .context("Failed to store the confirmation token for a new subscriber.")?,
+            ),
+        },
+    };
+    Ok(subscription_token)
+}
+
 #[derive(thiserror::Error)]
 pub enum SubscribeError {
     #[error("{0}")]
@@ -93,27 +165,55 @@ impl ResponseError for SubscribeError {
     name = "Saving new subscriber details in the database",
     skip(new_subscriber, transaction)
 )]
+/// Returns `None`, without touching the existing row, if the email address
+/// is already subscribed.
 pub async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     new_subscriber: &NewSubscriber,
-) -> Result<Uuid, sqlx::Error> {
+) -> Result<Option<Uuid>, sqlx::Error> {
     let subscriber_id = Uuid::new_v4();
     let query = sqlx::query!(
         r#"
-    INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-    VALUES($1, $2, $3, $4, 'pending_confirmation')
+    INSERT INTO subscriptions (id, email, email_hash, name, subscribed_at, status)
+    VALUES($1, $2, $3, $4, $5, 'pending_confirmation')
+    ON CONFLICT (email) DO NOTHING
     "#,
         subscriber_id,
         new_subscriber.email.as_ref(),
+        email_hash(new_subscriber.email.as_ref()),
         new_subscriber.name.as_ref(),
         Utc::now()
     );
-    transaction.execute(query).await.map_err(|e| {
-        tracing::error!("Failed to execute query: {:?}", e);
+    let n_inserted = transaction
+        .execute(query)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
 
-        e
-    })?;
-    Ok(subscriber_id)
+            e
+        })?
+        .rows_affected();
+    Ok((n_inserted > 0).then_some(subscriber_id))
+}
+
+/// Generate a fresh token, valid for `ttl`, for `subscriber_id` to confirm
+/// their subscription to `list_id`, and store it.
+async fn issue_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+    ttl: chrono::Duration,
+) -> Result<String, StoreTokenError> {
+    let subscription_token = generate_subscription_token();
+    store_token(
+        transaction,
+        subscriber_id,
+        list_id,
+        &subscription_token,
+        Utc::now() + ttl,
+    )
+    .await?;
+    Ok(subscription_token)
 }
 
 #[tracing::instrument(
@@ -123,13 +223,17 @@ pub async fn insert_subscriber(
 pub async fn store_token(
     transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
+    list_id: Uuid,
     subscription_token: &str,
+    expires_at: DateTime<Utc>,
 ) -> Result<(), StoreTokenError> {
     let query = sqlx::query!(
-        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id)
-        VALUES ($1, $2)"#,
+        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id, list_id, expires_at)
+        VALUES ($1, $2, $3, $4)"#,
         subscription_token,
         subscriber_id,
+        list_id,
+        expires_at,
     );
     transaction.execute(query).await.map_err(|e| {
         tracing::error!("Failed to execute query: {:?}", e);
@@ -138,33 +242,75 @@ pub async fn store_token(
     Ok(())
 }
 
-#[tracing::instrument(name = "Get subscriber and his token by provided email.", skip(pool))]
-pub async fn get_subscriber_token_by_email(
-    pool: &PgPool,
-    subscriber_email: SubscriberEmail,
-) -> Result<(String, String), sqlx::Error> {
+#[tracing::instrument(
+    name = "Get subscriber by provided email.",
+    skip(transaction, subscriber_email)
+)]
+pub async fn get_subscriber_id_by_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_email: &SubscriberEmail,
+) -> Result<Option<Uuid>, sqlx::Error> {
     let saved = sqlx::query!(
-        r#"
-        SELECT
-                subscriptions.id,
-                subscriptions.email,
-                subscriptions.status AS status,
-                subscription_tokens.subscription_token AS token
-        FROM subscriptions
-        JOIN subscription_tokens ON
-            subscription_tokens.subscriber_id = subscriptions.id
-        WHERE email = $1
-        "#,
+        r#"SELECT id FROM subscriptions WHERE email = $1 FOR UPDATE"#,
         subscriber_email.as_ref()
     )
-    .fetch_optional(pool)
+    .fetch_optional(&mut **transaction)
     .await
     .map_err(|e| {
         tracing::error!("Failed to execute query: {:?}", e);
         e
     })?;
-    let subscriber = saved.map(|s| (s.status, s.token)).unwrap();
-    Ok(subscriber)
+    Ok(saved.map(|s| s.id))
+}
+
+/// Make `subscriber_id` a member of `list_id`, pending confirmation unless
+/// they are a confirmed member already, and return the membership status.
+#[tracing::instrument(name = "Add subscriber to a mailing list", skip(transaction))]
+async fn join_list(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+) -> Result<String, sqlx::Error> {
+    let membership = sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (subscriber_id, list_id, status)
+        VALUES ($1, $2, 'pending_confirmation')
+        ON CONFLICT (subscriber_id, list_id) DO UPDATE
+        SET status = CASE
+            WHEN list_memberships.status = 'confirmed' THEN 'confirmed'
+            ELSE 'pending_confirmation'
+        END
+        RETURNING status
+        "#,
+        subscriber_id,
+        list_id
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(membership.status)
+}
+
+/// The most recent signup token for `list_id` that has not expired yet, if any.
+#[tracing::instrument(name = "Get a pending signup token", skip(transaction))]
+async fn get_signup_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+) -> Result<Option<String>, sqlx::Error> {
+    let token = sqlx::query!(
+        r#"
+        SELECT subscription_token
+        FROM subscription_tokens
+        WHERE subscriber_id = $1 AND list_id = $2 AND expires_at > now()
+        ORDER BY expires_at DESC
+        LIMIT 1
+        "#,
+        subscriber_id,
+        list_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    Ok(token.map(|t| t.subscription_token))
 }
 
 #[tracing::instrument(
@@ -173,7 +319,7 @@ pub async fn get_subscriber_token_by_email(
 )]
 pub async fn send_confirmation_email(
     email_client: &EmailClient,
-    new_subscriber: NewSubscriber,
+    new_subscriber: &NewSubscriber,
     base_url: &str,
     // CSPRNG
     subscription_token: &str,
@@ -197,7 +343,7 @@ pub async fn send_confirmation_email(
 }
 
 /// Generate a random 25-characters-long case-sensitive subscription token.
-fn generate_subscription_token() -> String {
+pub(super) fn generate_subscription_token() -> String {
     let mut rng = thread_rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
@@ -1,35 +1,132 @@
-use actix_web::{web, HttpResponse};
+use crate::personal_data::email_hash;
+use crate::routes::error_chain_fmt;
+use actix_web::http::header::ContentType;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
 use sqlx::PgPool;
 use uuid::Uuid;
 
 #[derive(serde::Deserialize)]
 pub struct Parameters {
-    #[allow(dead_code)]
     subscription_token: String,
 }
 
 #[tracing::instrument(name = "Confirm a pending subscriber", skip(parameters, pool))]
-pub async fn confirm(parameters: web::Query<Parameters>, pool: web::Data<PgPool>) -> HttpResponse {
-    let id = match get_subscriber_id_from_token(&pool, &parameters.subscription_token).await {
-        Ok(id) => id,
-        Err(_) => return HttpResponse::InternalServerError().finish(),
+pub async fn confirm(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ConfirmError> {
+    let token = get_token(&pool, &parameters.subscription_token)
+        .await
+        .context("Failed to retrieve the subscription token.")?
+        .ok_or(ConfirmError::InvalidToken)?;
+    if let Some(new_email) = token.new_email {
+        return confirm_email_change(
+            &pool,
+            token.subscriber_id,
+            &token.email,
+            &new_email,
+            token.expires_at,
+        )
+        .await;
+    }
+    let (Some(list_id), Some(status)) = (token.list_id, token.status) else {
+        return Err(ConfirmError::InvalidToken);
     };
-    match id {
-        None => HttpResponse::Unauthorized().finish(),
-        Some(subscriber_id) => {
-            if confirm_subscriber(&pool, subscriber_id).await.is_err() {
-                return HttpResponse::InternalServerError().finish();
+    match status.as_str() {
+        // Clicking the link again is harmless, whether or not it has expired since.
+        "confirmed" => Ok(confirmation_page(
+            "Already confirmed",
+            "Your subscription was already confirmed, there is nothing left to do.",
+        )),
+        "pending_confirmation" if token.expires_at > Utc::now() => {
+            confirm_subscriber(&pool, token.subscriber_id, list_id)
+                .await
+                .context("Failed to mark the subscriber as confirmed.")?;
+            Ok(confirmation_page(
+                "Subscription confirmed",
+                "Thanks for confirming your subscription, you will receive our next issue.",
+            ))
+        }
+        // Expired, or left since: a new opt-in is needed either way.
+        _ => Err(ConfirmError::ExpiredToken),
+    }
+}
+
+/// Switch the subscriber to the address the token was sent to.
+async fn confirm_email_change(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    current_email: &str,
+    new_email: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<HttpResponse, ConfirmError> {
+    // Clicking the link again is harmless here too.
+    if current_email != new_email {
+        if expires_at <= Utc::now() {
+            return Err(ConfirmError::ExpiredToken);
+        }
+        match change_subscriber_email(pool, subscriber_id, new_email).await {
+            Ok(()) => {}
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                return Err(ConfirmError::EmailAlreadySubscribed)
+            }
+            Err(e) => {
+                return Err(anyhow::Error::new(e)
+                    .context("Failed to change the subscriber's email address.")
+                    .into())
             }
-            HttpResponse::Ok().finish()
         }
     }
+    Ok(confirmation_page(
+        "Email address updated",
+        "Thanks for confirming your new email address, our next issues will be sent there.",
+    ))
 }
 
+fn confirmation_page(title: &str, message: &str) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(page(title, message))
+}
+
+fn page(title: &str, message: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                <title>{title}</title>
+            </head>
+            <body>
+                <h1>{title}</h1>
+                <p>{message}</p>
+                <p><a href="/">Home</a></p>
+            </body>
+            </html>"#
+    )
+}
+
+/// Confirm the subscriber's membership of `list_id`, which also proves
+/// they own their email address.
 #[tracing::instrument(name = "Mark subscriber as confirmed", skip(subscriber_id, pool))]
-pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
+pub async fn confirm_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+) -> Result<(), sqlx::Error> {
     sqlx::query!(
-        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
-        subscriber_id
+        r#"
+        WITH membership AS (
+            UPDATE list_memberships SET status = 'confirmed'
+            WHERE subscriber_id = $1 AND list_id = $2
+        )
+        UPDATE subscriptions SET status = 'confirmed' WHERE id = $1
+        "#,
+        subscriber_id,
+        list_id
     )
     .execute(pool)
     .await
@@ -40,14 +137,54 @@ pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<()
     Ok(())
 }
 
+#[tracing::instrument(name = "Change subscriber email", skip(pool))]
+async fn change_subscriber_email(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    new_email: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriptions SET email = $2, email_hash = $3 WHERE id = $1"#,
+        subscriber_id,
+        new_email,
+        email_hash(new_email)
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+struct SubscriptionToken {
+    subscriber_id: Uuid,
+    email: String,
+    list_id: Option<Uuid>,
+    /// The status of the membership the token confirms.
+    status: Option<String>,
+    expires_at: DateTime<Utc>,
+    new_email: Option<String>,
+}
+
 #[tracing::instrument(name = "Get subscriber_id from token", skip(subscription_token, pool))]
-async fn get_subscriber_id_from_token(
+async fn get_token(
     pool: &PgPool,
     subscription_token: &str,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let result = sqlx::query!(
-        "SELECT subscriber_id FROM subscription_tokens \
-        WHERE subscription_token = $1",
+) -> Result<Option<SubscriptionToken>, sqlx::Error> {
+    let result = sqlx::query_as!(
+        SubscriptionToken,
+        r#"
+        SELECT
+            t.subscriber_id,
+            s.email,
+            t.list_id,
+            m.status AS "status?",
+            t.expires_at,
+            t.new_email
+        FROM subscription_tokens t
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        LEFT JOIN list_memberships m ON
+            m.subscriber_id = t.subscriber_id AND m.list_id = t.list_id
+        WHERE t.subscription_token = $1
+        "#,
         subscription_token,
     )
     .fetch_optional(pool)
@@ -56,5 +193,61 @@ async fn get_subscriber_id_from_token(
         tracing::error!("Failed to execute query: {:?}", e);
         e
     })?;
-    Ok(result.map(|r| r.subscriber_id))
+    Ok(result)
+}
+
+#[derive(thiserror::Error)]
+pub enum ConfirmError {
+    #[error("The confirmation link is invalid.")]
+    InvalidToken,
+    #[error("The confirmation link has expired.")]
+    ExpiredToken,
+    #[error("The new email address is already subscribed.")]
+    EmailAlreadySubscribed,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ConfirmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for ConfirmError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ConfirmError::InvalidToken => StatusCode::UNAUTHORIZED,
+            ConfirmError::ExpiredToken => StatusCode::GONE,
+            ConfirmError::EmailAlreadySubscribed => StatusCode::CONFLICT,
+            ConfirmError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let (title, message) = match self {
+            ConfirmError::InvalidToken => (
+                "Invalid link",
+                "This confirmation link is not valid. \
+                Please check that you copied it in full from the email we sent you.",
+            ),
+            ConfirmError::ExpiredToken => (
+                "Link expired",
+                "This confirmation link has expired. \
+                Please subscribe again to receive a new one.",
+            ),
+            ConfirmError::EmailAlreadySubscribed => (
+                "Address already subscribed",
+                "This email address is already subscribed to our newsletter, \
+                so the subscription you were changing keeps its current address.",
+            ),
+            ConfirmError::UnexpectedError(_) => (
+                "Something went wrong",
+                "We could not confirm your subscription, please try again later.",
+            ),
+        };
+        HttpResponse::build(self.status_code())
+            .content_type(ContentType::html())
+            .body(page(title, message))
+    }
 }
@@ -0,0 +1,262 @@
+use crate::configuration::SubscriptionSettings;
+use crate::domain::{SubscriberEmail, SubscriberToken, TokenPurpose};
+use crate::email_client::EmailClient;
+use crate::personal_data::{export_personal_data, Requester};
+use crate::routes::{error_chain_fmt, personal_data_download};
+use crate::startup::{ApplicationBaseUrl, HmacSecret};
+use crate::utils::see_other;
+use actix_web::http::header::ContentType;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::Utc;
+use htmlescape::encode_minimal;
+use secrecy::Secret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct PersonalDataParameters {
+    subscriber_id: Uuid,
+    token: String,
+}
+
+impl PersonalDataParameters {
+    fn verify(
+        &self,
+        purpose: TokenPurpose,
+        hmac_secret: &HmacSecret,
+    ) -> Result<(), PersonalDataError> {
+        SubscriberToken::verify(self.subscriber_id, purpose, &self.token, &hmac_secret.0)
+            .map_err(PersonalDataError::InvalidToken)
+    }
+
+    fn query(&self) -> String {
+        format!("subscriber_id={}&token={}", self.subscriber_id, self.token)
+    }
+}
+
+/// Build the link to the page where a subscriber can download or erase
+/// their data.
+///
+/// It is only ever sent to the address on file and expires after `ttl`:
+/// unlike the links in newsletters, it must not work for whoever an email
+/// gets forwarded to.
+pub fn personal_data_link(
+    base_url: &str,
+    subscriber_id: Uuid,
+    ttl: chrono::Duration,
+    secret: &Secret<String>,
+) -> String {
+    let token = SubscriberToken::generate(
+        subscriber_id,
+        TokenPurpose::PersonalData,
+        Some(Utc::now() + ttl),
+        secret,
+    );
+    format!(
+        "{}/subscriptions/personal_data?subscriber_id={}&token={}",
+        base_url,
+        subscriber_id,
+        token.as_ref()
+    )
+}
+
+/// Email a link to download or erase their data to the subscriber, from the
+/// preference center.
+#[tracing::instrument(
+    name = "Send a personal data link",
+    skip(parameters, pool, email_client, base_url, hmac_secret, settings)
+)]
+pub async fn request_subscriber_personal_data_link(
+    parameters: web::Query<PersonalDataParameters>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, PersonalDataError> {
+    parameters.verify(TokenPurpose::ManagePreferences, &hmac_secret)?;
+    let Some(email) = get_subscriber_email(&pool, parameters.subscriber_id).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let email = SubscriberEmail::parse(email)
+        .map_err(|e| anyhow::anyhow!(e))
+        .context("The subscriber's email address is invalid.")?;
+    let ttl = settings.personal_data_link_ttl();
+    let link = personal_data_link(&base_url.0, parameters.subscriber_id, ttl, &hmac_secret.0);
+    let plain_body = format!(
+        "Visit {} to download or delete the data we hold on you.\n\
+        The link expires in {} minutes. If you did not ask for it, you can ignore this email.",
+        link,
+        ttl.num_minutes()
+    );
+    let html_body = format!(
+        "Click <a href=\"{}\">here</a> to download or delete the data we hold on you.<br />\
+        The link expires in {} minutes. If you did not ask for it, you can ignore this email.",
+        encode_minimal(&link),
+        ttl.num_minutes()
+    );
+    email_client
+        .send_email(&email, "Your personal data", &html_body, &plain_body)
+        .await
+        .context("Failed to send a personal data link.")?;
+    FlashMessage::info("We have sent you an email with a link to download or delete your data.")
+        .send();
+    Ok(see_other(&format!(
+        "/subscriptions/preferences?{}",
+        parameters.query()
+    )))
+}
+
+/// The page a personal data link leads to. Nothing happens until the
+/// subscriber asks for it, as mail scanners follow links on their own.
+#[tracing::instrument(name = "Show the personal data page", skip(parameters, hmac_secret))]
+pub async fn subscriber_personal_data(
+    parameters: web::Query<PersonalDataParameters>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, PersonalDataError> {
+    parameters.verify(TokenPurpose::PersonalData, &hmac_secret)?;
+    let query = encode_minimal(&parameters.query());
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                <title>Your data</title>
+            </head>
+            <body>
+                <h1>Your data</h1>
+                <form action="/subscriptions/personal_data/export?{query}" method="post">
+                    <button type="submit">Download your data</button>
+                </form>
+                <form action="/subscriptions/personal_data/erase?{query}" method="post">
+                    <button type="submit">Delete all my data</button>
+                </form>
+            </body>
+            </html>"#
+        )))
+}
+
+/// Ask before exporting: the export is audited, and mail scanners or link
+/// previews must not trigger it by following a link.
+#[tracing::instrument(
+    name = "Show the personal data export form",
+    skip(parameters, hmac_secret)
+)]
+pub async fn download_subscriber_personal_data_form(
+    parameters: web::Query<PersonalDataParameters>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, PersonalDataError> {
+    parameters.verify(TokenPurpose::PersonalData, &hmac_secret)?;
+    let query = encode_minimal(&parameters.query());
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                <title>Download your data</title>
+            </head>
+            <body>
+                <p>Do you want to download the data we hold on you?</p>
+                <form action="/subscriptions/personal_data/export?{query}" method="post">
+                    <button type="submit">Download your data</button>
+                </form>
+            </body>
+            </html>"#
+        )))
+}
+
+/// Let a subscriber download everything we hold on them.
+#[tracing::instrument(
+    name = "Export a subscriber's personal data",
+    skip(parameters, pool, hmac_secret)
+)]
+pub async fn download_subscriber_personal_data(
+    parameters: web::Query<PersonalDataParameters>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, PersonalDataError> {
+    parameters.verify(TokenPurpose::PersonalData, &hmac_secret)?;
+    let Some(email) = get_subscriber_email(&pool, parameters.subscriber_id).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let Some(document) = export_personal_data(&pool, &email, Requester::Subscriber).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    Ok(personal_data_download(&document)?)
+}
+
+/// Let a subscriber erase everything we hold on them.
+#[tracing::instrument(
+    name = "Erase a subscriber's personal data",
+    skip(parameters, pool, hmac_secret)
+)]
+pub async fn erase_subscriber_personal_data(
+    parameters: web::Query<PersonalDataParameters>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, PersonalDataError> {
+    parameters.verify(TokenPurpose::PersonalData, &hmac_secret)?;
+    let Some(email) = get_subscriber_email(&pool, parameters.subscriber_id).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    crate::personal_data::erase_personal_data(&pool, &email, Requester::Subscriber).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                <title>Data erased</title>
+            </head>
+            <body>
+                <p>All the data we held on you has been erased. You will not receive any more emails from us.</p>
+            </body>
+            </html>"#,
+        ))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_subscriber_email(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        "SELECT email FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the subscriber's email address.")?;
+    Ok(row.map(|r| r.email))
+}
+
+#[derive(thiserror::Error)]
+pub enum PersonalDataError {
+    #[error("The link is invalid.")]
+    InvalidToken(#[source] anyhow::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for PersonalDataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for PersonalDataError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            PersonalDataError::InvalidToken(_) => StatusCode::UNAUTHORIZED,
+            PersonalDataError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
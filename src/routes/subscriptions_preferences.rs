@@ -0,0 +1,472 @@
+use super::subscriptions::{
+    generate_subscription_token, request_membership, send_confirmation_email,
+};
+use crate::configuration::SubscriptionSettings;
+use crate::domain::{
+    NewSubscriber, SubscriberEmail, SubscriberName, SubscriberToken, TokenPurpose,
+};
+use crate::email_client::EmailClient;
+use crate::routes::{error_chain_fmt, unsubscribe_link};
+use crate::startup::{ApplicationBaseUrl, HmacSecret};
+use crate::suppression::is_suppressed;
+use crate::utils::see_other;
+use actix_web::http::header::ContentType;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use chrono::Utc;
+use htmlescape::encode_minimal;
+use secrecy::Secret;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::fmt::Write;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct PreferencesParameters {
+    subscriber_id: Uuid,
+    token: String,
+}
+
+impl PreferencesParameters {
+    fn verify(&self, secret: &Secret<String>) -> Result<(), PreferencesError> {
+        SubscriberToken::verify(
+            self.subscriber_id,
+            TokenPurpose::ManagePreferences,
+            &self.token,
+            secret,
+        )
+        .map_err(PreferencesError::InvalidToken)
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "/subscriptions/preferences?subscriber_id={}&token={}",
+            self.subscriber_id, self.token
+        )
+    }
+}
+
+/// Build the signed link a subscriber can follow to manage their subscription.
+///
+/// Whoever holds the link can change the subscriber's address, so it expires
+/// after `ttl` rather than living on in forwarded emails.
+pub fn preferences_link(
+    base_url: &str,
+    subscriber_id: Uuid,
+    ttl: chrono::Duration,
+    secret: &Secret<String>,
+) -> String {
+    let token = SubscriberToken::generate(
+        subscriber_id,
+        TokenPurpose::ManagePreferences,
+        Some(Utc::now() + ttl),
+        secret,
+    );
+    format!(
+        "{}/subscriptions/preferences?subscriber_id={}&token={}",
+        base_url,
+        subscriber_id,
+        token.as_ref()
+    )
+}
+
+struct Preferences {
+    email: String,
+    name: String,
+}
+
+struct ListChoice {
+    list_id: Uuid,
+    name: String,
+    chosen: bool,
+}
+
+#[tracing::instrument(
+    name = "Show the preference center",
+    skip(parameters, pool, hmac_secret, flash_messages)
+)]
+pub async fn preferences_form(
+    parameters: web::Query<PreferencesParameters>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, PreferencesError> {
+    parameters.verify(&hmac_secret.0)?;
+    let Some(preferences) = get_preferences(&pool, parameters.subscriber_id)
+        .await
+        .context("Failed to retrieve the subscriber's preferences.")?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let lists = get_list_choices(&pool, parameters.subscriber_id)
+        .await
+        .context("Failed to retrieve the subscriber's lists.")?;
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut lists_html = String::new();
+    for l in &lists {
+        writeln!(
+            lists_html,
+            r#"<label><input type="checkbox" name="list" value="{}"{}> {}</label><br>"#,
+            l.list_id,
+            if l.chosen { " checked" } else { "" },
+            encode_minimal(&l.name),
+        )
+        .unwrap();
+    }
+    let action = encode_minimal(&parameters.path());
+    let unsubscribe = encode_minimal(&unsubscribe_link(
+        "",
+        parameters.subscriber_id,
+        None,
+        &hmac_secret.0,
+    ));
+    let personal_data = encode_minimal(&format!(
+        "/subscriptions/personal_data/request?subscriber_id={}&token={}",
+        parameters.subscriber_id, parameters.token
+    ));
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                <title>Your preferences</title>
+            </head>
+            <body>
+                {msg_html}
+                <h1>Your preferences</h1>
+                <form action="{action}" method="post">
+                    <label>Name
+                        <input type="text" name="name" value="{name}">
+                    </label>
+                    <br>
+                    <label>Email address
+                        <input type="email" name="email" value="{email}">
+                    </label>
+                    <br>
+                    <fieldset>
+                        <legend>Mailing lists</legend>
+                        {lists_html}
+                    </fieldset>
+                    <button type="submit">Save</button>
+                </form>
+                <p><a href="{unsubscribe}">Unsubscribe</a></p>
+                <h2>Your data</h2>
+                <form action="{personal_data}" method="post">
+                    <button type="submit">Email me a link to download or delete my data</button>
+                </form>
+            </body>
+            </html>"#,
+            name = encode_minimal(&preferences.name),
+            email = encode_minimal(&preferences.email),
+        )))
+}
+
+/// The form holds one `list` field per checked box, which is why it is
+/// deserialized as a list of pairs rather than as a struct.
+#[tracing::instrument(
+    name = "Update a subscriber's preferences",
+    skip(parameters, form, pool, email_client, base_url, hmac_secret, settings)
+)]
+pub async fn update_preferences(
+    parameters: web::Query<PreferencesParameters>,
+    form: web::Form<Vec<(String, String)>>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, PreferencesError> {
+    parameters.verify(&hmac_secret.0)?;
+    let subscriber_id = parameters.subscriber_id;
+    let mut name = String::new();
+    let mut email = String::new();
+    let mut list_ids = Vec::new();
+    for (key, value) in form.0 {
+        match key.as_str() {
+            "name" => name = value,
+            "email" => email = value,
+            "list" => list_ids.extend(value.parse::<Uuid>()),
+            _ => {}
+        }
+    }
+    let (Ok(name), Ok(email)) = (SubscriberName::parse(name), SubscriberEmail::parse(email)) else {
+        FlashMessage::error("Please enter a valid name and email address.").send();
+        return Ok(see_other(&parameters.path()));
+    };
+    let Some(current) = get_preferences(&pool, subscriber_id)
+        .await
+        .context("Failed to retrieve the subscriber's preferences.")?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let email_changed = email.as_ref() != current.email;
+    // As when signing up, the answer is the same whether or not either
+    // address is suppressed, so that it can't be used to find out.
+    let current_email_suppressed = is_suppressed(pool.get_ref(), &current.email)
+        .await
+        .context("Failed to check the suppression list.")?;
+    let new_email_suppressed = email_changed
+        && is_suppressed(pool.get_ref(), email.as_ref())
+            .await
+            .context("Failed to check the suppression list.")?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let lists_to_join = update_name_and_lists(&mut transaction, subscriber_id, &name, &list_ids)
+        .await
+        .context("Failed to update the subscriber's preferences.")?;
+    let mut list_tokens = Vec::new();
+    if !current_email_suppressed {
+        for &list_id in &lists_to_join {
+            list_tokens.extend(
+                request_membership(
+                    &mut transaction,
+                    subscriber_id,
+                    list_id,
+                    settings.confirmation_token_ttl(),
+                )
+                .await?,
+            );
+        }
+    }
+    let email_change_token = if email_changed && !new_email_suppressed {
+        let subscription_token = generate_subscription_token();
+        store_email_change_token(
+            &mut transaction,
+            subscriber_id,
+            &subscription_token,
+            &email,
+            settings.confirmation_token_ttl(),
+        )
+        .await
+        .context("Failed to store the token for an email change.")?;
+        Some(subscription_token)
+    } else {
+        None
+    };
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to update a subscriber's preferences.")?;
+
+    // New lists are joined once confirmed from the current address.
+    let mut message = String::from("Your preferences have been saved.");
+    if !list_tokens.is_empty() {
+        let current_subscriber = NewSubscriber {
+            email: SubscriberEmail::parse(current.email)
+                .map_err(anyhow::Error::msg)
+                .context("The stored email address is invalid.")?,
+            name,
+        };
+        for subscription_token in &list_tokens {
+            send_confirmation_email(
+                &email_client,
+                &current_subscriber,
+                &base_url.0,
+                subscription_token,
+            )
+            .await
+            .context("Failed to send a confirmation email for a new list.")?;
+        }
+    }
+    if !lists_to_join.is_empty() {
+        message.push_str(" Please follow the links we sent you to confirm the lists you joined.");
+    }
+    // The address only changes once its owner has confirmed it.
+    if let Some(subscription_token) = email_change_token {
+        send_email_change_confirmation(&email_client, &email, &base_url.0, &subscription_token)
+            .await
+            .context("Failed to send a confirmation email for an email change.")?;
+    }
+    if email_changed {
+        message.push_str(
+            " Please follow the link we sent to your new email address to start using it.",
+        );
+    }
+    FlashMessage::info(message).send();
+    Ok(see_other(&parameters.path()))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_preferences(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<Preferences>, sqlx::Error> {
+    sqlx::query_as!(
+        Preferences,
+        r#"SELECT email, name FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_list_choices(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<ListChoice>, sqlx::Error> {
+    sqlx::query_as!(
+        ListChoice,
+        r#"
+        SELECT
+            l.list_id,
+            l.name,
+            EXISTS (
+                SELECT 1 FROM list_memberships m
+                WHERE
+                    m.list_id = l.list_id AND
+                    m.subscriber_id = $1 AND
+                    m.status = 'confirmed'
+            ) AS "chosen!"
+        FROM lists l
+        ORDER BY l.name
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Leave the lists that are no longer ticked.
+/// Returns the ticked lists the subscriber is not a confirmed member of yet:
+/// they are only joined once confirmed, as when signing up, because the
+/// preferences link may have been forwarded.
+#[tracing::instrument(skip(transaction, name))]
+async fn update_name_and_lists(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    name: &SubscriberName,
+    list_ids: &[Uuid],
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    transaction
+        .execute(sqlx::query!(
+            r#"UPDATE subscriptions SET name = $2 WHERE id = $1"#,
+            subscriber_id,
+            name.as_ref()
+        ))
+        .await?;
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            UPDATE list_memberships SET status = 'unsubscribed'
+            WHERE subscriber_id = $1 AND NOT (list_id = ANY($2))
+            "#,
+            subscriber_id,
+            list_ids
+        ))
+        .await?;
+    // Unknown list ids are ignored rather than rejected.
+    let lists_to_join = sqlx::query_scalar!(
+        r#"
+        SELECT l.list_id
+        FROM lists l
+        WHERE
+            l.list_id = ANY($2) AND
+            NOT EXISTS (
+                SELECT 1 FROM list_memberships m
+                WHERE
+                    m.list_id = l.list_id AND
+                    m.subscriber_id = $1 AND
+                    m.status = 'confirmed'
+            )
+        ORDER BY l.name
+        "#,
+        subscriber_id,
+        list_ids
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+    Ok(lists_to_join)
+}
+
+#[tracing::instrument(skip(transaction, subscription_token, new_email))]
+async fn store_email_change_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    subscription_token: &str,
+    new_email: &SubscriberEmail,
+    ttl: chrono::Duration,
+) -> Result<(), sqlx::Error> {
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            INSERT INTO subscription_tokens (subscription_token, subscriber_id, expires_at, new_email)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            subscription_token,
+            subscriber_id,
+            Utc::now() + ttl,
+            new_email.as_ref()
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Unlike a signup confirmation, this is sent to someone who already reads
+/// the newsletter and only asked to receive it elsewhere.
+async fn send_email_change_confirmation(
+    email_client: &EmailClient,
+    new_email: &SubscriberEmail,
+    base_url: &str,
+    subscription_token: &str,
+) -> Result<(), anyhow::Error> {
+    let confirmation_link = format!(
+        "{}/subscriptions/confirm?subscription_token={}",
+        base_url, subscription_token,
+    );
+    let plain_body = format!(
+        "You asked to receive our newsletter at this address.\n\
+        Visit {} to confirm the change. \
+        If you did not ask for it, you can ignore this email.",
+        confirmation_link
+    );
+    let html_body = format!(
+        "You asked to receive our newsletter at this address.<br />\
+        Click <a href=\"{}\">here</a> to confirm the change. \
+        If you did not ask for it, you can ignore this email.",
+        confirmation_link
+    );
+    email_client
+        .send_email(
+            new_email,
+            "Confirm your new email address",
+            &html_body,
+            &plain_body,
+        )
+        .await
+}
+
+#[derive(thiserror::Error)]
+pub enum PreferencesError {
+    #[error("The preferences link is invalid.")]
+    InvalidToken(#[source] anyhow::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for PreferencesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for PreferencesError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            PreferencesError::InvalidToken(_) => StatusCode::UNAUTHORIZED,
+            PreferencesError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use crate::domain::{SubscriberToken, TokenPurpose};
+use crate::routes::error_chain_fmt;
+use crate::startup::HmacSecret;
+use actix_web::http::header::ContentType;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use secrecy::Secret;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct UnsubscribeParameters {
+    subscriber_id: Uuid,
+    token: String,
+    /// The list to leave, all of them if not set.
+    list_id: Option<Uuid>,
+}
+
+/// Build the signed link a subscriber can follow to leave `list_id`, or every list.
+///
+/// The link never expires, as mailbox providers may follow the `List-Unsubscribe`
+/// header of an old email, but it is good for nothing but unsubscribing, and
+/// only from the list it was signed for.
+pub fn unsubscribe_link(
+    base_url: &str,
+    subscriber_id: Uuid,
+    list_id: Option<Uuid>,
+    secret: &Secret<String>,
+) -> String {
+    let purpose = TokenPurpose::unsubscribe(list_id);
+    let token = SubscriberToken::generate(subscriber_id, purpose, None, secret);
+    let mut link = format!(
+        "{}/subscriptions/unsubscribe?subscriber_id={}&token={}",
+        base_url,
+        subscriber_id,
+        token.as_ref()
+    );
+    if let Some(list_id) = list_id {
+        write!(link, "&list_id={}", list_id).unwrap();
+    }
+    link
+}
+
+#[tracing::instrument(name = "Show the unsubscribe form", skip(parameters, hmac_secret))]
+pub async fn unsubscribe_form(
+    parameters: web::Query<UnsubscribeParameters>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, UnsubscribeError> {
+    SubscriberToken::verify(
+        parameters.subscriber_id,
+        TokenPurpose::unsubscribe(parameters.list_id),
+        &parameters.token,
+        &hmac_secret.0,
+    )
+    .map_err(UnsubscribeError::InvalidToken)?;
+    let UnsubscribeParameters {
+        subscriber_id,
+        token,
+        list_id,
+    } = parameters.0;
+    let list_param = list_id
+        .map(|list_id| format!("&amp;list_id={list_id}"))
+        .unwrap_or_default();
+    // Mail scanners follow GET links on their own, so leaving requires an explicit POST.
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                <title>Unsubscribe</title>
+            </head>
+            <body>
+                <p>Do you want to stop receiving our newsletter?</p>
+                <form action="/subscriptions/unsubscribe?subscriber_id={subscriber_id}&amp;token={token}{list_param}" method="post">
+                    <button type="submit">Unsubscribe</button>
+                </form>
+            </body>
+            </html>"#
+        )))
+}
+
+#[tracing::instrument(name = "Unsubscribe a subscriber", skip(parameters, pool, hmac_secret))]
+pub async fn unsubscribe(
+    parameters: web::Query<UnsubscribeParameters>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, UnsubscribeError> {
+    SubscriberToken::verify(
+        parameters.subscriber_id,
+        TokenPurpose::unsubscribe(parameters.list_id),
+        &parameters.token,
+        &hmac_secret.0,
+    )
+    .map_err(UnsubscribeError::InvalidToken)?;
+    mark_subscriber_as_unsubscribed(&pool, parameters.subscriber_id, parameters.list_id)
+        .await
+        .context("Failed to mark the subscriber as unsubscribed.")?;
+    let message = match parameters.list_id {
+        Some(_) => {
+            "You have been unsubscribed. You will not receive any more emails from this list."
+        }
+        None => "You have been unsubscribed. You will not receive any more emails from us.",
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+                <title>Unsubscribed</title>
+            </head>
+            <body>
+                <p>{message}</p>
+            </body>
+            </html>"#
+        )))
+}
+
+/// Leave `list_id`, or every list along with the newsletter as a whole.
+#[tracing::instrument(name = "Mark subscriber as unsubscribed", skip(pool))]
+pub async fn mark_subscriber_as_unsubscribed(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    list_id: Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE list_memberships SET status = 'unsubscribed'
+        WHERE subscriber_id = $1 AND ($2::uuid IS NULL OR list_id = $2)
+        "#,
+        subscriber_id,
+        list_id
+    )
+    .execute(pool)
+    .await?;
+    if list_id.is_none() {
+        sqlx::query!(
+            r#"UPDATE subscriptions SET status = 'unsubscribed' WHERE id = $1"#,
+            subscriber_id
+        )
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+#[derive(thiserror::Error)]
+pub enum UnsubscribeError {
+    #[error("The unsubscribe link is invalid.")]
+    InvalidToken(#[source] anyhow::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for UnsubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for UnsubscribeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            UnsubscribeError::InvalidToken(_) => StatusCode::UNAUTHORIZED,
+            UnsubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
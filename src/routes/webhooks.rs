@@ -0,0 +1,271 @@
+use crate::configuration::WebhookSettings;
+use crate::routes::error_chain_fmt;
+use crate::suppression::{suppress, SuppressionReason};
+use actix_web::http::header::{HeaderMap, HeaderValue};
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use secrecy::ExposeSecret;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+
+/// A Postmark webhook payload. Record types we do not track are acknowledged
+/// and dropped, so that Postmark does not retry them.
+#[derive(serde::Deserialize)]
+#[serde(tag = "RecordType")]
+enum PostmarkEvent {
+    Bounce(PostmarkBounce),
+    SpamComplaint(PostmarkBounce),
+    Delivery(PostmarkDelivery),
+    #[serde(other)]
+    Other,
+}
+
+/// Bounces and spam complaints share their shape.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PostmarkBounce {
+    #[serde(rename = "Type")]
+    bounce_type: String,
+    email: String,
+    #[serde(rename = "MessageID")]
+    message_id: Option<String>,
+    bounced_at: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PostmarkDelivery {
+    recipient: String,
+    #[serde(rename = "MessageID")]
+    message_id: Option<String>,
+    delivered_at: Option<String>,
+}
+
+struct EmailEvent {
+    kind: &'static str,
+    provider_type: Option<String>,
+    email: String,
+    provider_message_id: Option<String>,
+    occurred_at: Option<DateTime<Utc>>,
+}
+
+impl EmailEvent {
+    fn from_bounce(kind: &'static str, bounce: PostmarkBounce) -> Self {
+        Self {
+            kind,
+            provider_type: Some(bounce.bounce_type),
+            email: bounce.email,
+            provider_message_id: bounce.message_id,
+            occurred_at: parse_timestamp(bounce.bounced_at.as_deref()),
+        }
+    }
+
+    fn from_delivery(delivery: PostmarkDelivery) -> Self {
+        Self {
+            kind: "delivery",
+            provider_type: None,
+            email: delivery.recipient,
+            provider_message_id: delivery.message_id,
+            occurred_at: parse_timestamp(delivery.delivered_at.as_deref()),
+        }
+    }
+
+    /// What the event means for the address, if anything.
+    fn suppression_reason(&self) -> Option<SuppressionReason> {
+        match (self.kind, self.provider_type.as_deref()) {
+            // Soft bounces and the like may go away on their own.
+            ("bounce", Some("HardBounce" | "BadEmailAddress")) => {
+                Some(SuppressionReason::HardBounce)
+            }
+            ("spam_complaint", _) => Some(SuppressionReason::SpamComplaint),
+            _ => None,
+        }
+    }
+}
+
+fn parse_timestamp(timestamp: Option<&str>) -> Option<DateTime<Utc>> {
+    timestamp
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Receive the bounce, spam complaint and delivery events Postmark sends us.
+///
+/// Hard bounces and complaints unsubscribe the address and suppress it, so
+/// that we never email it again.
+#[tracing::instrument(name = "Receive an email event", skip_all)]
+pub async fn receive_email_event(
+    request: HttpRequest,
+    body: web::Bytes,
+    pool: web::Data<PgPool>,
+    settings: web::Data<WebhookSettings>,
+) -> Result<HttpResponse, WebhookError> {
+    check_credentials(request.headers(), &settings).map_err(WebhookError::AuthError)?;
+    let payload = std::str::from_utf8(&body)
+        .map_err(|_| WebhookError::ValidationError("The payload is not UTF-8.".into()))?;
+    let event = match serde_json::from_str(payload)
+        .map_err(|e| WebhookError::ValidationError(format!("Invalid payload: {}", e)))?
+    {
+        PostmarkEvent::Bounce(bounce) => EmailEvent::from_bounce("bounce", bounce),
+        PostmarkEvent::SpamComplaint(complaint) => {
+            EmailEvent::from_bounce("spam_complaint", complaint)
+        }
+        PostmarkEvent::Delivery(delivery) => EmailEvent::from_delivery(delivery),
+        PostmarkEvent::Other => return Ok(HttpResponse::Ok().finish()),
+    };
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    store_event(&mut transaction, &event, payload)
+        .await
+        .context("Failed to store an email event.")?;
+    if let Some(reason) = event.suppression_reason() {
+        tracing::info!(
+            reason = reason.as_str(),
+            "Suppressing an undeliverable address."
+        );
+        suppress(&mut *transaction, &event.email, reason, "postmark")
+            .await
+            .context("Failed to suppress an address.")?;
+        mark_subscriber_as_undeliverable(&mut transaction, &event.email, reason)
+            .await
+            .context("Failed to update the status of a subscriber.")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store an email event.")?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+fn check_credentials(headers: &HeaderMap, settings: &WebhookSettings) -> Result<(), anyhow::Error> {
+    let header_value = headers
+        .get("Authorization")
+        .context("The 'Authorization' header was missing")?
+        .to_str()
+        .context("The 'Authorization' header was not a valid UTF8 string.")?;
+    let base64encoded_segment = header_value
+        .strip_prefix("Basic ")
+        .context("The authorization scheme was not 'Basic'.")?;
+    let decoded_bytes = base64::engine::general_purpose::STANDARD
+        .decode(base64encoded_segment)
+        .context("Failed to base64-decode 'Basic' credentials.")?;
+    let decoded_credentials = String::from_utf8(decoded_bytes)
+        .context("The decoded credential string is not valid UTF8.")?;
+    let (username, password) = decoded_credentials
+        .split_once(':')
+        .context("A password must be provided in 'Basic' auth.")?;
+    // Compared in constant time, and both of them, so that the response time
+    // does not tell how much of the credentials was right.
+    let username_matches = username.as_bytes().ct_eq(settings.username.as_bytes());
+    let password_matches = password
+        .as_bytes()
+        .ct_eq(settings.password.expose_secret().as_bytes());
+    if !bool::from(username_matches & password_matches) {
+        anyhow::bail!("Invalid webhook credentials.");
+    }
+    Ok(())
+}
+
+#[tracing::instrument(skip(transaction, event, payload))]
+async fn store_event(
+    transaction: &mut Transaction<'_, Postgres>,
+    event: &EmailEvent,
+    payload: &str,
+) -> Result<(), sqlx::Error> {
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            INSERT INTO email_events (
+                event_id, kind, provider_type, email, provider_message_id,
+                occurred_at, payload, received_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7::text::jsonb, now())
+            "#,
+            Uuid::new_v4(),
+            event.kind,
+            event.provider_type,
+            event.email,
+            event.provider_message_id,
+            event.occurred_at,
+            payload
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Take the address off every list, with a status saying why.
+#[tracing::instrument(skip(transaction, email))]
+async fn mark_subscriber_as_undeliverable(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+    reason: SuppressionReason,
+) -> Result<(), sqlx::Error> {
+    let status = match reason {
+        SuppressionReason::SpamComplaint => "complained",
+        _ => "bounced",
+    };
+    let subscriber = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = $2
+        WHERE lower(email) = lower($1)
+        RETURNING id
+        "#,
+        email,
+        status
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    if let Some(subscriber) = subscriber {
+        transaction
+            .execute(sqlx::query!(
+                "UPDATE list_memberships SET status = 'unsubscribed' WHERE subscriber_id = $1",
+                subscriber.id
+            ))
+            .await?;
+    }
+    Ok(())
+}
+
+#[derive(thiserror::Error)]
+pub enum WebhookError {
+    #[error("Authentication failed.")]
+    AuthError(#[source] anyhow::Error),
+    #[error("{0}")]
+    ValidationError(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for WebhookError {
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::new(self.status_code());
+        if let WebhookError::AuthError(_) = self {
+            let header_value = HeaderValue::from_str(r#"Basic realm="webhooks""#).unwrap();
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, header_value);
+        }
+        response
+    }
+
+    fn status_code(&self) -> StatusCode {
+        match self {
+            WebhookError::AuthError(_) => StatusCode::UNAUTHORIZED,
+            WebhookError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            WebhookError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
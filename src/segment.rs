@@ -0,0 +1,401 @@
+//! Segment filters pick the subscribers of a list an issue goes to, e.g.
+//! `tag:beta and (attr.plan = "pro" or attr.seats >= 10)`.
+//!
+//! - `tag:<tag>` matches subscribers carrying the tag, e.g. `tag:region:eu`;
+//! - `attr.<key> = <value>` and `!=` compare a custom attribute with a
+//!   double-quoted string, a number, `true` or `false`;
+//! - `<`, `<=`, `>` and `>=` compare a numeric attribute with a number;
+//! - conditions combine with `not`, `and`, `or` (by decreasing precedence)
+//!   and parentheses.
+use sqlx::{Postgres, QueryBuilder};
+
+/// A parsed segment filter.
+#[derive(Debug)]
+pub struct Segment(Expr);
+
+#[derive(Debug, PartialEq)]
+enum Expr {
+    Tag(String),
+    Attribute {
+        key: String,
+        comparison: Comparison,
+        value: serde_json::Value,
+    },
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn as_sql(self) -> &'static str {
+        match self {
+            Comparison::Eq => "=",
+            Comparison::Ne => "!=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Comparison(Comparison),
+    Str(String),
+    Word(String),
+}
+
+impl Segment {
+    pub fn parse(s: &str) -> Result<Segment, String> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+        };
+        let expr = parser.or()?;
+        match parser.next() {
+            None => Ok(Segment(expr)),
+            Some(token) => Err(format!("Unexpected {} in the segment.", describe(&token))),
+        }
+    }
+
+    /// Append the filter as a boolean SQL expression over the `subscriptions`
+    /// table aliased as `s`, with every value bound as a parameter.
+    pub fn push_sql(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        push_expr(&self.0, query);
+    }
+}
+
+fn push_expr(expr: &Expr, query: &mut QueryBuilder<'_, Postgres>) {
+    match expr {
+        Expr::Tag(tag) => {
+            query.push("s.tags @> ARRAY[");
+            query.push_bind(tag.clone());
+            query.push("]::text[]");
+        }
+        Expr::Attribute {
+            key,
+            comparison: comparison @ (Comparison::Eq | Comparison::Ne),
+            value,
+        } => {
+            // A missing attribute is different from any value. Comparing it
+            // with `=` would yield NULL, which `NOT` leaves as NULL, so that
+            // `not attr.x = v` would disagree with `attr.x != v`.
+            query.push("(s.attributes -> ");
+            query.push_bind(key.clone());
+            query.push(if *comparison == Comparison::Eq {
+                ") IS NOT DISTINCT FROM "
+            } else {
+                ") IS DISTINCT FROM "
+            });
+            query.push_bind(value.to_string());
+            query.push("::jsonb");
+        }
+        Expr::Attribute {
+            key,
+            comparison,
+            value,
+        } => {
+            // The cast is only attempted on numbers, others never match.
+            query.push("CASE WHEN jsonb_typeof(s.attributes -> ");
+            query.push_bind(key.clone());
+            query.push(") = 'number' THEN (s.attributes ->> ");
+            query.push_bind(key.clone());
+            query.push(")::float8 ");
+            query.push(comparison.as_sql());
+            query.push(" ");
+            query.push_bind(value.as_f64().unwrap_or_default());
+            query.push(" ELSE false END");
+        }
+        Expr::Not(expr) => {
+            query.push("NOT (");
+            push_expr(expr, query);
+            query.push(")");
+        }
+        Expr::And(left, right) | Expr::Or(left, right) => {
+            query.push("(");
+            push_expr(left, query);
+            query.push(if matches!(expr, Expr::And(..)) {
+                " AND "
+            } else {
+                " OR "
+            });
+            push_expr(right, query);
+            query.push(")");
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push(if c == '(' {
+                    Token::LParen
+                } else {
+                    Token::RParen
+                });
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let or_equal = chars.next_if_eq(&'=').is_some();
+                let comparison = match (c, or_equal) {
+                    ('=', _) => Comparison::Eq,
+                    ('!', true) => Comparison::Ne,
+                    ('<', false) => Comparison::Lt,
+                    ('<', true) => Comparison::Le,
+                    ('>', false) => Comparison::Gt,
+                    ('>', true) => Comparison::Ge,
+                    _ => return Err("`!` must be followed by `=`.".into()),
+                };
+                tokens.push(Token::Comparison(comparison));
+            }
+            '"' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => value.extend(chars.next()),
+                        Some(c) => value.push(c),
+                        None => return Err("A quoted string is not closed.".into()),
+                    }
+                }
+                tokens.push(Token::Str(value));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) =
+                    chars.next_if(|c| !c.is_whitespace() && !"()=!<>\"".contains(*c))
+                {
+                    word.push(c);
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::LParen => "`(`".into(),
+        Token::RParen => "`)`".into(),
+        Token::Comparison(c) => format!("`{}`", c.as_sql()),
+        Token::Str(s) => format!("\"{}\"", s),
+        Token::Word(w) => format!("`{}`", w),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn next_is_keyword(&mut self, keyword: &str) -> bool {
+        match self.tokens.get(self.position) {
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword) => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.next_is_keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.next_is_keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.next_is_keyword("not") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.condition()
+    }
+
+    fn condition(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::LParen) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => Err("A `(` is not closed.".into()),
+                }
+            }
+            Some(Token::Word(w)) => {
+                if let Some(tag) = w.strip_prefix("tag:").filter(|t| !t.is_empty()) {
+                    Ok(Expr::Tag(tag.to_owned()))
+                } else if let Some(key) = w.strip_prefix("attr.").filter(|k| !k.is_empty()) {
+                    self.attribute(key.to_owned())
+                } else {
+                    Err(format!(
+                        "Expected a `tag:<tag>` or `attr.<key>` condition, found `{}`.",
+                        w
+                    ))
+                }
+            }
+            Some(token) => Err(format!("Unexpected {} in the segment.", describe(&token))),
+            None => Err("The segment ends where a condition was expected.".into()),
+        }
+    }
+
+    fn attribute(&mut self, key: String) -> Result<Expr, String> {
+        let Some(Token::Comparison(comparison)) = self.next() else {
+            return Err(format!("`attr.{}` must be followed by a comparison.", key));
+        };
+        let value = match self.next() {
+            Some(Token::Str(s)) => serde_json::Value::String(s),
+            Some(Token::Word(w)) if w == "true" || w == "false" => {
+                serde_json::Value::Bool(w == "true")
+            }
+            Some(Token::Word(w)) => w
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(serde_json::Value::Number)
+                .ok_or_else(|| format!("`{}` is not a value, quote strings with `\"`.", w))?,
+            _ => return Err(format!("`attr.{}` is compared to nothing.", key)),
+        };
+        if !matches!(comparison, Comparison::Eq | Comparison::Ne) && !value.is_number() {
+            return Err(format!("`{}` only compares numbers.", comparison.as_sql()));
+        }
+        Ok(Expr::Attribute {
+            key,
+            comparison,
+            value,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Comparison, Expr, Segment};
+    use claims::assert_err;
+    use sqlx::{Postgres, QueryBuilder};
+
+    fn parse(s: &str) -> Expr {
+        Segment::parse(s).unwrap().0
+    }
+
+    fn tag(t: &str) -> Box<Expr> {
+        Box::new(Expr::Tag(t.into()))
+    }
+
+    #[test]
+    fn tags_may_contain_colons() {
+        assert_eq!(parse("tag:region:eu"), Expr::Tag("region:eu".into()));
+    }
+
+    #[test]
+    fn attributes_are_compared_to_json_values() {
+        assert_eq!(
+            parse(r#"attr.plan = "pro \"plus\"""#),
+            Expr::Attribute {
+                key: "plan".into(),
+                comparison: Comparison::Eq,
+                value: serde_json::json!("pro \"plus\""),
+            }
+        );
+        assert_eq!(
+            parse("attr.seats>=10"),
+            Expr::Attribute {
+                key: "seats".into(),
+                comparison: Comparison::Ge,
+                value: serde_json::json!(10.0),
+            }
+        );
+        assert_eq!(
+            parse("attr.vip != true"),
+            Expr::Attribute {
+                key: "vip".into(),
+                comparison: Comparison::Ne,
+                value: serde_json::json!(true),
+            }
+        );
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and_which_binds_tighter_than_or() {
+        assert_eq!(
+            parse("tag:a or not tag:b AND tag:c"),
+            Expr::Or(
+                tag("a"),
+                Box::new(Expr::And(Box::new(Expr::Not(tag("b"))), tag("c")))
+            )
+        );
+        assert_eq!(
+            parse("(tag:a or tag:b) and tag:c"),
+            Expr::And(Box::new(Expr::Or(tag("a"), tag("b"))), tag("c"))
+        );
+    }
+
+    #[test]
+    fn invalid_segments_are_rejected() {
+        for segment in [
+            "",
+            "beta",
+            "tag:",
+            "tag:a tag:b",
+            "tag:a and",
+            "(tag:a",
+            "tag:a)",
+            "attr.plan",
+            "attr.plan = pro",
+            "attr.plan > \"pro\"",
+            "attr.plan ! 1",
+            "attr.plan = \"pro",
+        ] {
+            assert_err!(Segment::parse(segment), "{segment} should be rejected");
+        }
+    }
+
+    #[test]
+    fn values_are_bound_rather_than_spliced_into_the_sql() {
+        let mut query = QueryBuilder::<Postgres>::new("");
+        Segment::parse(r#"tag:beta and not attr.plan = "'; DROP TABLE x; --""#)
+            .unwrap()
+            .push_sql(&mut query);
+        assert_eq!(
+            query.sql(),
+            "(s.tags @> ARRAY[$1]::text[] AND NOT ((s.attributes -> $2) IS NOT DISTINCT FROM $3::jsonb))"
+        );
+    }
+}
@@ -12,14 +12,28 @@ use std::net::TcpListener;
 use tracing_actix_web::TracingLogger;
 
 use crate::authentication::reject_anonymous_users;
-use crate::configuration::{DatabaseSettings, Settings};
+use crate::configuration::{DatabaseSettings, Settings, SubscriptionSettings, WebhookSettings};
 use crate::email_client::EmailClient;
 use crate::routes::{
-    admin_dashboard, change_password, change_password_form, log_out, publish_newsletter,
-    publish_newsletter_form,
+    add_suppression, admin_dashboard, cancel_newsletter_issue, change_email, change_email_form,
+    change_password, change_password_form, create_mailing_list, create_newsletter_draft,
+    edit_newsletter_draft, edit_newsletter_draft_form, erase_personal_data, export_personal_data,
+    export_subscribers, failed_deliveries, import_subscribers, import_subscribers_form, log_out,
+    mailing_lists, newsletter_issue, newsletter_issues, personal_data, preview_newsletter_issue,
+    publish_newsletter, remove_suppression, reschedule_newsletter_issue,
+    retry_all_failed_deliveries, retry_failed_delivery, send_test_newsletter, subscriber,
+    subscriber_import, subscribers, suppressions, update_subscriber, MAX_IMPORT_SIZE,
+};
+use crate::routes::{
+    archived_issue, atom_feed, confirm, download_subscriber_personal_data,
+    download_subscriber_personal_data_form, erase_subscriber_personal_data, home, issues_archive,
+    login, login_form, preferences_form, receive_email_event,
+    request_subscriber_personal_data_link, rss_feed, subscriber_personal_data, unsubscribe,
+    unsubscribe_form, update_preferences,
 };
-use crate::routes::{confirm, home, login, login_form};
 use crate::routes::{health_check, subscribe};
+use crate::suppression::backfill_email_hashes;
+use actix_multipart::form::MultipartFormConfig;
 use sqlx::postgres::PgPoolOptions;
 
 pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
@@ -34,8 +48,10 @@ pub struct Application {
 impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
         let connection_pool = get_connection_pool(&configuration.database);
+        // Audiences are only filtered right once every address has its hash.
+        backfill_email_hashes(&connection_pool).await?;
 
-        let email_client = configuration.email_client.client();
+        let email_client = configuration.email_client.client()?;
 
         let address = format!(
             "{}:{}",
@@ -49,6 +65,8 @@ impl Application {
             email_client,
             configuration.application.base_url,
             configuration.application.hmac_secret,
+            configuration.subscriptions,
+            configuration.webhooks,
             configuration.redis_uri,
         )
         .await?;
@@ -67,18 +85,23 @@ impl Application {
 
 pub struct ApplicationBaseUrl(pub String);
 
+#[allow(clippy::too_many_arguments)]
 async fn run(
     listener: TcpListener,
     db_pool: PgPool,
     email_client: EmailClient,
     base_url: String,
     hmac_secret: Secret<String>,
+    subscription_settings: SubscriptionSettings,
+    webhook_settings: WebhookSettings,
     redis_uri: Secret<String>,
 ) -> Result<Server, anyhow::Error> {
     // DI
     let db_pool = Data::new(db_pool);
     let email_client = Data::new(email_client);
     let base_url = Data::new(ApplicationBaseUrl(base_url));
+    let subscription_settings = Data::new(subscription_settings);
+    let webhook_settings = Data::new(webhook_settings);
 
     // middleware
     let secret_key = Key::from(hmac_secret.expose_secret().as_bytes());
@@ -100,15 +123,125 @@ async fn run(
             .route("/login", web::get().to(login_form))
             .route("/login", web::post().to(login))
             .route("/health_check", web::get().to(health_check))
-            .route("/newsletters", web::post().to(publish_newsletter))
+            .route("/feed.atom", web::get().to(atom_feed))
+            .route("/feed.rss", web::get().to(rss_feed))
+            .route("/issues", web::get().to(issues_archive))
+            .route(
+                "/issues/{newsletter_issue_id}",
+                web::get().to(archived_issue),
+            )
             .route("/subscriptions", web::post().to(subscribe))
             .route("/subscriptions/confirm", web::get().to(confirm))
+            .route(
+                "/subscriptions/unsubscribe",
+                web::get().to(unsubscribe_form),
+            )
+            .route("/subscriptions/unsubscribe", web::post().to(unsubscribe))
+            .route(
+                "/subscriptions/preferences",
+                web::get().to(preferences_form),
+            )
+            .route(
+                "/subscriptions/preferences",
+                web::post().to(update_preferences),
+            )
+            .route(
+                "/subscriptions/personal_data",
+                web::get().to(subscriber_personal_data),
+            )
+            .route(
+                "/subscriptions/personal_data/request",
+                web::post().to(request_subscriber_personal_data_link),
+            )
+            .route(
+                "/subscriptions/personal_data/export",
+                web::get().to(download_subscriber_personal_data_form),
+            )
+            .route(
+                "/subscriptions/personal_data/export",
+                web::post().to(download_subscriber_personal_data),
+            )
+            .route(
+                "/subscriptions/personal_data/erase",
+                web::post().to(erase_subscriber_personal_data),
+            )
+            .route(
+                "/webhooks/email-events",
+                web::post().to(receive_email_event),
+            )
             .service(
                 web::scope("/admin")
                     .wrap(from_fn(reject_anonymous_users))
                     .route("/dashboard", web::get().to(admin_dashboard))
-                    .route("/newsletters", web::get().to(publish_newsletter_form))
-                    .route("/newsletters", web::post().to(publish_newsletter))
+                    .route("/lists", web::get().to(mailing_lists))
+                    .route("/lists", web::post().to(create_mailing_list))
+                    .route("/subscribers", web::get().to(subscribers))
+                    .route("/subscribers/export", web::get().to(export_subscribers))
+                    .route(
+                        "/subscribers/import",
+                        web::get().to(import_subscribers_form),
+                    )
+                    .route("/subscribers/import", web::post().to(import_subscribers))
+                    .route(
+                        "/subscribers/import/{import_id}",
+                        web::get().to(subscriber_import),
+                    )
+                    .route("/subscribers/{subscriber_id}", web::get().to(subscriber))
+                    .route(
+                        "/subscribers/{subscriber_id}",
+                        web::post().to(update_subscriber),
+                    )
+                    .route("/suppressions", web::get().to(suppressions))
+                    .route("/suppressions", web::post().to(add_suppression))
+                    .route("/suppressions/remove", web::post().to(remove_suppression))
+                    .route("/personal_data", web::get().to(personal_data))
+                    .route("/personal_data/export", web::get().to(export_personal_data))
+                    .route("/personal_data/erase", web::post().to(erase_personal_data))
+                    .route("/newsletters", web::get().to(newsletter_issues))
+                    .route("/newsletters", web::post().to(create_newsletter_draft))
+                    .route(
+                        "/newsletters/{newsletter_issue_id}",
+                        web::get().to(newsletter_issue),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/edit",
+                        web::get().to(edit_newsletter_draft_form),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/edit",
+                        web::post().to(edit_newsletter_draft),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/preview",
+                        web::get().to(preview_newsletter_issue),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/publish",
+                        web::post().to(publish_newsletter),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/send_test",
+                        web::post().to(send_test_newsletter),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/reschedule",
+                        web::post().to(reschedule_newsletter_issue),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/cancel",
+                        web::post().to(cancel_newsletter_issue),
+                    )
+                    .route("/deliveries/failed", web::get().to(failed_deliveries))
+                    .route(
+                        "/deliveries/failed/retry",
+                        web::post().to(retry_failed_delivery),
+                    )
+                    .route(
+                        "/deliveries/failed/retry_all",
+                        web::post().to(retry_all_failed_deliveries),
+                    )
+                    .route("/email", web::get().to(change_email_form))
+                    .route("/email", web::post().to(change_email))
                     .route("/password", web::get().to(change_password_form))
                     .route("/password", web::post().to(change_password))
                     .route("/logout", web::post().to(log_out)),
@@ -116,7 +249,14 @@ async fn run(
             .app_data(db_pool.clone())
             .app_data(email_client.clone())
             .app_data(base_url.clone())
+            .app_data(subscription_settings.clone())
+            .app_data(webhook_settings.clone())
             .app_data(Data::new(HmacSecret(hmac_secret.clone())))
+            .app_data(
+                MultipartFormConfig::default()
+                    .memory_limit(MAX_IMPORT_SIZE)
+                    .total_limit(MAX_IMPORT_SIZE),
+            )
     })
     .listen(listener)?
     .run();
@@ -0,0 +1,306 @@
+use crate::configuration::{Settings, SubscriptionSettings};
+use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::email_client::EmailClient;
+use crate::issue_delivery_worker::ExecutionOutcome;
+use crate::personal_data::email_hash;
+use crate::routes::{send_confirmation_email, sign_up};
+use crate::startup::get_connection_pool;
+use crate::suppression::is_suppressed;
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::time::Duration;
+use tracing::{field::display, Span};
+use uuid::Uuid;
+
+type PgTransaction = Transaction<'static, Postgres>;
+
+struct ImportRow {
+    import_id: Uuid,
+    line: i32,
+    email: String,
+    name: String,
+    list_id: Uuid,
+    confirmed: bool,
+    consent_source: Option<String>,
+}
+
+/// Where a row ended up, with an explanation for anything but an import.
+enum RowOutcome {
+    Imported,
+    Skipped(&'static str),
+    Invalid(String),
+}
+
+/// Import the next pending row of a CSV upload.
+#[tracing::instrument(
+    skip_all,
+    fields(import_id=tracing::field::Empty, line=tracing::field::Empty),
+    err
+)]
+pub async fn try_execute_import_task(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+    settings: &SubscriptionSettings,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let Some((mut transaction, row)) = dequeue_row(pool).await? else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current()
+        .record("import_id", display(row.import_id))
+        .record("line", display(row.line));
+
+    let new_subscriber = match (
+        SubscriberEmail::parse(row.email.clone()),
+        SubscriberName::parse(row.name.clone()),
+    ) {
+        (Ok(email), Ok(name)) => NewSubscriber { email, name },
+        (Err(e), _) | (_, Err(e)) => {
+            finish_row(transaction, &row, RowOutcome::Invalid(e)).await?;
+            return Ok(ExecutionOutcome::TaskCompleted);
+        }
+    };
+    if is_suppressed(&mut *transaction, new_subscriber.email.as_ref()).await? {
+        finish_row(
+            transaction,
+            &row,
+            RowOutcome::Skipped("The address is on the suppression list."),
+        )
+        .await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
+    // Imports never override a choice the subscriber made themselves, be it
+    // about the whole newsletter or about this list.
+    let outcome = match get_subscriber_status(&mut transaction, &row)
+        .await?
+        .as_deref()
+    {
+        Some("unsubscribed") => RowOutcome::Skipped("Unsubscribed from the newsletter before."),
+        Some("bounced") => RowOutcome::Skipped("The address bounced before."),
+        Some("complained") => RowOutcome::Skipped("Complained about our emails before."),
+        _ => RowOutcome::Imported,
+    };
+    if !matches!(outcome, RowOutcome::Imported) {
+        finish_row(transaction, &row, outcome).await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
+    let outcome = match get_membership_status(&mut transaction, &row)
+        .await?
+        .as_deref()
+    {
+        Some("confirmed") => RowOutcome::Skipped("Already subscribed."),
+        Some("unsubscribed") => RowOutcome::Skipped("Unsubscribed from this list before."),
+        Some(_) if !row.confirmed => RowOutcome::Skipped("Already asked to confirm."),
+        _ => RowOutcome::Imported,
+    };
+    if !matches!(outcome, RowOutcome::Imported) {
+        finish_row(transaction, &row, outcome).await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
+
+    if row.confirmed {
+        import_confirmed_subscriber(&mut transaction, &row, &new_subscriber).await?;
+        finish_row(transaction, &row, RowOutcome::Imported).await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
+    let subscription_token = sign_up(
+        &mut transaction,
+        &new_subscriber,
+        row.list_id,
+        settings.confirmation_token_ttl(),
+    )
+    .await?;
+    finish_row(transaction, &row, RowOutcome::Imported).await?;
+    if let Some(subscription_token) = subscription_token {
+        if let Err(e) =
+            send_confirmation_email(email_client, &new_subscriber, base_url, &subscription_token)
+                .await
+        {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to send the confirmation email of an imported subscriber."
+            );
+            mark_row_as_failed(pool, &row).await?;
+        }
+    }
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+#[tracing::instrument(skip_all)]
+async fn dequeue_row(pool: &PgPool) -> Result<Option<(PgTransaction, ImportRow)>, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let row = sqlx::query_as!(
+        ImportRow,
+        r#"
+        SELECT r.import_id, r.line, r.email, r.name, i.list_id, i.confirmed, i.consent_source
+        FROM subscriber_import_rows r
+        JOIN subscriber_imports i USING (import_id)
+        WHERE r.status = 'pending'
+        ORDER BY i.created_at, r.line
+        FOR UPDATE OF r
+        SKIP LOCKED
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    Ok(row.map(|row| (transaction, row)))
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_subscriber_status(
+    transaction: &mut PgTransaction,
+    row: &ImportRow,
+) -> Result<Option<String>, sqlx::Error> {
+    let subscriber = sqlx::query!(
+        "SELECT status FROM subscriptions WHERE email = $1",
+        row.email
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    Ok(subscriber.map(|s| s.status))
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_membership_status(
+    transaction: &mut PgTransaction,
+    row: &ImportRow,
+) -> Result<Option<String>, sqlx::Error> {
+    let membership = sqlx::query!(
+        r#"
+        SELECT m.status
+        FROM list_memberships m
+        JOIN subscriptions s ON s.id = m.subscriber_id
+        WHERE s.email = $1 AND m.list_id = $2
+        "#,
+        row.email,
+        row.list_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    Ok(membership.map(|m| m.status))
+}
+
+/// The admin vouched for the subscriber's consent: no confirmation is needed.
+///
+/// An existing subscriber still waiting to confirm is confirmed, any other
+/// status is left as it is.
+#[tracing::instrument(skip_all)]
+async fn import_confirmed_subscriber(
+    transaction: &mut PgTransaction,
+    row: &ImportRow,
+    new_subscriber: &NewSubscriber,
+) -> Result<(), anyhow::Error> {
+    let subscriber = sqlx::query!(
+        r#"
+        INSERT INTO subscriptions
+            (id, email, email_hash, name, subscribed_at, status, consent_source)
+        VALUES ($1, $2, $3, $4, now(), 'confirmed', $5)
+        ON CONFLICT (email) DO UPDATE
+        SET
+            status = CASE subscriptions.status
+                WHEN 'pending_confirmation' THEN 'confirmed'
+                ELSE subscriptions.status
+            END,
+            consent_source = COALESCE(subscriptions.consent_source, EXCLUDED.consent_source)
+        RETURNING id
+        "#,
+        Uuid::new_v4(),
+        new_subscriber.email.as_ref(),
+        email_hash(new_subscriber.email.as_ref()),
+        new_subscriber.name.as_ref(),
+        row.consent_source
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    .context("Failed to insert an imported subscriber.")?;
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            INSERT INTO list_memberships (subscriber_id, list_id, status)
+            VALUES ($1, $2, 'confirmed')
+            ON CONFLICT (subscriber_id, list_id) DO UPDATE SET status = 'confirmed'
+            "#,
+            subscriber.id,
+            row.list_id
+        ))
+        .await
+        .context("Failed to add an imported subscriber to the mailing list.")?;
+    Ok(())
+}
+
+/// Record the outcome of a row.
+#[tracing::instrument(skip_all)]
+async fn finish_row(
+    mut transaction: PgTransaction,
+    row: &ImportRow,
+    outcome: RowOutcome,
+) -> Result<(), anyhow::Error> {
+    let (status, message) = match outcome {
+        RowOutcome::Imported => ("imported", None),
+        RowOutcome::Skipped(message) => ("skipped", Some(message.to_owned())),
+        RowOutcome::Invalid(message) => ("invalid", Some(message)),
+    };
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            UPDATE subscriber_import_rows SET status = $3, message = $4
+            WHERE import_id = $1 AND line = $2
+            "#,
+            row.import_id,
+            row.line,
+            status,
+            message
+        ))
+        .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn mark_row_as_failed(pool: &PgPool, row: &ImportRow) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriber_import_rows
+        SET status = 'failed', message = 'Failed to send the confirmation email.'
+        WHERE import_id = $1 AND line = $2
+        "#,
+        row.import_id,
+        row.line
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn worker_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    base_url: String,
+    settings: SubscriptionSettings,
+) -> Result<(), anyhow::Error> {
+    loop {
+        match try_execute_import_task(&pool, &email_client, &base_url, &settings).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+pub async fn run_import_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    let email_client = configuration.email_client.client()?;
+    worker_loop(
+        connection_pool,
+        email_client,
+        configuration.application.base_url,
+        configuration.subscriptions,
+    )
+    .await
+}
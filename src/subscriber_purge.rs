@@ -0,0 +1,82 @@
+use crate::configuration::{Settings, SubscriptionSettings};
+use crate::startup::get_connection_pool;
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::{Executor, PgPool};
+
+pub struct PurgeOutcome {
+    pub n_subscribers: u64,
+    pub n_tokens: u64,
+}
+
+/// Delete `pending_confirmation` subscribers who signed up more than
+/// `pending_ttl` ago, along with their tokens.
+///
+/// Tokens that expired more than `pending_ttl` ago are deleted as well, whoever they
+/// belong to: until then they are kept to tell an expired link from an unknown one.
+#[tracing::instrument(skip(pool), err)]
+pub async fn purge_stale_subscribers(
+    pool: &PgPool,
+    pending_ttl: chrono::Duration,
+) -> Result<PurgeOutcome, anyhow::Error> {
+    let cutoff = Utc::now() - pending_ttl;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let n_tokens = transaction
+        .execute(sqlx::query!(
+            r#"
+            DELETE FROM subscription_tokens
+            WHERE
+                expires_at < $1 OR
+                subscriber_id IN (
+                    SELECT id FROM subscriptions
+                    WHERE status = 'pending_confirmation' AND subscribed_at < $1
+                )
+            "#,
+            cutoff
+        ))
+        .await
+        .context("Failed to delete stale subscription tokens.")?
+        .rows_affected();
+    let n_subscribers = transaction
+        .execute(sqlx::query!(
+            r#"
+            DELETE FROM subscriptions
+            WHERE status = 'pending_confirmation' AND subscribed_at < $1
+            "#,
+            cutoff
+        ))
+        .await
+        .context("Failed to delete stale pending subscribers.")?
+        .rows_affected();
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to purge stale subscribers.")?;
+    Ok(PurgeOutcome {
+        n_subscribers,
+        n_tokens,
+    })
+}
+
+async fn purge_loop(pool: PgPool, settings: SubscriptionSettings) -> Result<(), anyhow::Error> {
+    let mut interval = tokio::time::interval(settings.purge_interval());
+    loop {
+        interval.tick().await;
+        if let Ok(outcome) = purge_stale_subscribers(&pool, settings.pending_subscriber_ttl()).await
+        {
+            tracing::info!(
+                n_subscribers = outcome.n_subscribers,
+                n_tokens = outcome.n_tokens,
+                "Purged stale subscribers"
+            );
+        }
+    }
+}
+
+pub async fn run_purge_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    purge_loop(connection_pool, configuration.subscriptions).await
+}
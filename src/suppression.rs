@@ -0,0 +1,218 @@
+//! The suppression list: addresses we must never email again, whatever their
+//! subscription says.
+//!
+//! Addresses are matched on their hash, case-insensitively. The address
+//! itself is only kept to show it to admins, and not at all once its owner's
+//! data has been erased.
+use crate::personal_data::email_hash;
+use anyhow::Context;
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SuppressionReason {
+    HardBounce,
+    SpamComplaint,
+    DoNotContact,
+}
+
+impl SuppressionReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SuppressionReason::HardBounce => "hard_bounce",
+            SuppressionReason::SpamComplaint => "spam_complaint",
+            SuppressionReason::DoNotContact => "do_not_contact",
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<SuppressionReason, String> {
+        match s {
+            "hard_bounce" => Ok(SuppressionReason::HardBounce),
+            "spam_complaint" => Ok(SuppressionReason::SpamComplaint),
+            "do_not_contact" => Ok(SuppressionReason::DoNotContact),
+            other => Err(format!("`{}` is not a suppression reason.", other)),
+        }
+    }
+
+    /// How the reason reads in the admin pages.
+    pub fn label(&self) -> &'static str {
+        match self {
+            SuppressionReason::HardBounce => "Hard bounce",
+            SuppressionReason::SpamComplaint => "Spam complaint",
+            SuppressionReason::DoNotContact => "Do not contact",
+        }
+    }
+}
+
+#[tracing::instrument(skip(executor, email))]
+pub async fn is_suppressed<'e>(
+    executor: impl PgExecutor<'e>,
+    email: &str,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM suppressions WHERE email_hash = $1) AS "suppressed!""#,
+        email_hash(email)
+    )
+    .fetch_one(executor)
+    .await
+}
+
+/// Add `email` to the suppression list.
+/// Returns `false` if it was there already, in which case it is left as is.
+#[tracing::instrument(skip(executor, email))]
+pub async fn suppress<'e>(
+    executor: impl PgExecutor<'e>,
+    email: &str,
+    reason: SuppressionReason,
+    source: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO suppressions (email, email_hash, reason, source, created_at)
+        VALUES ($1, $2, $3, $4, now())
+        ON CONFLICT (email_hash) DO NOTHING
+        "#,
+        email,
+        email_hash(email),
+        reason.as_str(),
+        source
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Add `email` to the suppression list by its hash only, forgetting the
+/// address if it was there already: for people whose data has been erased.
+#[tracing::instrument(skip(executor, email))]
+pub async fn suppress_anonymously<'e>(
+    executor: impl PgExecutor<'e>,
+    email: &str,
+    reason: SuppressionReason,
+    source: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO suppressions (email, email_hash, reason, source, created_at)
+        VALUES (NULL, $1, $2, $3, now())
+        ON CONFLICT (email_hash) DO UPDATE SET email = NULL
+        "#,
+        email_hash(email),
+        reason.as_str(),
+        source
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Fill in the address hashes that `email_hash` did not compute: those of
+/// the subscribers that predate them, and those of the suppressions that a
+/// migration hashed in SQL, which does not normalize addresses the same way.
+#[tracing::instrument(skip(pool))]
+pub async fn backfill_email_hashes(pool: &PgPool) -> Result<(), anyhow::Error> {
+    let subscribers = sqlx::query!("SELECT id, email FROM subscriptions WHERE email_hash IS NULL")
+        .fetch_all(pool)
+        .await
+        .context("Failed to retrieve the subscribers without an email hash.")?;
+    if !subscribers.is_empty() {
+        let (ids, hashes): (Vec<Uuid>, Vec<String>) = subscribers
+            .into_iter()
+            .map(|s| (s.id, email_hash(&s.email)))
+            .unzip();
+        sqlx::query!(
+            r#"
+            UPDATE subscriptions s SET email_hash = v.email_hash
+            FROM UNNEST($1::uuid[], $2::text[]) AS v(id, email_hash)
+            WHERE s.id = v.id
+            "#,
+            &ids,
+            &hashes
+        )
+        .execute(pool)
+        .await
+        .context("Failed to store the email hashes of subscribers.")?;
+    }
+
+    let suppressions = sqlx::query!(
+        r#"
+        SELECT email AS "email!", email_hash, reason, source, created_at
+        FROM suppressions
+        WHERE email IS NOT NULL
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the suppressions.")?;
+    for s in suppressions {
+        let hash = email_hash(&s.email);
+        if hash == s.email_hash {
+            continue;
+        }
+        // Addresses SQL told apart may hash the same here: keep the first one.
+        let mut transaction = pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool")?;
+        sqlx::query!(
+            "DELETE FROM suppressions WHERE email_hash = $1",
+            s.email_hash
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete a suppression to rehash.")?;
+        sqlx::query!(
+            r#"
+            INSERT INTO suppressions (email, email_hash, reason, source, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (email_hash) DO NOTHING
+            "#,
+            s.email,
+            hash,
+            s.reason,
+            s.source,
+            s.created_at
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to store a rehashed suppression.")?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to rehash a suppression.")?;
+    }
+    // Forget the erased addresses that only match now that they are rehashed.
+    sqlx::query!(
+        r#"
+        UPDATE suppressions s SET email = NULL
+        WHERE
+            email IS NOT NULL AND
+            EXISTS (
+                SELECT 1 FROM personal_data_audit_log l
+                WHERE l.action = 'erasure' AND l.email_hash = s.email_hash
+            )
+        "#
+    )
+    .execute(pool)
+    .await
+    .context("Failed to forget erased addresses.")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SuppressionReason;
+    use claims::assert_err;
+
+    #[test]
+    fn reasons_round_trip_through_their_name() {
+        for reason in [
+            SuppressionReason::HardBounce,
+            SuppressionReason::SpamComplaint,
+            SuppressionReason::DoNotContact,
+        ] {
+            assert_eq!(SuppressionReason::parse(reason.as_str()), Ok(reason));
+        }
+        assert_err!(SuppressionReason::parse("soft_bounce"));
+    }
+}
@@ -0,0 +1,138 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, publish_newsletter, spawn_app, TestApp,
+};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Publish an issue whose deliveries all fail permanently at the first attempt.
+async fn create_failed_deliveries(app: &TestApp) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    publish_newsletter(app).await;
+    sqlx::query!(
+        "UPDATE issue_delivery_queue SET n_retries = $1",
+        app.issue_delivery.max_retries
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.dispatch_all_pending_emails().await;
+}
+
+async fn n_queued_tasks(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT count(*) AS "n!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_failed_deliveries() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_failed_deliveries().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_retry_failed_deliveries() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_retry_all_failed_deliveries().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn permanently_failed_deliveries_are_listed() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    create_failed_deliveries(&app).await;
+    let email = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+
+    // Act
+    let html_page = app.get_failed_deliveries_html().await;
+
+    // Assert
+    assert!(html_page.contains("Failed deliveries (1)"));
+    assert!(html_page.contains(&email));
+    assert!(html_page.contains("500 Internal Server Error"));
+}
+
+#[tokio::test]
+async fn a_failed_delivery_can_be_re_enqueued() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    create_failed_deliveries(&app).await;
+    let failure = sqlx::query!(
+        "SELECT newsletter_issue_id, subscriber_email FROM issue_delivery_failures LIMIT 1"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act - Part 1 - Retry one delivery
+    let response = app
+        .post_retry_failed_delivery(&serde_json::json!({
+            "newsletter_issue_id": failure.newsletter_issue_id,
+            "subscriber_email": failure.subscriber_email,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/deliveries/failed");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_failed_deliveries_html().await;
+    assert!(html_page.contains("<p><i>1 failed deliveries have been re-enqueued.</i></p>"));
+    assert!(html_page.contains("Failed deliveries (1)"));
+    assert_eq!(n_queued_tasks(&app).await, 1);
+
+    // Act - Part 3 - The worker picks it up again
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(n_queued_tasks(&app).await, 0);
+}
+
+#[tokio::test]
+async fn all_failed_deliveries_can_be_re_enqueued_at_once() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    create_failed_deliveries(&app).await;
+
+    // Act
+    let response = app.post_retry_all_failed_deliveries().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/deliveries/failed");
+    let html_page = app.get_failed_deliveries_html().await;
+    assert!(html_page.contains("<p><i>2 failed deliveries have been re-enqueued.</i></p>"));
+    assert!(html_page.contains("Failed deliveries (0)"));
+    assert_eq!(n_queued_tasks(&app).await, 2);
+}
@@ -0,0 +1,78 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_change_email_form() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_change_email().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_change_email_form_shows_the_current_address() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let html_page = app.get_change_email_html().await;
+
+    // Assert
+    assert!(html_page.contains(&format!(r#"value="{}""#, app.test_user.email)));
+}
+
+#[tokio::test]
+async fn changing_email_works() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Change email
+    let response = app
+        .post_change_email(&serde_json::json!({ "email": "editor@example.com" }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/email");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_change_email_html().await;
+    assert!(html_page.contains("<p><i>Your email address has been changed.</i></p>"));
+
+    // Assert
+    let saved = sqlx::query!(
+        "SELECT email FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.email.as_deref(), Some("editor@example.com"));
+}
+
+#[tokio::test]
+async fn an_invalid_email_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app
+        .post_change_email(&serde_json::json!({ "email": "definitely-not-an-email" }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/email");
+
+    // Assert
+    let html_page = app.get_change_email_html().await;
+    assert!(html_page.contains("<p><i>Please enter a valid email address.</i></p>"));
+    let saved = sqlx::query!(
+        "SELECT email FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.email, Some(app.test_user.email.clone()));
+}
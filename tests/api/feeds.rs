@@ -0,0 +1,135 @@
+use crate::helpers::{create_newsletter_draft, publish_newsletter, spawn_app};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn feeds_list_published_issues_only() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let published_issue_id = publish_newsletter(&app).await;
+    let draft_id = create_newsletter_draft(&app).await;
+
+    for (feed, content_type) in [
+        ("feed.rss", "application/rss+xml"),
+        ("feed.atom", "application/atom+xml"),
+    ] {
+        // Act
+        let response = app.get_feed(feed, &[]).await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 200);
+        assert!(response.headers()["Content-Type"]
+            .to_str()
+            .unwrap()
+            .starts_with(content_type));
+        let xml = response.text().await.unwrap();
+        assert!(xml.contains(&published_issue_id.to_string()));
+        assert!(!xml.contains(&draft_id.to_string()));
+        // The HTML body is escaped rather than embedded as markup.
+        assert!(xml.contains("&lt;p&gt;Newsletter body as HTML&lt;/p&gt;"));
+    }
+}
+
+#[tokio::test]
+async fn rss_items_carry_a_guid_and_a_publication_date() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let newsletter_issue_id = publish_newsletter(&app).await;
+
+    // Act
+    let xml = app.get_feed("feed.rss", &[]).await.text().await.unwrap();
+
+    // Assert
+    assert!(xml.contains(&format!(
+        r#"<guid isPermaLink="true">{}/issues/{}</guid>"#,
+        app.base_url, newsletter_issue_id
+    )));
+    assert!(xml.contains("<pubDate>"));
+}
+
+#[tokio::test]
+async fn feeds_answer_304_when_the_etag_still_matches() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    publish_newsletter(&app).await;
+    let response = app.get_feed("feed.atom", &[]).await;
+    let etag = response.headers()["ETag"].to_str().unwrap().to_owned();
+
+    // Act
+    let response = app.get_feed("feed.atom", &[("If-None-Match", &etag)]).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 304);
+    assert_eq!(response.headers()["ETag"].to_str().unwrap(), etag);
+    assert!(response.text().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn feeds_answer_304_when_unmodified_since_the_last_fetch() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    publish_newsletter(&app).await;
+    let response = app.get_feed("feed.rss", &[]).await;
+    let last_modified = response.headers()["Last-Modified"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+
+    // Act
+    let response = app
+        .get_feed("feed.rss", &[("If-Modified-Since", &last_modified)])
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 304);
+}
+
+#[tokio::test]
+async fn feeds_change_etag_when_a_new_issue_goes_out() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    publish_newsletter(&app).await;
+    let response = app.get_feed("feed.rss", &[]).await;
+    let etag = response.headers()["ETag"].to_str().unwrap().to_owned();
+
+    // Act
+    let newsletter_issue_id = publish_newsletter(&app).await;
+    let response = app.get_feed("feed.rss", &[("If-None-Match", &etag)]).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_ne!(response.headers()["ETag"].to_str().unwrap(), etag);
+    let xml = response.text().await.unwrap();
+    assert!(xml.contains(&newsletter_issue_id.to_string()));
+}
+
+#[tokio::test]
+async fn issues_sent_to_a_segment_do_not_show_up_in_feeds() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    publish_newsletter(&app).await;
+    let response = app.get_feed("feed.atom", &[]).await;
+    let etag = response.headers()["ETag"].to_str().unwrap().to_owned();
+
+    // Act
+    let newsletter_issue_id = create_newsletter_draft(&app).await;
+    app.post_publish_newsletter(
+        newsletter_issue_id,
+        &serde_json::json!({
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "segment": "tag:beta",
+        }),
+    )
+    .await;
+    let response = app.get_feed("feed.atom", &[("If-None-Match", &etag)]).await;
+    let xml = app.get_feed("feed.rss", &[]).await.text().await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 304);
+    assert!(!xml.contains(&newsletter_issue_id.to_string()));
+}
@@ -1,16 +1,22 @@
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
-use secrecy::Secret;
+use fake::faker::internet::en::SafeEmail;
+use fake::faker::name::en::Name;
+use fake::Fake;
+use secrecy::{ExposeSecret, Secret};
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use std::sync::LazyLock;
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
 use zero2prod::configuration::{
-    get_configuration, DatabaseSettings, EmailProviderURLSettings, KindEmailProviderSettings,
+    get_configuration, DatabaseSettings, EmailProviderURLSettings, IssueDeliverySettings,
+    KindEmailProviderSettings, SubscriptionSettings, WebhookSettings,
 };
 use zero2prod::email_client::EmailClient;
 use zero2prod::issue_delivery_worker::{try_execute_task, ExecutionOutcome};
 use zero2prod::startup::{get_connection_pool, Application};
+use zero2prod::subscriber_import_worker::try_execute_import_task;
 use zero2prod::telemetry::{get_subscriber, init_subscriber};
 
 // Ensure that the `tracing` stack is only initialised once using `once_cell`
@@ -34,6 +40,11 @@ pub struct TestApp {
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
     pub email_client: EmailClient,
+    pub base_url: String,
+    pub hmac_secret: Secret<String>,
+    pub issue_delivery: IssueDeliverySettings,
+    pub subscriptions: SubscriptionSettings,
+    pub webhooks: WebhookSettings,
 }
 
 /// Confirmation links embedded in the request to the email API.
@@ -46,10 +57,32 @@ pub struct ConfirmationLinks {
 impl TestApp {
     pub async fn dispatch_all_pending_emails(&self) {
         loop {
-            if let ExecutionOutcome::EmptyQueue =
-                try_execute_task(&self.db_pool, &self.email_client)
-                    .await
-                    .unwrap()
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.base_url,
+                &self.hmac_secret,
+                &self.issue_delivery,
+                &self.subscriptions,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
+
+    pub async fn import_all_pending_rows(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = try_execute_import_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.base_url,
+                &self.subscriptions,
+            )
+            .await
+            .unwrap()
             {
                 break;
             }
@@ -66,7 +99,7 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
-    pub async fn get_publish_newsletter(&self) -> reqwest::Response {
+    pub async fn get_newsletter_issues(&self) -> reqwest::Response {
         self.api_client
             .get(format!("{}/admin/newsletters", &self.address))
             .send()
@@ -74,11 +107,11 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
-    pub async fn get_publish_newsletter_html(&self) -> String {
-        self.get_publish_newsletter().await.text().await.unwrap()
+    pub async fn get_newsletter_issues_html(&self) -> String {
+        self.get_newsletter_issues().await.text().await.unwrap()
     }
 
-    pub async fn post_publish_newsletter<Body>(&self, body: &Body) -> reqwest::Response
+    pub async fn post_newsletter_draft<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
@@ -90,6 +123,85 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_edit_newsletter_draft(&self, newsletter_issue_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletters/{}/edit",
+                &self.address, newsletter_issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_edit_newsletter_draft<Body>(
+        &self,
+        newsletter_issue_id: Uuid,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/{}/edit",
+                &self.address, newsletter_issue_id
+            ))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_newsletter_issue_preview(
+        &self,
+        newsletter_issue_id: Uuid,
+        revision: Option<i32>,
+    ) -> reqwest::Response {
+        let mut url = format!(
+            "{}/admin/newsletters/{}/preview",
+            &self.address, newsletter_issue_id
+        );
+        if let Some(revision) = revision {
+            url.push_str(&format!("?revision={}", revision));
+        }
+        self.api_client
+            .get(url)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_send_test_newsletter(&self, newsletter_issue_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/{}/send_test",
+                &self.address, newsletter_issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_publish_newsletter<Body>(
+        &self,
+        newsletter_issue_id: Uuid,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/{}/publish",
+                &self.address, newsletter_issue_id
+            ))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     /// Extract the confirmation links embedded in the request to the email API.
     pub fn get_confirmation_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
         let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
@@ -114,6 +226,92 @@ impl TestApp {
         ConfirmationLinks { html, plain_text }
     }
 
+    /// Find the first link in the plain text body of a newsletter email
+    /// whose path contains `needle`, pointed at the test application.
+    fn get_newsletter_link(&self, email_request: &wiremock::Request, needle: &str) -> reqwest::Url {
+        let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+        let raw_link = linkify::LinkFinder::new()
+            .links(body["TextBody"].as_str().unwrap())
+            .map(|l| l.as_str().to_owned())
+            .find(|l| l.contains(needle))
+            .unwrap();
+        let mut link = reqwest::Url::parse(&raw_link).unwrap();
+        assert_eq!(link.host_str().unwrap(), "127.0.0.1");
+        link.set_port(Some(self.port)).unwrap();
+        link
+    }
+
+    pub fn get_unsubscribe_link(&self, email_request: &wiremock::Request) -> reqwest::Url {
+        self.get_newsletter_link(email_request, "/subscriptions/unsubscribe")
+    }
+
+    pub fn get_preferences_link(&self, email_request: &wiremock::Request) -> reqwest::Url {
+        self.get_newsletter_link(email_request, "/subscriptions/preferences")
+    }
+
+    pub fn get_personal_data_link(&self, email_request: &wiremock::Request) -> reqwest::Url {
+        self.get_newsletter_link(email_request, "/subscriptions/personal_data")
+    }
+
+    pub fn get_archive_link(&self, email_request: &wiremock::Request) -> reqwest::Url {
+        self.get_newsletter_link(email_request, "/issues/")
+    }
+
+    pub async fn post_preferences<Body>(
+        &self,
+        preferences_link: reqwest::Url,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize + ?Sized,
+    {
+        self.api_client
+            .post(preferences_link)
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_unsubscribe(&self, unsubscribe_link: reqwest::Url) -> reqwest::Response {
+        self.api_client
+            .post(unsubscribe_link)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("List-Unsubscribe=One-Click")
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_issues_archive(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/issues", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_issues_archive_html(&self) -> String {
+        self.get_issues_archive().await.text().await.unwrap()
+    }
+
+    /// Fetch one of the feeds, e.g. `feed.rss`, with extra request headers.
+    pub async fn get_feed(&self, feed: &str, headers: &[(&str, &str)]) -> reqwest::Response {
+        let mut request = self.api_client.get(format!("{}/{}", &self.address, feed));
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn get_archived_issue(&self, newsletter_issue_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/issues/{}", &self.address, newsletter_issue_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -149,6 +347,230 @@ impl TestApp {
         self.get_admin_dashboard().await.text().await.unwrap()
     }
 
+    pub async fn get_mailing_lists(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/lists", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_mailing_lists_html(&self) -> String {
+        self.get_mailing_lists().await.text().await.unwrap()
+    }
+
+    pub async fn post_mailing_list<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/lists", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscribers_html(&self, tag: &str) -> String {
+        self.api_client
+            .get(format!("{}/admin/subscribers", &self.address))
+            .query(&[("tag", tag)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_subscriber_html(&self, subscriber_id: Uuid) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/{}",
+                &self.address, subscriber_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_subscriber<Body>(&self, subscriber_id: Uuid, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!(
+                "{}/admin/subscribers/{}",
+                &self.address, subscriber_id
+            ))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// The issue page, as shown by the "Preview audience" button.
+    pub async fn get_audience_preview_html(
+        &self,
+        newsletter_issue_id: Uuid,
+        list_id: Uuid,
+        segment: &str,
+    ) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletters/{}",
+                &self.address, newsletter_issue_id
+            ))
+            .query(&[
+                ("list_id", list_id.to_string().as_str()),
+                ("segment", segment),
+            ])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    /// Upload `csv` along with the other `fields` of the import form.
+    pub async fn post_subscriber_import(
+        &self,
+        csv: &str,
+        fields: &[(&'static str, String)],
+    ) -> reqwest::Response {
+        let file = reqwest::multipart::Part::text(csv.to_owned()).file_name("subscribers.csv");
+        let form = fields.iter().cloned().fold(
+            reqwest::multipart::Form::new().part("file", file),
+            |form, (name, value)| form.text(name, value),
+        );
+        self.api_client
+            .post(format!("{}/admin/subscribers/import", &self.address))
+            .multipart(form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscriber_import_html(&self, location: &str) -> String {
+        self.api_client
+            .get(format!("{}{}", &self.address, location))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_subscriber_export(&self, query: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/subscribers/export", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_email_event(&self, event: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/webhooks/email-events", &self.address))
+            .basic_auth(
+                &self.webhooks.username,
+                Some(self.webhooks.password.expose_secret()),
+            )
+            .json(event)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_suppressions(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/suppressions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_suppressions_html(&self) -> String {
+        self.get_suppressions().await.text().await.unwrap()
+    }
+
+    pub async fn post_suppression(&self, email: &str, reason: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/suppressions", &self.address))
+            .form(&[("email", email), ("reason", reason)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_remove_suppression(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/suppressions/remove", &self.address))
+            .form(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_personal_data_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/personal_data", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_personal_data_export(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/personal_data/export", &self.address))
+            .query(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_personal_data_erasure(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/personal_data/erase", &self.address))
+            .form(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_change_email(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/email", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_change_email_html(&self) -> String {
+        self.get_change_email().await.text().await.unwrap()
+    }
+
+    pub async fn post_change_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/email", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn get_change_password(&self) -> reqwest::Response {
         self.api_client
             .get(format!("{}/admin/password", &self.address))
@@ -173,6 +595,85 @@ impl TestApp {
         self.get_change_password().await.text().await.unwrap()
     }
 
+    pub async fn get_newsletter_issue(&self, newsletter_issue_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletters/{}",
+                &self.address, newsletter_issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_reschedule_newsletter_issue<Body>(
+        &self,
+        newsletter_issue_id: Uuid,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/{}/reschedule",
+                &self.address, newsletter_issue_id
+            ))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_cancel_newsletter_issue(
+        &self,
+        newsletter_issue_id: Uuid,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/{}/cancel",
+                &self.address, newsletter_issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_failed_deliveries(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/deliveries/failed", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_failed_deliveries_html(&self) -> String {
+        self.get_failed_deliveries().await.text().await.unwrap()
+    }
+
+    pub async fn post_retry_failed_delivery<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/deliveries/failed/retry", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_retry_all_failed_deliveries(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/deliveries/failed/retry_all",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
             .post(format!("{}/admin/logout", &self.address))
@@ -186,6 +687,7 @@ pub struct TestUser {
     pub user_id: Uuid,
     pub username: String,
     pub password: String,
+    pub email: String,
 }
 
 impl TestUser {
@@ -194,6 +696,7 @@ impl TestUser {
             user_id: Uuid::new_v4(),
             username: Uuid::new_v4().to_string(),
             password: Uuid::new_v4().to_string(),
+            email: SafeEmail().fake(),
         }
     }
 
@@ -217,11 +720,12 @@ impl TestUser {
         .to_string();
 
         sqlx::query!(
-            "INSERT INTO users (user_id, username, password_hash)
-VALUES ($1, $2, $3)",
+            "INSERT INTO users (user_id, username, password_hash, email)
+VALUES ($1, $2, $3, $4)",
             self.user_id,
             self.username,
             password_hash,
+            self.email,
         )
         .execute(pool)
         .await
@@ -277,7 +781,12 @@ pub async fn spawn_app() -> TestApp {
         email_server,
         test_user: TestUser::generate(),
         api_client: client,
-        email_client: configuration.email_client.client(),
+        email_client: configuration.email_client.client().unwrap(),
+        base_url: configuration.application.base_url.clone(),
+        hmac_secret: configuration.application.hmac_secret.clone(),
+        issue_delivery: configuration.issue_delivery.clone(),
+        subscriptions: configuration.subscriptions.clone(),
+        webhooks: configuration.webhooks.clone(),
     };
     test_app.test_user.store(&test_app.db_pool).await;
     test_app
@@ -310,6 +819,104 @@ async fn configure_database(config: &DatabaseSettings) -> PgPool {
     connection_pool
 }
 
+pub async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
+    let name: String = Name().fake();
+    let email: String = SafeEmail().fake();
+    let body = serde_urlencoded::to_string(serde_json::json!({
+        "name": name,
+        "email": email,
+    }))
+    .unwrap();
+
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .named("Create unconfirmed subscriber")
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions(body.into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    app.get_confirmation_links(email_request)
+}
+
+pub async fn create_confirmed_subscriber(app: &TestApp) {
+    let confirmation_link = create_unconfirmed_subscriber(app).await.html;
+    reqwest::get(confirmation_link)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+pub async fn default_list_id(app: &TestApp) -> Uuid {
+    sqlx::query!("SELECT list_id FROM lists WHERE is_default")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .list_id
+}
+
+/// Create a mailing list next to the default one and return its id.
+pub async fn create_mailing_list(app: &TestApp, name: &str) -> Uuid {
+    let list_id = Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO lists (list_id, name) VALUES ($1, $2)",
+        list_id,
+        name
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    list_id
+}
+
+/// Save a newsletter draft and return its id.
+pub async fn create_newsletter_draft(app: &TestApp) -> Uuid {
+    let response = app
+        .post_newsletter_draft(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 303);
+    let location = response
+        .headers()
+        .get("Location")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    location
+        .strip_prefix("/admin/newsletters/")
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+/// Publish a newsletter issue to all confirmed subscribers and return its id.
+pub async fn publish_newsletter(app: &TestApp) -> Uuid {
+    let newsletter_issue_id = create_newsletter_draft(app).await;
+    app.post_publish_newsletter(
+        newsletter_issue_id,
+        &serde_json::json!({
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }),
+    )
+    .await;
+    newsletter_issue_id
+}
+
 pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
     assert_eq!(response.status().as_u16(), 303);
     assert_eq!(response.headers().get("Location").unwrap(), location);
@@ -0,0 +1,115 @@
+use crate::helpers::{create_confirmed_subscriber, publish_newsletter, spawn_app};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn failed_deliveries_are_rescheduled_with_a_backoff() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let task = sqlx::query!(
+        r#"
+        SELECT n_retries, execute_after > now() AS "in_the_future!"
+        FROM issue_delivery_queue
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("The failed task should still be queued");
+    assert_eq!(task.n_retries, 1);
+    assert!(task.in_the_future);
+}
+
+#[tokio::test]
+async fn rescheduled_deliveries_are_sent_once_their_time_has_come() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    sqlx::query!("UPDATE issue_delivery_queue SET execute_after = now()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let n_tasks = sqlx::query!(r#"SELECT count(*) AS "n!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_tasks, 0);
+}
+
+#[tokio::test]
+async fn deliveries_are_moved_to_the_failures_table_after_the_maximum_number_of_retries() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    publish_newsletter(&app).await;
+    sqlx::query!(
+        "UPDATE issue_delivery_queue SET n_retries = $1",
+        app.issue_delivery.max_retries
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let n_tasks = sqlx::query!(r#"SELECT count(*) AS "n!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_tasks, 0);
+    let failure = sqlx::query!("SELECT n_attempts, last_error FROM issue_delivery_failures")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(failure.n_attempts, app.issue_delivery.max_retries + 1);
+    assert!(failure.last_error.contains("500 Internal Server Error"));
+}
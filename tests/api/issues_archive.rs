@@ -0,0 +1,202 @@
+use crate::helpers::{
+    create_confirmed_subscriber, create_mailing_list, create_newsletter_draft, publish_newsletter,
+    spawn_app,
+};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn published_issues_are_listed_in_the_public_archive() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let newsletter_issue_id = publish_newsletter(&app).await;
+    app.post_logout().await;
+
+    // Act - Part 1 - List the issues
+    let html_page = app.get_issues_archive_html().await;
+    assert!(html_page.contains(&format!(r#"href="/issues/{newsletter_issue_id}""#)));
+    assert!(html_page.contains("Newsletter title"));
+
+    // Act - Part 2 - Read one of them
+    let response = app.get_archived_issue(newsletter_issue_id).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<p>Newsletter body as HTML</p>"));
+}
+
+#[tokio::test]
+async fn drafts_are_not_in_the_public_archive() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let newsletter_issue_id = create_newsletter_draft(&app).await;
+
+    // Act
+    let html_page = app.get_issues_archive_html().await;
+    let response = app.get_archived_issue(newsletter_issue_id).await;
+
+    // Assert
+    assert!(!html_page.contains(&newsletter_issue_id.to_string()));
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn scheduled_and_cancelled_issues_are_not_in_the_public_archive() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let scheduled_issue_id = create_newsletter_draft(&app).await;
+    let cancelled_issue_id = create_newsletter_draft(&app).await;
+    for newsletter_issue_id in [scheduled_issue_id, cancelled_issue_id] {
+        app.post_publish_newsletter(
+            newsletter_issue_id,
+            &serde_json::json!({
+                "idempotency_key": Uuid::new_v4().to_string(),
+                "send_at": (Utc::now() + Duration::days(1)).to_rfc3339(),
+            }),
+        )
+        .await;
+    }
+    app.post_cancel_newsletter_issue(cancelled_issue_id).await;
+
+    // Act
+    let html_page = app.get_issues_archive_html().await;
+
+    // Assert
+    for newsletter_issue_id in [scheduled_issue_id, cancelled_issue_id] {
+        assert!(!html_page.contains(&newsletter_issue_id.to_string()));
+        let response = app.get_archived_issue(newsletter_issue_id).await;
+        assert_eq!(response.status().as_u16(), 404);
+    }
+}
+
+#[tokio::test]
+async fn issues_sent_to_a_restricted_audience_are_not_in_the_public_archive() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let list_id = create_mailing_list(&app, "Release notes").await;
+    let list_issue_id = create_newsletter_draft(&app).await;
+    let segment_issue_id = create_newsletter_draft(&app).await;
+    app.post_publish_newsletter(
+        list_issue_id,
+        &serde_json::json!({
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "list_id": list_id,
+        }),
+    )
+    .await;
+    app.post_publish_newsletter(
+        segment_issue_id,
+        &serde_json::json!({
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "segment": "tag:beta",
+        }),
+    )
+    .await;
+
+    // Act
+    let html_page = app.get_issues_archive_html().await;
+
+    // Assert
+    for newsletter_issue_id in [list_issue_id, segment_issue_id] {
+        assert!(!html_page.contains(&newsletter_issue_id.to_string()));
+        let response = app.get_archived_issue(newsletter_issue_id).await;
+        assert_eq!(response.status().as_u16(), 404);
+    }
+}
+
+#[tokio::test]
+async fn newsletter_emails_link_to_the_web_version_of_the_issue() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let newsletter_issue_id = publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let archive_link = app.get_archive_link(&email_request);
+
+    // Assert
+    assert_eq!(
+        archive_link.path(),
+        format!("/issues/{newsletter_issue_id}")
+    );
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert!(body["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .contains("View in browser"));
+    let response = reqwest::get(archive_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn issues_sent_to_a_restricted_audience_do_not_link_to_a_web_version() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let list_id = create_mailing_list(&app, "Release notes").await;
+    sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (subscriber_id, list_id, status)
+        SELECT id, $1, 'confirmed' FROM subscriptions
+        "#,
+        list_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let newsletter_issue_id = create_newsletter_draft(&app).await;
+    app.post_publish_newsletter(
+        newsletter_issue_id,
+        &serde_json::json!({
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "list_id": list_id,
+        }),
+    )
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    for content in [&body["HtmlBody"], &body["TextBody"]] {
+        let content = content.as_str().unwrap();
+        assert!(!content.contains("View in browser"));
+        assert!(!content.contains(&format!("/issues/{newsletter_issue_id}")));
+        assert!(content.contains("Unsubscribe"));
+    }
+}
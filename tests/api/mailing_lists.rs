@@ -0,0 +1,306 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_mailing_list,
+    create_newsletter_draft, default_list_id, spawn_app, TestApp,
+};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Subscribe to `list_id` and follow the confirmation link.
+async fn join_list(app: &TestApp, email: &str, list_id: Uuid) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let body = format!("name=le%20guin&email={email}&list_id={list_id}");
+    app.post_subscriptions(body)
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_link = app.get_confirmation_links(&email_request).html;
+    reqwest::get(confirmation_link)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+async fn publish_to_list(app: &TestApp, list_id: Uuid) -> reqwest::Response {
+    let newsletter_issue_id = create_newsletter_draft(app).await;
+    app.post_publish_newsletter(
+        newsletter_issue_id,
+        &serde_json::json!({
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "list_id": list_id,
+        }),
+    )
+    .await
+}
+
+async fn membership_status(app: &TestApp, list_id: Uuid) -> String {
+    sqlx::query!(
+        "SELECT status FROM list_memberships WHERE list_id = $1",
+        list_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .status
+}
+
+#[tokio::test]
+async fn subscribers_join_the_default_list_when_none_is_chosen() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    create_confirmed_subscriber(&app).await;
+
+    // Assert
+    let saved = sqlx::query!(
+        r#"
+        SELECT l.name, m.status
+        FROM list_memberships m
+        JOIN lists l ON l.list_id = m.list_id
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.name, "Newsletter");
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn subscribers_can_join_a_chosen_list() {
+    // Arrange
+    let app = spawn_app().await;
+    let list_id = create_mailing_list(&app, "Release notes").await;
+
+    // Act
+    join_list(&app, "ursula_le_guin%40gmail.com", list_id).await;
+
+    // Assert
+    assert_eq!(membership_status(&app, list_id).await, "confirmed");
+}
+
+#[tokio::test]
+async fn subscribing_to_an_unknown_list_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_subscriptions(format!(
+            "name=le%20guin&email=ursula_le_guin%40gmail.com&list_id={}",
+            Uuid::new_v4()
+        ))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn joining_a_second_list_needs_its_own_confirmation() {
+    // Arrange
+    let app = spawn_app().await;
+    let email = "ursula_le_guin%40gmail.com";
+    let default_list_id = default_list_id(&app).await;
+    let list_id = create_mailing_list(&app, "Release notes").await;
+    join_list(&app, email, default_list_id).await;
+
+    // Act
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions(format!("name=le%20guin&email={email}&list_id={list_id}"))
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    assert_eq!(membership_status(&app, default_list_id).await, "confirmed");
+    assert_eq!(
+        membership_status(&app, list_id).await,
+        "pending_confirmation"
+    );
+}
+
+#[tokio::test]
+async fn issues_are_only_delivered_to_the_confirmed_members_of_their_list() {
+    // Arrange
+    let app = spawn_app().await;
+    let list_id = create_mailing_list(&app, "Release notes").await;
+    create_confirmed_subscriber(&app).await;
+    join_list(&app, "ursula_le_guin%40gmail.com", list_id).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = publish_to_list(&app, list_id).await;
+    assert_eq!(response.status().as_u16(), 303);
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "ursula_le_guin@gmail.com");
+    // Mock verifies on Drop that only one newsletter email went out
+}
+
+#[tokio::test]
+async fn publishing_to_an_unknown_list_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = publish_to_list(&app, Uuid::new_v4()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn unsubscribing_from_a_list_keeps_the_other_memberships() {
+    // Arrange
+    let app = spawn_app().await;
+    let list_id = create_mailing_list(&app, "Release notes").await;
+    let email = "ursula_le_guin%40gmail.com";
+    join_list(&app, email, list_id).await;
+    let default_list_id = default_list_id(&app).await;
+    join_list(&app, email, default_list_id).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    publish_to_list(&app, list_id).await;
+    app.dispatch_all_pending_emails().await;
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let unsubscribe_link = app.get_unsubscribe_link(&email_request);
+
+    // Act
+    let response = app.post_unsubscribe(unsubscribe_link).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(membership_status(&app, list_id).await, "unsubscribed");
+    assert_eq!(membership_status(&app, default_list_id).await, "confirmed");
+}
+
+#[tokio::test]
+async fn the_list_of_an_unsubscribe_link_cannot_be_changed() {
+    // Arrange
+    let app = spawn_app().await;
+    let list_id = create_mailing_list(&app, "Release notes").await;
+    let email = "ursula_le_guin%40gmail.com";
+    join_list(&app, email, list_id).await;
+    let default_list_id = default_list_id(&app).await;
+    join_list(&app, email, default_list_id).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    publish_to_list(&app, list_id).await;
+    app.dispatch_all_pending_emails().await;
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let unsubscribe_link = app.get_unsubscribe_link(&email_request);
+    // The list id comes last in the link: drop it, or point it at another list.
+    let (all_lists_link, _) = unsubscribe_link.as_str().split_once("&list_id=").unwrap();
+    let tampered_links = [
+        all_lists_link.to_owned(),
+        format!("{}&list_id={}", all_lists_link, default_list_id),
+    ]
+    .map(|link| reqwest::Url::parse(&link).unwrap());
+
+    for link in tampered_links {
+        // Act
+        let form = app.api_client.get(link.clone()).send().await.unwrap();
+        let response = app.post_unsubscribe(link).await;
+
+        // Assert
+        assert_eq!(form.status().as_u16(), 401);
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    assert_eq!(membership_status(&app, list_id).await, "confirmed");
+    assert_eq!(membership_status(&app, default_list_id).await, "confirmed");
+}
+
+#[tokio::test]
+async fn admins_can_create_mailing_lists() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Create the list
+    let response = app
+        .post_mailing_list(&serde_json::json!({ "name": "Release notes" }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/lists");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_mailing_lists_html().await;
+    assert!(html_page.contains("<p><i>The list has been created.</i></p>"));
+    assert!(html_page.contains("Release notes"));
+
+    // Act - Part 3 - Try to reuse the name
+    app.post_mailing_list(&serde_json::json!({ "name": "Release notes" }))
+        .await;
+    let html_page = app.get_mailing_lists_html().await;
+    assert!(html_page.contains("<p><i>There is already a list with that name.</i></p>"));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_mailing_lists() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_mailing_lists().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
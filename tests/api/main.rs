@@ -1,8 +1,25 @@
 mod admin_dashboard;
+mod admin_deliveries;
+mod change_email;
 mod change_password;
+mod feeds;
 mod health_check;
 mod helpers;
+mod issue_delivery;
+mod issues_archive;
 mod login;
+mod mailing_lists;
 mod newsletetter;
+mod personal_data;
+mod scheduled_newsletters;
+mod segments;
+mod send_test_newsletter;
+mod subscriber_export;
+mod subscriber_import;
+mod subscriber_purge;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_preferences;
+mod subscriptions_unsubscribe;
+mod suppressions;
+mod webhooks;
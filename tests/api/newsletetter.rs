@@ -1,57 +1,19 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app, ConfirmationLinks, TestApp};
-use fake::faker::internet::en::SafeEmail;
-use fake::faker::name::en::Name;
-use fake::Fake;
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_newsletter_draft,
+    create_unconfirmed_subscriber, publish_newsletter, spawn_app,
+};
 use std::time::Duration;
+use uuid::Uuid;
 use wiremock::matchers::{any, method, path};
 use wiremock::{Mock, MockBuilder, ResponseTemplate};
 
-async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
-    let name: String = Name().fake();
-    let email: String = SafeEmail().fake();
-    let body = serde_urlencoded::to_string(serde_json::json!({
-        "name": name,
-        "email": email,
-    }))
-    .unwrap();
-
-    let _mock_guard = Mock::given(path("/email"))
-        .and(method("POST"))
-        .respond_with(ResponseTemplate::new(200))
-        .named("Create unconfirmed subscriber")
-        .expect(1)
-        .mount_as_scoped(&app.email_server)
-        .await;
-    app.post_subscriptions(body.into())
-        .await
-        .error_for_status()
-        .unwrap();
-
-    let email_request = &app
-        .email_server
-        .received_requests()
-        .await
-        .unwrap()
-        .pop()
-        .unwrap();
-    app.get_confirmation_links(email_request)
-}
-
-async fn create_confirmed_subscriber(app: &TestApp) {
-    let confirmation_link = create_unconfirmed_subscriber(app).await.html;
-    reqwest::get(confirmation_link)
-        .await
-        .unwrap()
-        .error_for_status()
-        .unwrap();
-}
-
 #[tokio::test]
 async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() {
     // Arrange
     let app = spawn_app().await;
     create_unconfirmed_subscriber(&app).await;
     app.test_user.login(&app).await;
+    let newsletter_issue_id = create_newsletter_draft(&app).await;
 
     Mock::given(any())
         .respond_with(ResponseTemplate::new(200))
@@ -59,19 +21,29 @@ async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() {
         .mount(&app.email_server)
         .await;
 
-    // Act - Part 1 - Submit newsletter form
-    let newsletter_request_body = serde_json::json!({
-        "title": "Newsletter title",
-        "text_content": "Newsletter body as plain text",
-        "html_content": "<p>Newsletter body as HTML</p>",
-        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    // Act - Part 1 - Publish the draft
+    let publish_request_body = serde_json::json!({
+        "idempotency_key": Uuid::new_v4().to_string(),
     });
-    let response = app.post_publish_newsletter(&newsletter_request_body).await;
-    assert_is_redirect_to(&response, "/admin/newsletters");
+    let response = app
+        .post_publish_newsletter(newsletter_issue_id, &publish_request_body)
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{newsletter_issue_id}"),
+    );
 
     // Act - Part 2 - Follow the redirect
-    let html_page = app.get_publish_newsletter_html().await;
-    assert!(html_page.contains("<p><i>The newsletter issue has been published!</i></p>"));
+    let html_page = app
+        .get_newsletter_issue(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(
+        "<p><i>The newsletter issue has been accepted - \
+        emails will go out shortly.</i></p>"
+    ));
     app.dispatch_all_pending_emails().await;
     // Mock verifies on Drop that we haven't sent the newsletter email
 }
@@ -82,6 +54,7 @@ async fn newsletters_are_delivered_to_confirmed_subscribers() {
     let app = spawn_app().await;
     create_confirmed_subscriber(&app).await;
     app.test_user.login(&app).await;
+    let newsletter_issue_id = create_newsletter_draft(&app).await;
 
     Mock::given(path("/email"))
         .and(method("POST"))
@@ -90,25 +63,35 @@ async fn newsletters_are_delivered_to_confirmed_subscribers() {
         .mount(&app.email_server)
         .await;
 
-    // Act - Part 1 - Submit newsletter form
-    let newsletter_request_body = serde_json::json!({
-        "title": "Newsletter title",
-        "text_content": "Newsletter body as plain text",
-        "html_content": "<p>Newsletter body as HTML</p>",
-        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    // Act - Part 1 - Publish the draft
+    let publish_request_body = serde_json::json!({
+        "idempotency_key": Uuid::new_v4().to_string(),
     });
-    let response = app.post_publish_newsletter(&newsletter_request_body).await;
-    assert_is_redirect_to(&response, "/admin/newsletters");
+    let response = app
+        .post_publish_newsletter(newsletter_issue_id, &publish_request_body)
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{newsletter_issue_id}"),
+    );
 
     // Act - Part 2 - Follow the redirect
-    let html_page = app.get_publish_newsletter_html().await;
-    assert!(html_page.contains("<p><i>The newsletter issue has been published!</i></p>"));
+    let html_page = app
+        .get_newsletter_issue(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(
+        "<p><i>The newsletter issue has been accepted - \
+        emails will go out shortly.</i></p>"
+    ));
     app.dispatch_all_pending_emails().await;
     // Mock verifies on Drop that we have sent the newsletter email
 }
 
 #[tokio::test]
-async fn you_must_be_logged_in_to_publish_a_newsletter() {
+async fn you_must_be_logged_in_to_create_a_draft() {
     // Arrange
     let app = spawn_app().await;
 
@@ -117,16 +100,162 @@ async fn you_must_be_logged_in_to_publish_a_newsletter() {
         "title": "Newsletter title",
         "text_content": "Newsletter body as plain text",
         "html_content": "<p>Newsletter body as HTML</p>",
-        "idempotency_key": uuid::Uuid::new_v4().to_string(),
     });
-    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    let response = app.post_newsletter_draft(&newsletter_request_body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_publish_a_newsletter() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let publish_request_body = serde_json::json!({
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    let response = app
+        .post_publish_newsletter(Uuid::new_v4(), &publish_request_body)
+        .await;
 
     // Assert
     assert_is_redirect_to(&response, "/login");
 }
 
 #[tokio::test]
-async fn newsletter_creation_is_idempotent() {
+async fn saving_a_draft_does_not_send_anything() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let newsletter_issue_id = create_newsletter_draft(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let html_page = app
+        .get_newsletter_issue(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("<p><i>The draft has been saved.</i></p>"));
+    assert!(html_page.contains("<p>Draft</p>"));
+    let html_page = app.get_newsletter_issues_html().await;
+    assert!(html_page.contains("Newsletter title</a> (draft)"));
+    // Mock verifies on Drop that we haven't sent the newsletter email
+}
+
+#[tokio::test]
+async fn editing_a_draft_keeps_every_revision() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let newsletter_issue_id = create_newsletter_draft(&app).await;
+
+    // Act
+    for title in ["Second title", "Third title"] {
+        let response = app
+            .post_edit_newsletter_draft(
+                newsletter_issue_id,
+                &serde_json::json!({
+                    "title": title,
+                    "text_content": "Newsletter body as plain text",
+                    "html_content": "<p>Newsletter body as HTML</p>",
+                }),
+            )
+            .await;
+        assert_is_redirect_to(
+            &response,
+            &format!("/admin/newsletters/{newsletter_issue_id}"),
+        );
+    }
+
+    // Assert
+    let edit_page = app
+        .get_edit_newsletter_draft(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(edit_page.contains(r#"value="Third title""#));
+    let n_revisions = sqlx::query!(
+        r#"SELECT count(*) AS "n!" FROM newsletter_issue_revisions WHERE newsletter_issue_id = $1"#,
+        newsletter_issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .n;
+    assert_eq!(n_revisions, 3);
+    for (revision, title) in [
+        (Some(1), "Newsletter title"),
+        (Some(2), "Second title"),
+        (None, "Third title"),
+    ] {
+        let preview = app
+            .get_newsletter_issue_preview(newsletter_issue_id, revision)
+            .await
+            .text()
+            .await
+            .unwrap();
+        assert!(preview.contains(&format!("<h1>{title}</h1>")));
+        assert!(preview.contains("<p>Newsletter body as HTML</p>"));
+    }
+}
+
+#[tokio::test]
+async fn published_issues_cannot_be_edited() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let newsletter_issue_id = publish_newsletter(&app).await;
+
+    // Act
+    let response = app
+        .post_edit_newsletter_draft(
+            newsletter_issue_id,
+            &serde_json::json!({
+                "title": "Sneaky edit",
+                "text_content": "Newsletter body as plain text",
+                "html_content": "<p>Newsletter body as HTML</p>",
+            }),
+        )
+        .await;
+
+    // Assert
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{newsletter_issue_id}"),
+    );
+    let html_page = app
+        .get_newsletter_issue(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("<p><i>Published issues can no longer be edited.</i></p>"));
+    assert!(html_page.contains("<h1>Newsletter title</h1>"));
+    assert_eq!(
+        app.get_edit_newsletter_draft(newsletter_issue_id)
+            .await
+            .status()
+            .as_u16(),
+        404
+    );
+}
+
+#[tokio::test]
+async fn an_issue_is_only_published_once() {
     // Arrange
     let app = spawn_app().await;
     create_confirmed_subscriber(&app).await;
@@ -138,34 +267,109 @@ async fn newsletter_creation_is_idempotent() {
         .expect(1)
         .mount(&app.email_server)
         .await;
+    let newsletter_issue_id = publish_newsletter(&app).await;
 
-    // Act - Part 1 - Submit newsletter form
-    let newsletter_request_body = serde_json::json!({
-        "title": "Newsletter title",
-        "text_content": "Newsletter body as plain text",
-        "html_content": "<p>Newsletter body as HTML</p>",
-        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    // Act - Publish again, with a fresh idempotency key
+    let publish_request_body = serde_json::json!({
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    app.post_publish_newsletter(newsletter_issue_id, &publish_request_body)
+        .await;
+
+    // Assert
+    let html_page = app
+        .get_newsletter_issue(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("<p><i>The newsletter issue has already been published.</i></p>"));
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that we have sent the newsletter email once
+}
 
+#[tokio::test]
+async fn publishing_an_unknown_issue_returns_a_404() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let publish_request_body = serde_json::json!({
+        "idempotency_key": Uuid::new_v4().to_string(),
     });
-    let response = app.post_publish_newsletter(&newsletter_request_body).await;
-    assert_is_redirect_to(&response, "/admin/newsletters");
+    let response = app
+        .post_publish_newsletter(Uuid::new_v4(), &publish_request_body)
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn newsletter_publishing_is_idempotent() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let newsletter_issue_id = create_newsletter_draft(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Publish the draft
+    let publish_request_body = serde_json::json!({
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    let response = app
+        .post_publish_newsletter(newsletter_issue_id, &publish_request_body)
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{newsletter_issue_id}"),
+    );
 
     // Act - Part 2 - Follow the redirect
-    let html_page = app.get_publish_newsletter_html().await;
+    let html_page = app
+        .get_newsletter_issue(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
     assert!(html_page.contains(
         "<p><i>The newsletter issue has been accepted - \
-        emails will go out shortly.!</i></p>"
+        emails will go out shortly.</i></p>"
     ));
 
-    // Act - Part 3 - Submit newsletter form again
-    let response = app.post_publish_newsletter(&newsletter_request_body).await;
-    assert_is_redirect_to(&response, "/admin/newsletters");
+    // Act - Part 3 - Submit the publish form again
+    let response = app
+        .post_publish_newsletter(newsletter_issue_id, &publish_request_body)
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{newsletter_issue_id}"),
+    );
 
     // Act - Part 4 - Follow the redirect
-    let html_page = app.get_publish_newsletter_html().await;
+    let html_page = app
+        .get_newsletter_issue(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
     assert!(html_page.contains(
         "<p><i>The newsletter issue has been accepted - \
-        emails will go out shortly.!</i></p>"
+        emails will go out shortly.</i></p>"
     ));
     app.dispatch_all_pending_emails().await;
 }
@@ -176,6 +380,7 @@ async fn concurrent_form_submissions_is_handled_gracefully() {
     let app = spawn_app().await;
     create_confirmed_subscriber(&app).await;
     app.test_user.login(&app).await;
+    let newsletter_issue_id = create_newsletter_draft(&app).await;
 
     Mock::given(path("/email"))
         .and(method("POST"))
@@ -184,15 +389,12 @@ async fn concurrent_form_submissions_is_handled_gracefully() {
         .mount(&app.email_server)
         .await;
 
-    // Act - Submit two newsletter forms concurrently
-    let newsletter_request_body = serde_json::json!({
-        "title": "Newsletter title",
-        "text_content": "Newsletter body as plain text",
-        "html_content": "<p>Newsletter body as HTML</p>",
-        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    // Act - Submit two publish forms concurrently
+    let publish_request_body = serde_json::json!({
+        "idempotency_key": Uuid::new_v4().to_string(),
     });
-    let response1 = app.post_publish_newsletter(&newsletter_request_body);
-    let response2 = app.post_publish_newsletter(&newsletter_request_body);
+    let response1 = app.post_publish_newsletter(newsletter_issue_id, &publish_request_body);
+    let response2 = app.post_publish_newsletter(newsletter_issue_id, &publish_request_body);
     let (response1, response2) = tokio::join!(response1, response2);
 
     assert_eq!(response1.status(), response2.status());
@@ -200,6 +402,7 @@ async fn concurrent_form_submissions_is_handled_gracefully() {
         response1.text().await.unwrap(),
         response2.text().await.unwrap()
     );
+    app.dispatch_all_pending_emails().await;
 }
 
 fn when_sending_an_email() -> MockBuilder {
@@ -210,41 +413,331 @@ fn when_sending_an_email() -> MockBuilder {
 async fn transient_errors_do_not_cause_duplicate_deliveries_on_retries() {
     // Arrange
     let app = spawn_app().await;
-    let newsletter_request_body = serde_json::json!({
-        "title": "Newsletter title",
-        "text_content": "Newsletter body as plain text",
-        "html_content": "<p>Newsletter body as HTML</p>",
-        "idempotency_key": uuid::Uuid::new_v4().to_string(),
-    });
     create_confirmed_subscriber(&app).await;
     create_confirmed_subscriber(&app).await;
     app.test_user.login(&app).await;
 
-    // Act - Part 1 - Submit newsletter form
+    // Act - Part 1 - Deliver, one of the two emails fails
     when_sending_an_email()
         .respond_with(ResponseTemplate::new(200))
-        .up_to_n_times(2)
+        .up_to_n_times(1)
         .expect(1)
         .mount(&app.email_server)
         .await;
     when_sending_an_email()
         .respond_with(ResponseTemplate::new(500))
-        .up_to_n_times(2)
+        .up_to_n_times(1)
         .expect(1)
         .mount(&app.email_server)
         .await;
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
 
-    let response = app.post_publish_newsletter(&newsletter_request_body).await;
-    assert_eq!(response.status().as_u16(), 500);
-
-    // Act - Part 2 - Retry submitting the form
+    // Act - Part 2 - The retry is due, only the failed email goes out again
     when_sending_an_email()
         .respond_with(ResponseTemplate::new(200))
-        .up_to_n_times(2)
         .expect(1)
         .named("Delivery retry")
         .mount(&app.email_server)
         .await;
-    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    sqlx::query!("UPDATE issue_delivery_queue SET execute_after = now()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn the_delivery_log_records_the_provider_message_id() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817"
+        })))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Publish
+    publish_newsletter(&app).await;
+
+    // Assert
+    let saved = sqlx::query!("SELECT status, provider_message_id FROM issue_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "queued");
+    assert_eq!(saved.provider_message_id, None);
+
+    // Act - Part 2 - Deliver
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let saved = sqlx::query!("SELECT status, provider_message_id FROM issue_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "sent");
+    assert_eq!(
+        saved.provider_message_id.as_deref(),
+        Some("b7bc2f4a-e38e-4336-af7d-e6c392c2f817")
+    );
+}
+
+#[tokio::test]
+async fn the_issue_page_shows_the_delivery_progress() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let newsletter_issue_id = publish_newsletter(&app).await;
+
+    // Act - Part 1 - Nothing has been sent yet
+    let html_page = app
+        .get_newsletter_issue(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(html_page.contains("Delivered to 0% of 2 recipients."));
+    assert!(html_page.contains("<tr><th>Queued</th><td>2</td></tr>"));
+
+    // Act - Part 2 - Deliver to one of the two subscribers, the other one is retried later
+    app.dispatch_all_pending_emails().await;
+    let html_page = app
+        .get_newsletter_issue(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(html_page.contains("Delivered to 50% of 2 recipients."));
+    assert!(html_page.contains("<tr><th>Sent</th><td>1</td></tr>"));
+}
+
+#[tokio::test]
+async fn the_issue_page_returns_404_for_an_unknown_issue() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app.get_newsletter_issue(Uuid::new_v4()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_issue_page() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_newsletter_issue(Uuid::new_v4()).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn both_bodies_are_rendered_from_markdown() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app
+        .post_newsletter_draft(&serde_json::json!({
+            "title": "Newsletter title",
+            "markdown_content": "Hello *world*, read [the post](https://example.com/post).",
+            "text_content": "",
+            "html_content": "",
+        }))
+        .await;
+
+    // Assert
     assert_eq!(response.status().as_u16(), 303);
+    let saved = sqlx::query!("SELECT text_content, html_content FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        saved.text_content,
+        "Hello world, read the post (https://example.com/post)."
+    );
+    assert_eq!(
+        saved.html_content,
+        "<p>Hello <em>world</em>, read <a href=\"https://example.com/post\">the post</a>.</p>\n"
+    );
+}
+
+#[tokio::test]
+async fn authors_can_override_a_rendered_body() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Override the plain-text body only
+    app.post_newsletter_draft(&serde_json::json!({
+        "title": "Newsletter title",
+        "markdown_content": "Hello *world*",
+        "text_content": "Hand-written plain text",
+        "html_content": "",
+    }))
+    .await;
+
+    // Assert
+    let saved = sqlx::query!(
+        "SELECT newsletter_issue_id, text_content, html_content FROM newsletter_issues"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.text_content, "Hand-written plain text");
+    assert_eq!(saved.html_content, "<p>Hello <em>world</em></p>\n");
+
+    // Act - Part 2 - The edit form only shows the override
+    let edit_page = app
+        .get_edit_newsletter_draft(saved.newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(edit_page.contains(">Hello *world*</textarea>"));
+    assert!(edit_page.contains(">Hand-written plain text</textarea>"));
+    assert!(!edit_page.contains("&lt;p&gt;Hello"));
+}
+
+#[tokio::test]
+async fn merge_tags_are_substituted_for_each_recipient() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    sqlx::query!("UPDATE subscriptions SET name = 'Tom & Jerry'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let email = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+    app.test_user.login(&app).await;
+    app.post_newsletter_draft(&serde_json::json!({
+        "title": "Hi {{name}}",
+        "text_content": "Hello {{ name }} <{{email}}>, leave: {{unsubscribe_url}}",
+        "html_content": r#"<p>Hello {{name}}</p><a href="{{unsubscribe_url}}">Leave</a>"#,
+    }))
+    .await;
+    let newsletter_issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_publish_newsletter(
+        newsletter_issue_id,
+        &serde_json::json!({ "idempotency_key": Uuid::new_v4().to_string() }),
+    )
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let unsubscribe_link = app.get_unsubscribe_link(&email_request);
+    let raw_unsubscribe_link = format!(
+        "{}/subscriptions/unsubscribe?{}",
+        app.base_url,
+        unsubscribe_link.query().unwrap()
+    );
+    assert_eq!(body["Subject"], "Hi Tom & Jerry");
+    assert!(body["TextBody"].as_str().unwrap().starts_with(&format!(
+        "Hello Tom & Jerry <{email}>, leave: {raw_unsubscribe_link}"
+    )));
+    assert!(body["HtmlBody"].as_str().unwrap().starts_with(&format!(
+        r#"<p>Hello Tom &amp; Jerry</p><a href="{}">Leave</a>"#,
+        raw_unsubscribe_link.replace('&', "&amp;")
+    )));
+}
+
+#[tokio::test]
+async fn unknown_merge_tags_are_rejected_at_publish_time() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    app.post_newsletter_draft(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Hello {{first_name}}",
+        "html_content": "<p>Hello</p>",
+    }))
+    .await;
+    let newsletter_issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_publish_newsletter(
+            newsletter_issue_id,
+            &serde_json::json!({ "idempotency_key": Uuid::new_v4().to_string() }),
+        )
+        .await;
+
+    // Assert
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{newsletter_issue_id}"),
+    );
+    let html_page = app
+        .get_newsletter_issue(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("Unknown merge tag {{first_name}}"));
+    assert!(html_page.contains("<p>Draft</p>"));
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that we haven't sent anything
 }
@@ -0,0 +1,397 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, publish_newsletter, spawn_app, TestApp,
+};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::personal_data::email_hash;
+
+/// Deliver an issue to the only confirmed subscriber and return the email
+/// they received it with.
+async fn deliver_a_newsletter(app: &TestApp) -> wiremock::Request {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    publish_newsletter(app).await;
+    app.dispatch_all_pending_emails().await;
+    app.email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap()
+}
+
+async fn subscriber_email(app: &TestApp) -> String {
+    sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email
+}
+
+/// The audit log entries recorded for `email`, as `(action, requested_by)`.
+async fn audit_entries(app: &TestApp, email: &str) -> Vec<(String, String)> {
+    sqlx::query!(
+        r#"
+        SELECT action, requested_by FROM personal_data_audit_log
+        WHERE email_hash = $1
+        ORDER BY created_at
+        "#,
+        email_hash(email)
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|e| (e.action, e.requested_by))
+    .collect()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_handle_personal_data_requests() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let export = app.get_personal_data_export("ursula@example.com").await;
+    let erasure = app.post_personal_data_erasure("ursula@example.com").await;
+
+    // Assert
+    assert_is_redirect_to(&export, "/login");
+    assert_is_redirect_to(&erasure, "/login");
+}
+
+#[tokio::test]
+async fn admins_can_export_everything_held_on_an_address() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    deliver_a_newsletter(&app).await;
+    let email = subscriber_email(&app).await;
+
+    // Act
+    let response = app.get_personal_data_export(&email.to_uppercase()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("Content-Disposition").unwrap(),
+        r#"attachment; filename="personal-data.json""#
+    );
+    let document: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(document["subscriber"]["email"], email.as_str());
+    assert_eq!(document["subscriber"]["status"], "confirmed");
+    assert_eq!(document["list_memberships"][0]["status"], "confirmed");
+    assert_eq!(document["subscription_tokens"].as_array().unwrap().len(), 1);
+    assert_eq!(document["deliveries"][0]["title"], "Newsletter title");
+    assert_eq!(
+        audit_entries(&app, &email).await,
+        vec![("export".to_owned(), app.test_user.username.clone())]
+    );
+}
+
+#[tokio::test]
+async fn exporting_an_unknown_address_says_nothing_is_held() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Export
+    let response = app.get_personal_data_export("nobody@example.com").await;
+    assert_is_redirect_to(&response, "/admin/personal_data");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_personal_data_html().await;
+    assert!(html_page.contains("<p><i>We hold no personal data on that address.</i></p>"));
+}
+
+#[tokio::test]
+async fn admins_can_erase_everything_held_on_an_address() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    deliver_a_newsletter(&app).await;
+    let email = subscriber_email(&app).await;
+
+    // Act - Part 1 - Erase
+    let response = app.post_personal_data_erasure(&email).await;
+    assert_is_redirect_to(&response, "/admin/personal_data");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_personal_data_html().await;
+    assert!(html_page.contains("<p><i>All personal data on that address has been erased.</i></p>"));
+
+    // Assert
+    let remaining = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT count(*) FROM subscriptions) AS "subscriptions!",
+            (SELECT count(*) FROM subscription_tokens) AS "tokens!",
+            (SELECT count(*) FROM list_memberships) AS "memberships!",
+            (SELECT count(*) FROM issue_delivery_queue) AS "queue!"
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(remaining.subscriptions, 0);
+    assert_eq!(remaining.tokens, 0);
+    assert_eq!(remaining.memberships, 0);
+    assert_eq!(remaining.queue, 0);
+    // The issue still counts its delivery, to nobody in particular.
+    let deliveries = sqlx::query!("SELECT subscriber_email, status FROM issue_deliveries")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert!(deliveries[0].subscriber_email.starts_with("erased:"));
+    assert_eq!(deliveries[0].status, "sent");
+    // The address is kept on the suppression list, as a hash only, so that it
+    // is never emailed again.
+    let suppression = sqlx::query!("SELECT email, email_hash, reason, source FROM suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(suppression.email, None);
+    assert_eq!(suppression.email_hash, email_hash(&email));
+    assert_eq!(suppression.reason, "do_not_contact");
+    assert_eq!(suppression.source, app.test_user.username);
+    assert_eq!(
+        audit_entries(&app, &email).await,
+        vec![("erasure".to_owned(), app.test_user.username.clone())]
+    );
+}
+
+#[tokio::test]
+async fn no_table_holds_an_erased_address() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    deliver_a_newsletter(&app).await;
+    let email = subscriber_email(&app).await;
+    // A suppression that predates the erasure holds the address too.
+    app.post_suppression(&email, "hard_bounce").await;
+
+    // Act
+    app.post_personal_data_erasure(&email.to_uppercase()).await;
+
+    // Assert
+    let tables = sqlx::query!(
+        r#"
+        SELECT table_name AS "table_name!"
+        FROM information_schema.tables
+        WHERE table_schema = 'public' AND table_type = 'BASE TABLE'
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(!tables.is_empty());
+    for table in tables {
+        let n_rows: i64 = sqlx::query_scalar(&format!(
+            r#"SELECT count(*) FROM "{0}" t WHERE strpos(lower(t::text), lower($1)) > 0"#,
+            table.table_name
+        ))
+        .bind(&email)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+        assert_eq!(n_rows, 0, "{} still holds the address", table.table_name);
+    }
+    assert!(app
+        .get_suppressions_html()
+        .await
+        .contains("<i>Erased address</i>"));
+}
+
+/// Ask for a personal data link from the preference center of the only
+/// confirmed subscriber and return the email it was sent in.
+async fn request_personal_data_link(
+    app: &TestApp,
+    preferences_link: &reqwest::Url,
+) -> wiremock::Request {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let mut request_link = preferences_link.clone();
+    request_link.set_path("/subscriptions/personal_data/request");
+    let response = app.api_client.post(request_link).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 303);
+    app.email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn subscribers_can_download_and_erase_their_data_from_the_preference_center() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let email_request = deliver_a_newsletter(&app).await;
+    let preferences_link = app.get_preferences_link(&email_request);
+    let email = subscriber_email(&app).await;
+    let html_page = app
+        .api_client
+        .get(preferences_link.clone())
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("Email me a link to download or delete my data"));
+
+    // Act - Part 1 - Ask for a link
+    let email_request = request_personal_data_link(&app, &preferences_link).await;
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], email.as_str());
+    let personal_data_link = app.get_personal_data_link(&email_request);
+    let html_page = app
+        .api_client
+        .get(personal_data_link.clone())
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("Download your data"));
+
+    // Act - Part 2 - Download, once asked for
+    let mut download_link = personal_data_link.clone();
+    download_link.set_path("/subscriptions/personal_data/export");
+    let response = app
+        .api_client
+        .get(download_link.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("Do you want to download the data we hold on you?"));
+    assert!(audit_entries(&app, &email).await.is_empty());
+    let response = app.api_client.post(download_link).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let document: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(document["subscriber"]["email"], email.as_str());
+
+    // Act - Part 3 - Erase
+    let mut erase_link = personal_data_link;
+    erase_link.set_path("/subscriptions/personal_data/erase");
+    let response = app.api_client.post(erase_link).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("All the data we held on you has been erased."));
+
+    // Assert
+    let n_subscribers = sqlx::query!(r#"SELECT count(*) AS "n!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_subscribers, 0);
+    let suppression = sqlx::query!(
+        "SELECT reason, source FROM suppressions WHERE email_hash = $1",
+        email_hash(&email)
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(suppression.reason, "do_not_contact");
+    assert_eq!(suppression.source, "subscriber");
+    assert_eq!(
+        audit_entries(&app, &email).await,
+        vec![
+            ("export".to_owned(), "subscriber".to_owned()),
+            ("erasure".to_owned(), "subscriber".to_owned())
+        ]
+    );
+}
+
+#[tokio::test]
+async fn newsletter_links_do_not_give_access_to_personal_data() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let email_request = deliver_a_newsletter(&app).await;
+    let preferences_link = app.get_preferences_link(&email_request);
+    let unsubscribe_link = app.get_unsubscribe_link(&email_request);
+
+    for link in [preferences_link, unsubscribe_link] {
+        let mut download_link = link.clone();
+        download_link.set_path("/subscriptions/personal_data/export");
+        let mut erase_link = link;
+        erase_link.set_path("/subscriptions/personal_data/erase");
+
+        // Act
+        let download_form = app
+            .api_client
+            .get(download_link.clone())
+            .send()
+            .await
+            .unwrap();
+        let download = app.api_client.post(download_link).send().await.unwrap();
+        let erasure = app.api_client.post(erase_link).send().await.unwrap();
+
+        // Assert
+        assert_eq!(download_form.status().as_u16(), 401);
+        assert_eq!(download.status().as_u16(), 401);
+        assert_eq!(erasure.status().as_u16(), 401);
+    }
+    let n_subscribers = sqlx::query!(r#"SELECT count(*) AS "n!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_subscribers, 1);
+}
+
+#[tokio::test]
+async fn personal_data_links_with_a_tampered_token_are_rejected_with_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+    let query = format!("subscriber_id={}&token=deadbeef", Uuid::new_v4());
+
+    for (http_method, path) in [
+        (reqwest::Method::GET, "/subscriptions/personal_data"),
+        (
+            reqwest::Method::POST,
+            "/subscriptions/personal_data/request",
+        ),
+        (reqwest::Method::GET, "/subscriptions/personal_data/export"),
+        (reqwest::Method::POST, "/subscriptions/personal_data/export"),
+        (reqwest::Method::POST, "/subscriptions/personal_data/erase"),
+    ] {
+        // Act
+        let response = app
+            .api_client
+            .request(http_method, format!("{}{}?{}", app.address, path, query))
+            .send()
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 401, "{}", path);
+    }
+}
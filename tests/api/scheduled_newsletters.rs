@@ -0,0 +1,231 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_newsletter_draft, spawn_app, TestApp,
+};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Schedule an issue one day from now and return its id.
+async fn schedule_newsletter_for_tomorrow(app: &TestApp) -> Uuid {
+    let newsletter_issue_id = create_newsletter_draft(app).await;
+    let send_at = Utc::now() + Duration::days(1);
+    let response = app
+        .post_publish_newsletter(
+            newsletter_issue_id,
+            &serde_json::json!({
+                "idempotency_key": Uuid::new_v4().to_string(),
+                "send_at": send_at.to_rfc3339(),
+            }),
+        )
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{newsletter_issue_id}"),
+    );
+    newsletter_issue_id
+}
+
+async fn n_queued_tasks(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT count(*) AS "n!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n
+}
+
+#[tokio::test]
+async fn scheduled_issues_are_not_delivered_before_their_send_time() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let newsletter_issue_id = schedule_newsletter_for_tomorrow(&app).await;
+    let html_page = app
+        .get_newsletter_issue(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert!(html_page.contains("The newsletter issue has been scheduled"));
+    assert_eq!(n_queued_tasks(&app).await, 1);
+    // Mock verifies on Drop that we haven't sent the newsletter email
+}
+
+#[tokio::test]
+async fn scheduled_issues_are_delivered_once_their_send_time_has_come() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    schedule_newsletter_for_tomorrow(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Let a day go by
+    sqlx::query!("UPDATE newsletter_issues SET send_at = send_at - interval '1 day'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_eq!(n_queued_tasks(&app).await, 0);
+    // Mock verifies on Drop that we have sent the newsletter email
+}
+
+#[tokio::test]
+async fn a_scheduled_issue_can_be_rescheduled() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let newsletter_issue_id = schedule_newsletter_for_tomorrow(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Bring the issue forward to right now
+    let response = app
+        .post_reschedule_newsletter_issue(
+            newsletter_issue_id,
+            &serde_json::json!({ "send_at": Utc::now().to_rfc3339() }),
+        )
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{newsletter_issue_id}"),
+    );
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app
+        .get_newsletter_issue(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("The newsletter issue has been rescheduled"));
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that we have sent the newsletter email
+}
+
+#[tokio::test]
+async fn a_scheduled_issue_can_be_cancelled() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let newsletter_issue_id = schedule_newsletter_for_tomorrow(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Cancel
+    let response = app.post_cancel_newsletter_issue(newsletter_issue_id).await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{newsletter_issue_id}"),
+    );
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app
+        .get_newsletter_issue(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("<p><i>The newsletter issue has been cancelled.</i></p>"));
+    assert!(html_page.contains("Cancelled at"));
+
+    // Act - Part 3 - Even once the send time has passed, nothing goes out
+    sqlx::query!("UPDATE newsletter_issues SET send_at = send_at - interval '1 day'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(n_queued_tasks(&app).await, 0);
+    // Mock verifies on Drop that we haven't sent the newsletter email
+}
+
+#[tokio::test]
+async fn an_issue_that_has_gone_out_cannot_be_cancelled() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let newsletter_issue_id = schedule_newsletter_for_tomorrow(&app).await;
+    sqlx::query!("UPDATE newsletter_issues SET send_at = now()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    app.post_cancel_newsletter_issue(newsletter_issue_id).await;
+
+    // Assert
+    let html_page = app
+        .get_newsletter_issue(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("has already gone out or has been cancelled"));
+    assert_eq!(n_queued_tasks(&app).await, 1);
+}
+
+#[tokio::test]
+async fn an_invalid_send_time_is_rejected_with_a_400() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let newsletter_issue_id = create_newsletter_draft(&app).await;
+
+    // Act
+    let response = app
+        .post_publish_newsletter(
+            newsletter_issue_id,
+            &serde_json::json!({
+                "idempotency_key": Uuid::new_v4().to_string(),
+                "send_at": "next tuesday",
+            }),
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_cancel_an_issue() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_cancel_newsletter_issue(Uuid::new_v4()).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
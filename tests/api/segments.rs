@@ -0,0 +1,315 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_newsletter_draft, default_list_id, spawn_app, TestApp,
+};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Subscribe and confirm `email`, then tag it through the admin area.
+/// Expects the test user to be logged in.
+async fn create_tagged_subscriber(
+    app: &TestApp,
+    email: &str,
+    tags: &str,
+    attributes: serde_json::Value,
+) -> Uuid {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let body = serde_urlencoded::to_string([("name", "le guin"), ("email", email)]).unwrap();
+    app.post_subscriptions(body)
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    reqwest::get(app.get_confirmation_links(&email_request).html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions WHERE email = $1", email)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    let response = app
+        .post_subscriber(
+            subscriber_id,
+            &serde_json::json!({
+                "tags": tags,
+                "attributes": attributes.to_string(),
+            }),
+        )
+        .await;
+    assert_is_redirect_to(&response, &format!("/admin/subscribers/{subscriber_id}"));
+    subscriber_id
+}
+
+/// Three subscribers to the default list, with various tags and attributes.
+async fn create_audience(app: &TestApp) {
+    create_tagged_subscriber(
+        app,
+        "beta_pro@example.com",
+        "beta, region:eu",
+        serde_json::json!({ "plan": "pro", "seats": 12 }),
+    )
+    .await;
+    create_tagged_subscriber(
+        app,
+        "beta_free@example.com",
+        "beta",
+        serde_json::json!({ "plan": "free", "seats": 1 }),
+    )
+    .await;
+    create_tagged_subscriber(app, "untagged@example.com", "", serde_json::json!({})).await;
+}
+
+#[tokio::test]
+async fn admins_can_tag_subscribers_and_set_attributes() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let subscriber_id = create_tagged_subscriber(
+        &app,
+        "ursula_le_guin@gmail.com",
+        " beta, region:eu, ,beta",
+        serde_json::json!({ "plan": "pro" }),
+    )
+    .await;
+
+    // Assert
+    let saved =
+        sqlx::query!(r#"SELECT tags, attributes::text AS "attributes!" FROM subscriptions"#)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(saved.tags, ["beta", "region:eu"]);
+    assert_eq!(saved.attributes, r#"{"plan": "pro"}"#);
+    let html_page = app.get_subscriber_html(subscriber_id).await;
+    assert!(html_page.contains("<p><i>The subscriber has been updated.</i></p>"));
+    assert!(html_page.contains(r#"value="beta, region:eu""#));
+    let html_page = app.get_subscribers_html("region:eu").await;
+    assert!(html_page.contains("ursula_le_guin@gmail.com"));
+    let html_page = app.get_subscribers_html("alpha").await;
+    assert!(!html_page.contains("ursula_le_guin@gmail.com"));
+}
+
+#[tokio::test]
+async fn invalid_tags_or_attributes_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscriber_id = create_tagged_subscriber(
+        &app,
+        "ursula_le_guin@gmail.com",
+        "beta",
+        serde_json::json!({}),
+    )
+    .await;
+    let test_cases = vec![
+        ("early adopter", "{}", "The tag `early adopter` can"),
+        ("beta", "[1, 2]", "The attributes must be a JSON object."),
+        ("beta", r#"{"a=b": 1}"#, "The attribute `a=b` can"),
+    ];
+
+    for (tags, attributes, error_message) in test_cases {
+        // Act
+        let response = app
+            .post_subscriber(
+                subscriber_id,
+                &serde_json::json!({ "tags": tags, "attributes": attributes }),
+            )
+            .await;
+
+        // Assert
+        assert_is_redirect_to(&response, &format!("/admin/subscribers/{subscriber_id}"));
+        let html_page = app.get_subscriber_html(subscriber_id).await;
+        assert!(
+            html_page.contains(error_message),
+            "{tags} / {attributes} should be rejected"
+        );
+    }
+    let saved = sqlx::query!("SELECT tags FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.tags, ["beta"]);
+}
+
+#[tokio::test]
+async fn the_audience_preview_counts_the_matching_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_audience(&app).await;
+    let list_id = default_list_id(&app).await;
+    let newsletter_issue_id = create_newsletter_draft(&app).await;
+    let test_cases = vec![
+        ("", "3 confirmed subscribers"),
+        ("tag:beta", "2 confirmed subscribers"),
+        (
+            r#"tag:beta and attr.plan = "pro""#,
+            "1 confirmed subscriber.",
+        ),
+        ("attr.seats > 1 or not tag:beta", "2 confirmed subscribers"),
+        (
+            "tag:region:eu and attr.plan != \"pro\"",
+            "0 confirmed subscribers",
+        ),
+        ("attr.plan > 1", "0 confirmed subscribers"),
+        (
+            "tag:beta and",
+            "The segment ends where a condition was expected.",
+        ),
+    ];
+
+    for (segment, expected) in test_cases {
+        // Act
+        let html_page = app
+            .get_audience_preview_html(newsletter_issue_id, list_id, segment)
+            .await;
+
+        // Assert
+        assert!(
+            html_page.contains(expected),
+            "{segment} should show {expected}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn a_negated_equality_matches_subscribers_without_the_attribute() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_audience(&app).await;
+    let list_id = default_list_id(&app).await;
+    let newsletter_issue_id = create_newsletter_draft(&app).await;
+
+    for segment in [r#"not attr.plan = "pro""#, r#"attr.plan != "pro""#] {
+        // Act
+        let html_page = app
+            .get_audience_preview_html(newsletter_issue_id, list_id, segment)
+            .await;
+
+        // Assert
+        // `untagged@example.com` has no plan at all, which is not "pro".
+        assert!(
+            html_page.contains("2 confirmed subscribers"),
+            "{segment} should match 2 subscribers"
+        );
+    }
+}
+
+#[tokio::test]
+async fn issues_published_to_a_segment_only_reach_its_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_audience(&app).await;
+    let newsletter_issue_id = create_newsletter_draft(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_publish_newsletter(
+            newsletter_issue_id,
+            &serde_json::json!({
+                "idempotency_key": Uuid::new_v4().to_string(),
+                "segment": r#"tag:beta and attr.seats >= 10"#,
+            }),
+        )
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{newsletter_issue_id}"),
+    );
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "beta_pro@example.com");
+    let html_page = app
+        .get_newsletter_issue(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("segment: <code>tag:beta and attr.seats &gt;= 10</code>"));
+    // Mock verifies on Drop that only one newsletter email went out
+}
+
+#[tokio::test]
+async fn an_invalid_segment_does_not_publish_the_issue() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let newsletter_issue_id = create_newsletter_draft(&app).await;
+
+    // Act
+    let response = app
+        .post_publish_newsletter(
+            newsletter_issue_id,
+            &serde_json::json!({
+                "idempotency_key": Uuid::new_v4().to_string(),
+                "segment": "beta",
+            }),
+        )
+        .await;
+
+    // Assert
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{newsletter_issue_id}"),
+    );
+    let html_page = app
+        .get_newsletter_issue(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("Expected a `tag:&lt;tag&gt;` or `attr.&lt;key&gt;` condition"));
+    assert!(html_page.contains("<p>Draft</p>"));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/admin/subscribers", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,129 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_newsletter_draft, spawn_app,
+};
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_send_a_test_email() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_send_test_newsletter(Uuid::new_v4()).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_test_email_is_only_sent_to_the_admin() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let newsletter_issue_id = create_newsletter_draft(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Send a test email
+    let response = app.post_send_test_newsletter(newsletter_issue_id).await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{newsletter_issue_id}"),
+    );
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app
+        .get_newsletter_issue(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(&format!(
+        "<p><i>A test email has been sent to {}.</i></p>",
+        app.test_user.email
+    )));
+
+    // Assert
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], app.test_user.email.as_str());
+    assert_eq!(body["Subject"], "Newsletter title");
+    assert!(body["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .starts_with("<p>Newsletter body as HTML</p>"));
+    assert!(body["TextBody"].as_str().unwrap().contains("Unsubscribe: "));
+
+    // The issue is still a draft, nothing was queued for subscribers
+    let n_queued = sqlx::query!(r#"SELECT count(*) AS "n!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_queued, 0);
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that only the test email went out
+}
+
+#[tokio::test]
+async fn a_test_email_needs_an_admin_email_address() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let newsletter_issue_id = create_newsletter_draft(&app).await;
+    sqlx::query!("UPDATE users SET email = NULL")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_send_test_newsletter(newsletter_issue_id).await;
+
+    // Assert
+    let html_page = app
+        .get_newsletter_issue(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("<p><i>Set your email address before sending a test email.</i></p>"));
+}
+
+#[tokio::test]
+async fn sending_a_test_email_of_an_unknown_issue_returns_a_404() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_send_test_newsletter(Uuid::new_v4()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -0,0 +1,207 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_mailing_list,
+    create_unconfirmed_subscriber, spawn_app, TestApp,
+};
+
+/// Insert `n` confirmed subscribers who signed up on `day`, straight into the database.
+async fn insert_subscribers(app: &TestApp, n: i32, prefix: &str, day: &str) {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        SELECT gen_random_uuid(), $2 || i || '@example.com', 'Subscriber ' || i, $3::date, 'confirmed'
+        FROM generate_series(1, $1) AS i
+        "#,
+        n,
+        prefix,
+        day as &str
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn subscribers_are_exported_as_csv() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscribers(&app, 1, "tom,\"the cat\"", "2026-01-15").await;
+
+    // Act
+    let response = app.get_subscriber_export(&[]).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("Content-Type").unwrap(),
+        "text/csv; charset=utf-8"
+    );
+    assert_eq!(
+        response.headers().get("Content-Disposition").unwrap(),
+        r#"attachment; filename="subscribers.csv""#
+    );
+    let body = response.text().await.unwrap();
+    let mut lines = body.lines();
+    assert_eq!(lines.next(), Some("id,email,name,status,subscribed_at"));
+    let row = lines.next().unwrap();
+    assert!(row.contains(
+        r#","tom,""the cat""1@example.com",Subscriber 1,confirmed,2026-01-15T00:00:00+00:00"#
+    ));
+    assert_eq!(lines.next(), None);
+}
+
+#[tokio::test]
+async fn csv_cells_are_not_read_as_spreadsheet_formulas() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES (gen_random_uuid(), '-1+1@example.com', '=HYPERLINK("https://evil.example")', now(), 'confirmed')
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    let body = app.get_subscriber_export(&[]).await.text().await.unwrap();
+
+    // Assert
+    assert!(
+        body.contains(r#",'-1+1@example.com,"'=HYPERLINK(""https://evil.example"")",confirmed,"#)
+    );
+}
+
+#[tokio::test]
+async fn subscribers_are_exported_as_ndjson() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscribers(&app, 2, "ndjson", "2026-01-15").await;
+
+    // Act
+    let response = app.get_subscriber_export(&[("format", "ndjson")]).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("Content-Type").unwrap(),
+        "application/x-ndjson"
+    );
+    let body = response.text().await.unwrap();
+    let mut rows: Vec<serde_json::Value> = body
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(rows.len(), 2);
+    // Both signed up at the same time, in no particular order.
+    rows.sort_by_key(|r| r["email"].as_str().unwrap().to_owned());
+    assert_eq!(rows[0]["email"], "ndjson1@example.com");
+    assert_eq!(rows[0]["status"], "confirmed");
+    assert_eq!(rows[0]["subscribed_at"], "2026-01-15T00:00:00+00:00");
+}
+
+#[tokio::test]
+async fn exports_can_be_filtered() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscribers(&app, 2, "january", "2026-01-15").await;
+    insert_subscribers(&app, 3, "march", "2026-03-01").await;
+    create_confirmed_subscriber(&app).await;
+    create_unconfirmed_subscriber(&app).await;
+    let list_id = create_mailing_list(&app, "Release notes").await;
+    sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (subscriber_id, list_id, status)
+        SELECT id, $1, 'unsubscribed' FROM subscriptions WHERE email LIKE 'march%'
+        "#,
+        list_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let list_id = list_id.to_string();
+    let test_cases = vec![
+        (vec![], 7),
+        (vec![("status", "pending_confirmation")], 1),
+        (vec![("status", "confirmed")], 6),
+        (vec![("subscribed_until", "2026-01-15")], 2),
+        (vec![("subscribed_from", "2026-01-16")], 5),
+        (
+            vec![
+                ("subscribed_from", "2026-01-01"),
+                ("subscribed_until", "2026-03-01"),
+            ],
+            5,
+        ),
+        (vec![("list_id", list_id.as_str())], 3),
+        (
+            vec![("list_id", list_id.as_str()), ("status", "confirmed")],
+            0,
+        ),
+    ];
+
+    for (query, n_rows) in test_cases {
+        // Act
+        let response = app
+            .get_subscriber_export(&[query.as_slice(), &[("format", "ndjson")]].concat())
+            .await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 200);
+        let body = response.text().await.unwrap();
+        assert_eq!(body.lines().count(), n_rows, "{query:?}");
+    }
+}
+
+#[tokio::test]
+async fn large_exports_are_streamed_in_full() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscribers(&app, 5000, "bulk", "2026-01-15").await;
+
+    // Act
+    let response = app.get_subscriber_export(&[]).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers().get("Content-Length").is_none());
+    let body = response.text().await.unwrap();
+    assert_eq!(body.lines().count(), 5001);
+}
+
+#[tokio::test]
+async fn invalid_export_filters_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let test_cases = vec![
+        ("format", "xml"),
+        ("subscribed_from", "15/01/2026"),
+        ("list_id", "not-a-list"),
+    ];
+
+    for (key, value) in test_cases {
+        // Act
+        let response = app.get_subscriber_export(&[(key, value)]).await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 400, "{key}={value}");
+    }
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_export_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_subscriber_export(&[]).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,309 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_mailing_list, default_list_id,
+    spawn_app, TestApp,
+};
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Upload `csv` and return the location of the progress page.
+async fn start_import(app: &TestApp, csv: &str, mode: &str, consent_source: &str) -> String {
+    let list_id = default_list_id(app).await;
+    let response = app
+        .post_subscriber_import(
+            csv,
+            &[
+                ("list_id", list_id.to_string()),
+                ("mode", mode.into()),
+                ("consent_source", consent_source.into()),
+            ],
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 303);
+    let location = response
+        .headers()
+        .get("Location")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(location.starts_with("/admin/subscribers/import/"));
+    location.to_owned()
+}
+
+#[tokio::test]
+async fn subscribers_can_be_imported_as_confirmed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let csv = "Name,Email,Plan\n\
+        Ursula Le Guin,ursula_le_guin@gmail.com,pro\n\
+        Not An Email,definitely-not-an-email,free\n\
+        ,nameless@gmail.com,free\n";
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Upload the file
+    let location = start_import(&app, csv, "confirmed", "Signed up at the 2026 conference").await;
+    let html_page = app.get_subscriber_import_html(&location).await;
+    assert!(html_page.contains("<p><i>The import has started.</i></p>"));
+    assert!(html_page.contains("Processed 0% of 3 rows."));
+    assert!(html_page.contains(r#"<meta http-equiv="refresh""#));
+
+    // Act - Part 2 - Let the worker go through the rows
+    app.import_all_pending_rows().await;
+
+    // Assert
+    let saved = sqlx::query!(
+        r#"
+        SELECT s.email, s.name, s.status, s.consent_source, m.status AS list_status
+        FROM subscriptions s
+        JOIN list_memberships m ON m.subscriber_id = s.id
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved[0].name, "Ursula Le Guin");
+    assert_eq!(saved[0].status, "confirmed");
+    assert_eq!(saved[0].list_status, "confirmed");
+    assert_eq!(
+        saved[0].consent_source.as_deref(),
+        Some("Signed up at the 2026 conference")
+    );
+
+    let html_page = app.get_subscriber_import_html(&location).await;
+    assert!(html_page.contains("Processed 100% of 3 rows."));
+    assert!(!html_page.contains(r#"<meta http-equiv="refresh""#));
+    assert!(html_page.contains(
+        "<tr><td>3</td><td>definitely-not-an-email</td><td>invalid</td>\
+        <td>definitely-not-an-email is not a valid subscriber email.</td></tr>"
+    ));
+    assert!(html_page.contains(
+        "<tr><td>4</td><td>nameless@gmail.com</td><td>invalid</td>\
+        <td> is not a valid subscriber name.</td></tr>"
+    ));
+}
+
+#[tokio::test]
+async fn imported_subscribers_can_be_asked_to_confirm() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let csv = "email,name\n\
+        ursula_le_guin@gmail.com,Ursula Le Guin\n\
+        ursula_le_guin@gmail.com,Ursula Le Guin\n\
+        octavia_butler@gmail.com,Octavia Butler\n";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Import the file
+    let location = start_import(&app, csv, "confirmation_email", "").await;
+    app.import_all_pending_rows().await;
+
+    // Assert
+    let saved = sqlx::query!("SELECT status FROM list_memberships")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.len(), 2);
+    assert!(saved.iter().all(|m| m.status == "pending_confirmation"));
+    let html_page = app.get_subscriber_import_html(&location).await;
+    assert!(html_page.contains(
+        "<tr><td>3</td><td>ursula_le_guin@gmail.com</td><td>skipped</td>\
+        <td>Already asked to confirm.</td></tr>"
+    ));
+
+    // Act - Part 2 - Follow a confirmation link
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let saved = sqlx::query!("SELECT status FROM subscriptions WHERE status = 'confirmed'")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.len(), 1);
+}
+
+#[tokio::test]
+async fn imports_do_not_resubscribe_people_who_left_the_list() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let email = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+    sqlx::query!("UPDATE list_memberships SET status = 'unsubscribed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let location = start_import(
+        &app,
+        &format!("email,name\n{email},Someone\n"),
+        "confirmed",
+        "Paper form",
+    )
+    .await;
+    app.import_all_pending_rows().await;
+
+    // Assert
+    let saved = sqlx::query!("SELECT status FROM list_memberships")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+    let html_page = app.get_subscriber_import_html(&location).await;
+    assert!(html_page.contains("Unsubscribed from this list before."));
+}
+
+#[tokio::test]
+async fn imports_do_not_resubscribe_people_who_left_the_newsletter() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let email = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+    // They left every list, then a new one is created.
+    sqlx::query!("UPDATE list_memberships SET status = 'unsubscribed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    sqlx::query!("UPDATE subscriptions SET status = 'unsubscribed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let list_id = create_mailing_list(&app, "Release notes").await;
+
+    // Act
+    let response = app
+        .post_subscriber_import(
+            &format!("email,name\n{email},Someone\n"),
+            &[
+                ("list_id", list_id.to_string()),
+                ("mode", "confirmed".into()),
+                ("consent_source", "Paper form".into()),
+            ],
+        )
+        .await;
+    app.import_all_pending_rows().await;
+
+    // Assert
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+    let n_memberships = sqlx::query!(
+        r#"SELECT count(*) AS "n!" FROM list_memberships WHERE list_id = $1"#,
+        list_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .n;
+    assert_eq!(n_memberships, 0);
+    let location = response
+        .headers()
+        .get("Location")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    let html_page = app.get_subscriber_import_html(location).await;
+    assert!(html_page.contains("Unsubscribed from the newsletter before."));
+}
+
+#[tokio::test]
+async fn uploads_that_cannot_be_imported_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let list_id = default_list_id(&app).await.to_string();
+    let test_cases = vec![
+        (
+            "email,name\nursula_le_guin@gmail.com,Ursula\n",
+            "confirmed",
+            "",
+            "Importing subscribers as confirmed needs a note",
+        ),
+        (
+            "email,full_name\nursula_le_guin@gmail.com,Ursula\n",
+            "confirmation_email",
+            "",
+            "The file needs an `email` and a `name` column.",
+        ),
+        (
+            "email,name\n",
+            "confirmation_email",
+            "",
+            "The file has no rows to import.",
+        ),
+    ];
+
+    for (csv, mode, consent_source, error_message) in test_cases {
+        // Act
+        let response = app
+            .post_subscriber_import(
+                csv,
+                &[
+                    ("list_id", list_id.clone()),
+                    ("mode", mode.into()),
+                    ("consent_source", consent_source.into()),
+                ],
+            )
+            .await;
+
+        // Assert
+        assert_is_redirect_to(&response, "/admin/subscribers/import");
+        let html_page = app
+            .get_subscriber_import_html("/admin/subscribers/import")
+            .await;
+        assert!(
+            html_page.contains(error_message),
+            "{csv} should be rejected"
+        );
+    }
+    let n_imports = sqlx::query!(r#"SELECT count(*) AS "n!" FROM subscriber_imports"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_imports, 0);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_import_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_subscriber_import("email,name\n", &[("mode", "confirmed".into())])
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,75 @@
+use crate::helpers::{create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app};
+use chrono::Duration;
+use zero2prod::subscriber_purge::purge_stale_subscribers;
+
+#[tokio::test]
+async fn stale_pending_subscribers_are_purged_with_their_tokens() {
+    // Arrange
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+    sqlx::query!("UPDATE subscriptions SET subscribed_at = now() - interval '2 days'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let outcome = purge_stale_subscribers(&app.db_pool, Duration::days(1))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(outcome.n_subscribers, 1);
+    assert_eq!(outcome.n_tokens, 1);
+    let n_subscribers = sqlx::query!(r#"SELECT count(*) AS "n!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_subscribers, 0);
+}
+
+#[tokio::test]
+async fn recent_pending_subscribers_are_kept() {
+    // Arrange
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+
+    // Act
+    let outcome = purge_stale_subscribers(&app.db_pool, Duration::days(1))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(outcome.n_subscribers, 0);
+    assert_eq!(outcome.n_tokens, 0);
+}
+
+#[tokio::test]
+async fn confirmed_subscribers_are_never_purged() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    sqlx::query!("UPDATE subscriptions SET subscribed_at = now() - interval '30 days'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    sqlx::query!("UPDATE subscription_tokens SET expires_at = now() - interval '30 days'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let outcome = purge_stale_subscribers(&app.db_pool, Duration::days(1))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(outcome.n_subscribers, 0);
+    // Their long expired token goes, though.
+    assert_eq!(outcome.n_tokens, 1);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
@@ -167,3 +167,105 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     // Assert
     assert_eq!(response.status().as_u16(), 500);
 }
+
+#[tokio::test]
+async fn subscribing_twice_while_pending_resends_the_same_confirmation_link() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let first = app.post_subscriptions(body.into()).await;
+    let second = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(first.status().as_u16(), 200);
+    assert_eq!(second.status().as_u16(), 200);
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let first_links = app.get_confirmation_links(&email_requests[0]);
+    let second_links = app.get_confirmation_links(&email_requests[1]);
+    assert_eq!(first_links.html, second_links.html);
+
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].status, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn subscribing_again_once_confirmed_succeeds_without_sending_an_email() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    let mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let first = app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    drop(mock_guard);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let second = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(second.status().as_u16(), first.status().as_u16());
+    assert_eq!(second.text().await.unwrap(), first.text().await.unwrap());
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn subscribing_again_once_the_link_has_expired_sends_a_new_one() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions(body.into()).await;
+    sqlx::query!("UPDATE subscription_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    app.post_subscriptions(body.into()).await;
+
+    // Assert
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let expired_links = app.get_confirmation_links(&email_requests[0]);
+    let new_links = app.get_confirmation_links(&email_requests[1]);
+    assert_ne!(expired_links.html, new_links.html);
+    let response = reqwest::get(new_links.html).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -3,7 +3,7 @@ use wiremock::{
     Mock, ResponseTemplate,
 };
 
-use crate::helpers::spawn_app;
+use crate::helpers::{create_unconfirmed_subscriber, spawn_app};
 
 #[tokio::test]
 async fn confirmations_without_token_are_rejected_with_a_400() {
@@ -107,3 +107,89 @@ async fn clicking_on_the_confirmation_link_twice() {
 
     assert_eq!(saved.len(), 1)
 }
+
+#[tokio::test]
+async fn expired_confirmation_links_are_rejected_with_a_410() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_links = create_unconfirmed_subscriber(&app).await;
+    sqlx::query!("UPDATE subscription_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 410);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("<h1>Link expired</h1>"));
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn confirming_shows_a_success_page_then_an_already_confirmed_page() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_links = create_unconfirmed_subscriber(&app).await;
+
+    // Act - Part 1 - First click
+    let response = reqwest::get(confirmation_links.html.clone()).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("<h1>Subscription confirmed</h1>"));
+
+    // Act - Part 2 - Click again, even once the link has expired
+    sqlx::query!("UPDATE subscription_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("<h1>Already confirmed</h1>"));
+}
+
+#[tokio::test]
+async fn unknown_confirmation_tokens_are_rejected_with_an_html_401() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(format!(
+        "{}/subscriptions/confirm?subscription_token=notarealtoken",
+        app.address
+    ))
+    .await
+    .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("<h1>Invalid link</h1>"));
+}
@@ -0,0 +1,467 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_mailing_list, publish_newsletter,
+    spawn_app, TestApp,
+};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::domain::{SubscriberToken, TokenPurpose};
+
+/// Deliver an issue to the only confirmed subscriber and return the
+/// preferences link it carries.
+async fn get_preferences_link_from_a_newsletter(app: &TestApp) -> reqwest::Url {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    publish_newsletter(app).await;
+    app.dispatch_all_pending_emails().await;
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    app.get_preferences_link(&email_request)
+}
+
+fn relative(link: &reqwest::Url) -> String {
+    format!("{}?{}", link.path(), link.query().unwrap())
+}
+
+#[tokio::test]
+async fn preferences_with_a_tampered_token_are_rejected_with_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(format!(
+        "{}/subscriptions/preferences?subscriber_id={}&token=deadbeef",
+        app.address,
+        Uuid::new_v4()
+    ))
+    .await
+    .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn the_unsubscribe_link_does_not_open_the_preference_center() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let mut preferences_link = app.get_unsubscribe_link(&email_request);
+    preferences_link.set_path("/subscriptions/preferences");
+
+    // Act
+    let page = app
+        .api_client
+        .get(preferences_link.clone())
+        .send()
+        .await
+        .unwrap();
+    let update = app
+        .post_preferences(
+            preferences_link,
+            &[("name", "Mallory"), ("email", "mallory@example.com")],
+        )
+        .await;
+
+    // Assert
+    assert_eq!(page.status().as_u16(), 401);
+    assert_eq!(update.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn expired_preferences_links_are_rejected_with_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    let token = SubscriberToken::generate(
+        subscriber_id,
+        TokenPurpose::ManagePreferences,
+        Some(Utc::now() - Duration::minutes(1)),
+        &app.hmac_secret,
+    );
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/subscriptions/preferences?subscriber_id={}&token={}",
+            app.address,
+            subscriber_id,
+            token.as_ref()
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn newsletters_link_to_a_prefilled_preference_center() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    create_mailing_list(&app, "Release notes").await;
+
+    // Act
+    let preferences_link = get_preferences_link_from_a_newsletter(&app).await;
+    let response = app.api_client.get(preferences_link).send().await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    let saved = sqlx::query!("SELECT email, name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(html_page.contains(&saved.email));
+    assert!(html_page.contains("Release notes"));
+}
+
+#[tokio::test]
+async fn subscribers_can_change_their_name_and_lists() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let chosen_list_id = create_mailing_list(&app, "Release notes").await;
+    create_mailing_list(&app, "Events").await;
+    let preferences_link = get_preferences_link_from_a_newsletter(&app).await;
+    let email = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Save the form
+    let response = app
+        .post_preferences(
+            preferences_link.clone(),
+            &[
+                ("name", "Ursula"),
+                ("email", email.as_str()),
+                ("list", chosen_list_id.to_string().as_str()),
+            ],
+        )
+        .await;
+    assert_is_redirect_to(&response, &relative(&preferences_link));
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app
+        .api_client
+        .get(preferences_link)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("<p><i>Your preferences have been saved."));
+
+    // Assert
+    let saved = sqlx::query!("SELECT name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.name, "Ursula");
+    // Unticked lists, the default one included, are left, and ticked lists
+    // wait for a confirmation.
+    let memberships = sqlx::query!("SELECT list_id, status FROM list_memberships ORDER BY status")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(memberships.len(), 2);
+    assert_eq!(memberships[0].list_id, chosen_list_id);
+    assert_eq!(memberships[0].status, "pending_confirmation");
+    assert_eq!(memberships[1].status, "unsubscribed");
+
+    // Act - Part 3 - Confirm the new list
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], email);
+    let confirmation_links = app.get_confirmation_links(&email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let status = sqlx::query!(
+        "SELECT status FROM list_memberships WHERE list_id = $1",
+        chosen_list_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .status;
+    assert_eq!(status, "confirmed");
+}
+
+#[tokio::test]
+async fn lists_that_stay_ticked_stay_confirmed() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let preferences_link = get_preferences_link_from_a_newsletter(&app).await;
+    let saved = sqlx::query!(
+        r#"
+        SELECT s.email, m.list_id
+        FROM subscriptions s
+        JOIN list_memberships m ON m.subscriber_id = s.id
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_preferences(
+        preferences_link,
+        &[
+            ("name", "le guin"),
+            ("email", saved.email.as_str()),
+            ("list", saved.list_id.to_string().as_str()),
+        ],
+    )
+    .await;
+
+    // Assert
+    let status = sqlx::query!("SELECT status FROM list_memberships")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status;
+    assert_eq!(status, "confirmed");
+    // Mock verifies on Drop that no confirmation email went out
+}
+
+#[tokio::test]
+async fn changing_the_email_address_requires_confirming_the_new_one() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let preferences_link = get_preferences_link_from_a_newsletter(&app).await;
+    let old_email = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Ask for the change
+    app.post_preferences(
+        preferences_link,
+        &[("name", "le guin"), ("email", "new_address@example.com")],
+    )
+    .await;
+
+    // Assert
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "new_address@example.com");
+    assert_eq!(body["Subject"], "Confirm your new email address");
+    assert!(!body["TextBody"].as_str().unwrap().contains("Welcome"));
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.email, old_email);
+
+    // Act - Part 2 - Confirm the new address
+    let confirmation_links = app.get_confirmation_links(&email_request);
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("<h1>Email address updated</h1>"));
+    let saved = sqlx::query!("SELECT email, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.email, "new_address@example.com");
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn changing_to_a_suppressed_address_sends_nothing_and_says_nothing() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let preferences_link = get_preferences_link_from_a_newsletter(&app).await;
+    app.post_suppression("new_address@example.com", "hard_bounce")
+        .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Ask for the change
+    app.post_preferences(
+        preferences_link.clone(),
+        &[("name", "le guin"), ("email", "NEW_address@example.com")],
+    )
+    .await;
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app
+        .api_client
+        .get(preferences_link)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(html_page.contains(
+        "<p><i>Your preferences have been saved. \
+        Please follow the link we sent to your new email address to start using it.</i></p>"
+    ));
+    let n_tokens = sqlx::query!(
+        r#"SELECT count(*) AS "n!" FROM subscription_tokens WHERE new_email IS NOT NULL"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .n;
+    assert_eq!(n_tokens, 0);
+    // Mock verifies on Drop that no confirmation email went out
+}
+
+#[tokio::test]
+async fn joining_lists_from_a_suppressed_address_sends_nothing_and_says_nothing() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let list_id = create_mailing_list(&app, "Release notes").await;
+    let preferences_link = get_preferences_link_from_a_newsletter(&app).await;
+    let email = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+    app.post_suppression(&email, "hard_bounce").await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Tick a new list
+    app.post_preferences(
+        preferences_link.clone(),
+        &[
+            ("name", "le guin"),
+            ("email", email.as_str()),
+            ("list", list_id.to_string().as_str()),
+        ],
+    )
+    .await;
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app
+        .api_client
+        .get(preferences_link)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(html_page.contains(
+        "<p><i>Your preferences have been saved. \
+        Please follow the links we sent you to confirm the lists you joined.</i></p>"
+    ));
+    let n_tokens = sqlx::query!(
+        r#"SELECT count(*) AS "n!" FROM subscription_tokens WHERE list_id = $1"#,
+        list_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .n;
+    assert_eq!(n_tokens, 0);
+    // Mock verifies on Drop that no confirmation email went out
+}
@@ -0,0 +1,161 @@
+use crate::helpers::{create_confirmed_subscriber, publish_newsletter, spawn_app, TestApp};
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Publish an issue to every confirmed subscriber and return the last
+/// email request that went out.
+async fn publish_and_get_newsletter_email(app: &TestApp) -> wiremock::Request {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    publish_newsletter(app).await;
+    app.dispatch_all_pending_emails().await;
+
+    app.email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap()
+}
+
+async fn publish_and_get_unsubscribe_link(app: &TestApp) -> reqwest::Url {
+    let email_request = publish_and_get_newsletter_email(app).await;
+    app.get_unsubscribe_link(&email_request)
+}
+
+#[tokio::test]
+async fn unsubscribe_with_a_tampered_token_is_rejected_with_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(format!(
+        "{}/subscriptions/unsubscribe?subscriber_id={}&token=deadbeef",
+        app.address,
+        Uuid::new_v4()
+    ))
+    .await
+    .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn the_unsubscribe_link_shows_a_confirmation_form() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let unsubscribe_link = publish_and_get_unsubscribe_link(&app).await;
+
+    // Act
+    let response = reqwest::get(unsubscribe_link).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(r#"method="post""#));
+
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn posting_to_the_unsubscribe_link_unsubscribes_the_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let unsubscribe_link = publish_and_get_unsubscribe_link(&app).await;
+
+    // Act
+    let response = app.post_unsubscribe(unsubscribe_link).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT status FROM list_memberships")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+}
+
+#[tokio::test]
+async fn newsletters_are_not_delivered_to_unsubscribed_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let unsubscribe_link = publish_and_get_unsubscribe_link(&app).await;
+    app.post_unsubscribe(unsubscribe_link)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Mock verifies on Drop that we haven't sent the newsletter email
+}
+
+#[tokio::test]
+async fn newsletter_emails_carry_one_click_list_unsubscribe_headers() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Deliver an issue
+    let email_request = publish_and_get_newsletter_email(&app).await;
+
+    // Assert
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let header = |name: &str| {
+        body["Headers"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|h| h["Name"] == name)
+            .map(|h| h["Value"].as_str().unwrap().to_owned())
+            .unwrap()
+    };
+    assert_eq!(
+        header("List-Unsubscribe-Post"),
+        "List-Unsubscribe=One-Click"
+    );
+    let list_unsubscribe = header("List-Unsubscribe");
+    let raw_link = list_unsubscribe
+        .strip_prefix('<')
+        .and_then(|l| l.strip_suffix('>'))
+        .unwrap();
+    let mut unsubscribe_link = reqwest::Url::parse(raw_link).unwrap();
+    unsubscribe_link.set_port(Some(app.port)).unwrap();
+    assert_eq!(unsubscribe_link, app.get_unsubscribe_link(&email_request));
+
+    // Act - Part 2 - One-click unsubscribe, as a mailbox provider would
+    let response = app.post_unsubscribe(unsubscribe_link).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT status FROM list_memberships")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+}
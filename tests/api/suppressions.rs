@@ -0,0 +1,301 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, default_list_id, publish_newsletter,
+    spawn_app, TestApp,
+};
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::suppression::{backfill_email_hashes, is_suppressed};
+
+async fn subscriber_email(app: &TestApp) -> String {
+    sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_suppressions() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let page = app.get_suppressions().await;
+    let addition = app
+        .post_suppression("ursula@example.com", "do_not_contact")
+        .await;
+    let removal = app.post_remove_suppression("ursula@example.com").await;
+
+    // Assert
+    assert_is_redirect_to(&page, "/login");
+    assert_is_redirect_to(&addition, "/login");
+    assert_is_redirect_to(&removal, "/login");
+}
+
+#[tokio::test]
+async fn admins_can_add_and_remove_suppressions() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Add
+    let response = app
+        .post_suppression("ursula@example.com", "do_not_contact")
+        .await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_suppressions_html().await;
+    assert!(html_page
+        .contains("<p><i>ursula@example.com has been added to the suppression list.</i></p>"));
+    assert!(html_page.contains(&format!(
+        "<tr><td>ursula@example.com</td><td>Do not contact</td><td>{}</td>",
+        app.test_user.username
+    )));
+
+    // Act - Part 3 - Add again, with a different case
+    app.post_suppression("URSULA@example.com", "hard_bounce")
+        .await;
+    let html_page = app.get_suppressions_html().await;
+    assert!(
+        html_page.contains("<p><i>URSULA@example.com is already on the suppression list.</i></p>")
+    );
+
+    // Act - Part 4 - Remove
+    let response = app.post_remove_suppression("ursula@example.com").await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+    let html_page = app.get_suppressions_html().await;
+    assert!(html_page
+        .contains("<p><i>ursula@example.com has been removed from the suppression list.</i></p>"));
+    assert!(!html_page.contains("<td>ursula@example.com</td>"));
+}
+
+#[tokio::test]
+async fn invalid_addresses_cannot_be_suppressed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app
+        .post_suppression("definitely-not-an-email", "do_not_contact")
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/suppressions");
+    let n_suppressions = sqlx::query!(r#"SELECT count(*) AS "n!" FROM suppressions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_suppressions, 0);
+}
+
+#[tokio::test]
+async fn suppressed_addresses_cannot_subscribe() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_suppression("ursula_le_guin@gmail.com", "spam_complaint")
+        .await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=Ursula_Le_Guin%40gmail.com".into())
+        .await;
+
+    // Assert
+    // Suppressed addresses get the same answer as everybody else.
+    assert_eq!(response.status().as_u16(), 200);
+    let n_subscribers = sqlx::query!(r#"SELECT count(*) AS "n!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_subscribers, 0);
+}
+
+#[tokio::test]
+async fn newsletters_are_not_delivered_to_suppressed_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let email = subscriber_email(&app).await;
+    app.post_suppression(&email, "hard_bounce").await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let n_deliveries = sqlx::query!(r#"SELECT count(*) AS "n!" FROM issue_deliveries"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_deliveries, 0);
+}
+
+#[tokio::test]
+async fn queued_deliveries_are_skipped_once_the_subscriber_is_suppressed() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let email = subscriber_email(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    publish_newsletter(&app).await;
+    app.post_suppression(&email, "do_not_contact").await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let delivery = sqlx::query!("SELECT status FROM issue_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(delivery.status, "skipped");
+}
+
+#[tokio::test]
+async fn suppressed_addresses_are_skipped_by_imports() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_suppression("ursula_le_guin@gmail.com", "do_not_contact")
+        .await;
+    let list_id = default_list_id(&app).await;
+
+    // Act
+    let response = app
+        .post_subscriber_import(
+            "name,email\nUrsula Le Guin,ursula_le_guin@gmail.com\n",
+            &[
+                ("list_id", list_id.to_string()),
+                ("mode", "confirmed".into()),
+                ("consent_source", "Signed up at the 2026 conference".into()),
+            ],
+        )
+        .await;
+    app.import_all_pending_rows().await;
+
+    // Assert
+    let location = response
+        .headers()
+        .get("Location")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    let html_page = app.get_subscriber_import_html(location).await;
+    assert!(html_page.contains(
+        "<td>ursula_le_guin@gmail.com</td><td>skipped</td><td>The address is on the suppression list.</td>"
+    ));
+    let n_subscribers = sqlx::query!(r#"SELECT count(*) AS "n!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_subscribers, 0);
+}
+
+#[tokio::test]
+async fn suppressions_match_addresses_that_only_differ_by_non_ascii_case() {
+    // Arrange
+    let app = spawn_app().await;
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula%40B%C3%9CCHER.example".into())
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    reqwest::get(app.get_confirmation_links(email_request).html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    drop(_mock_guard);
+    app.test_user.login(&app).await;
+    app.post_suppression("ursula@b\u{fc}cher.example", "hard_bounce")
+        .await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    // The subscriber is left out of the audience, not just skipped on delivery.
+    let n_deliveries = sqlx::query!(r#"SELECT count(*) AS "n!" FROM issue_deliveries"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_deliveries, 0);
+}
+
+#[tokio::test]
+async fn hashes_computed_before_the_application_did_are_backfilled() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    sqlx::query!("UPDATE subscriptions SET email_hash = NULL")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    // As the migration that introduced the hashes computed them.
+    sqlx::query!(
+        r#"
+        INSERT INTO suppressions (email, email_hash, reason, source, created_at)
+        VALUES ($1, encode(sha256(convert_to(lower($1), 'UTF8')), 'hex'), 'hard_bounce', 'postmark', now())
+        "#,
+        " URSULA@B\u{dc}CHER.example"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    backfill_email_hashes(&app.db_pool).await.unwrap();
+
+    // Assert
+    assert!(is_suppressed(&app.db_pool, "ursula@b\u{fc}cher.example")
+        .await
+        .unwrap());
+    let n_without_hash =
+        sqlx::query!(r#"SELECT count(*) AS "n!" FROM subscriptions WHERE email_hash IS NULL"#)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap()
+            .n;
+    assert_eq!(n_without_hash, 0);
+}
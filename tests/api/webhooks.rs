@@ -0,0 +1,337 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+
+async fn subscriber_email(app: &TestApp) -> String {
+    sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email
+}
+
+/// A bounce as Postmark sends it, trimmed of the fields we don't read.
+fn bounce(bounce_type: &str, email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "RecordType": "Bounce",
+        "ID": 4323372036854775807u64,
+        "Type": bounce_type,
+        "TypeCode": 1,
+        "Name": "Hard bounce",
+        "MessageID": "883953f4-6105-42a2-a16a-77a8eac79483",
+        "MessageStream": "outbound",
+        "Description": "The server was unable to deliver your message (ex: unknown user, mailbox not found).",
+        "Details": "smtp;550 5.1.1 The email account that you tried to reach does not exist.",
+        "Email": email,
+        "From": "sender@example.com",
+        "BouncedAt": "2026-10-18T16:33:54.9070259Z",
+        "Inactive": true,
+        "CanActivate": true,
+        "Subject": "Newsletter title"
+    })
+}
+
+fn spam_complaint(email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "RecordType": "SpamComplaint",
+        "ID": 42,
+        "Type": "SpamComplaint",
+        "TypeCode": 512,
+        "Name": "Spam complaint",
+        "MessageID": "00000000-0000-0000-0000-000000000000",
+        "MessageStream": "outbound",
+        "Email": email,
+        "From": "sender@example.com",
+        "BouncedAt": "2026-10-18T16:33:54.9070259Z",
+        "Inactive": true,
+        "CanActivate": false,
+        "Subject": "Newsletter title"
+    })
+}
+
+fn delivery(email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "RecordType": "Delivery",
+        "ServerID": 23,
+        "MessageStream": "outbound",
+        "MessageID": "00000000-0000-0000-0000-000000000000",
+        "Recipient": email,
+        "Tag": "",
+        "DeliveredAt": "2026-10-18T16:33:54.9070259Z",
+        "Details": "Test delivery webhook details",
+        "Metadata": {}
+    })
+}
+
+async fn statuses(app: &TestApp) -> (String, Vec<String>) {
+    let subscriber = sqlx::query!("SELECT id, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let memberships = sqlx::query!(
+        "SELECT status FROM list_memberships WHERE subscriber_id = $1",
+        subscriber.id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    (
+        subscriber.status,
+        memberships.into_iter().map(|m| m.status).collect(),
+    )
+}
+
+async fn suppression_reason(app: &TestApp, email: &str) -> Option<(String, String)> {
+    sqlx::query!(
+        "SELECT reason, source FROM suppressions WHERE lower(email) = lower($1)",
+        email
+    )
+    .fetch_optional(&app.db_pool)
+    .await
+    .unwrap()
+    .map(|s| (s.reason, s.source))
+}
+
+#[tokio::test]
+async fn requests_without_credentials_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/webhooks/email-events", &app.address))
+        .json(&bounce("HardBounce", "ursula@example.com"))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        r#"Basic realm="webhooks""#,
+        response.headers()["WWW-Authenticate"]
+    );
+}
+
+#[tokio::test]
+async fn requests_with_a_wrong_password_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/webhooks/email-events", &app.address))
+        .basic_auth(&app.webhooks.username, Some("not-the-password"))
+        .json(&bounce("HardBounce", "ursula@example.com"))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        r#"Basic realm="webhooks""#,
+        response.headers()["WWW-Authenticate"]
+    );
+    let n_events = sqlx::query!(r#"SELECT count(*) AS "n!" FROM email_events"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_events, 0);
+}
+
+#[tokio::test]
+async fn hard_bounces_unsubscribe_and_suppress_the_address() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let email = subscriber_email(&app).await;
+
+    // Act
+    let response = app
+        .post_email_event(&bounce("HardBounce", &email.to_uppercase()))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        statuses(&app).await,
+        ("bounced".into(), vec!["unsubscribed".into()])
+    );
+    assert_eq!(
+        suppression_reason(&app, &email).await,
+        Some(("hard_bounce".into(), "postmark".into()))
+    );
+    let event = sqlx::query!(
+        r#"
+        SELECT kind, provider_type, provider_message_id, occurred_at, payload ->> 'Details' AS details
+        FROM email_events
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(event.kind, "bounce");
+    assert_eq!(event.provider_type.as_deref(), Some("HardBounce"));
+    assert_eq!(
+        event.provider_message_id.as_deref(),
+        Some("883953f4-6105-42a2-a16a-77a8eac79483")
+    );
+    assert!(event.occurred_at.is_some());
+    assert_eq!(
+        event.details.as_deref(),
+        Some("smtp;550 5.1.1 The email account that you tried to reach does not exist.")
+    );
+}
+
+#[tokio::test]
+async fn soft_bounces_are_recorded_without_suppressing_the_address() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let email = subscriber_email(&app).await;
+
+    // Act
+    let response = app.post_email_event(&bounce("SoftBounce", &email)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        statuses(&app).await,
+        ("confirmed".into(), vec!["confirmed".into()])
+    );
+    assert_eq!(suppression_reason(&app, &email).await, None);
+    let event = sqlx::query!("SELECT kind, provider_type FROM email_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(event.kind, "bounce");
+    assert_eq!(event.provider_type.as_deref(), Some("SoftBounce"));
+}
+
+#[tokio::test]
+async fn spam_complaints_unsubscribe_and_suppress_the_address() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let email = subscriber_email(&app).await;
+
+    // Act
+    let response = app.post_email_event(&spam_complaint(&email)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        statuses(&app).await,
+        ("complained".into(), vec!["unsubscribed".into()])
+    );
+    assert_eq!(
+        suppression_reason(&app, &email).await,
+        Some(("spam_complaint".into(), "postmark".into()))
+    );
+}
+
+#[tokio::test]
+async fn bounces_of_unknown_addresses_are_still_suppressed() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_email_event(&bounce("HardBounce", "ursula@example.com"))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        suppression_reason(&app, "ursula@example.com").await,
+        Some(("hard_bounce".into(), "postmark".into()))
+    );
+}
+
+#[tokio::test]
+async fn deliveries_are_recorded() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let email = subscriber_email(&app).await;
+
+    // Act
+    let response = app.post_email_event(&delivery(&email)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let event = sqlx::query!("SELECT kind, email, provider_message_id FROM email_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(event.kind, "delivery");
+    assert_eq!(event.email, email);
+    assert_eq!(
+        event.provider_message_id.as_deref(),
+        Some("00000000-0000-0000-0000-000000000000")
+    );
+    assert_eq!(
+        statuses(&app).await,
+        ("confirmed".into(), vec!["confirmed".into()])
+    );
+}
+
+#[tokio::test]
+async fn untracked_record_types_are_acknowledged_and_dropped() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_email_event(&serde_json::json!({
+            "RecordType": "Open",
+            "MessageID": "00000000-0000-0000-0000-000000000000",
+            "Recipient": "ursula@example.com",
+            "FirstOpen": true
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let n_events = sqlx::query!(r#"SELECT count(*) AS "n!" FROM email_events"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_events, 0);
+}
+
+#[tokio::test]
+async fn invalid_payloads_are_rejected_with_a_400() {
+    // Arrange
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (
+            serde_json::json!({"Email": "ursula@example.com"}),
+            "no record type",
+        ),
+        (
+            serde_json::json!({"RecordType": "Bounce", "Type": "HardBounce"}),
+            "a bounce without an address",
+        ),
+        (
+            serde_json::json!({"RecordType": "Delivery", "MessageID": "x"}),
+            "a delivery without a recipient",
+        ),
+    ];
+
+    for (payload, description) in test_cases {
+        // Act
+        let response = app.post_email_event(&payload).await;
+
+        // Assert
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not fail with 400 Bad Request when the payload was {}.",
+            description
+        );
+    }
+}